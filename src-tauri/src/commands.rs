@@ -1,64 +1,101 @@
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 
 use encoding_rs::{EUC_JP, SHIFT_JIS};
+use ignore::overrides::Override;
+use regex::RegexBuilder;
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
 use tokio::fs;
 
-use crate::editor::search::QueryReplaceStatus;
-use crate::editor::state::{EditorSnapshot, EditorState};
+use crate::editor::command::{self, CommandSpec};
+use crate::editor::extended_command::ExtendedCommandCandidate;
+use crate::editor::file_settings::{file_local_variables, Charset, FileSettings};
+use crate::editor::grep::GREP_BUFFER_NAME;
+use crate::editor::highlight::LineSpans;
+use crate::editor::ibuffer::{BufferInfo, BufferListFilter};
+use crate::editor::keymap::KeyBinding;
+use crate::editor::kmacro::{self, MacroStep};
+use crate::editor::save_some::SaveSomeStatus;
+use crate::editor::search::{
+    contains_query, folds_case, ProjectReplaceSession, ProjectReplaceStatus, QueryReplaceStatus,
+};
+use crate::editor::settings::BackupPolicy;
+use crate::editor::state::{BufferId, BufferState, EditorSnapshot, EditorState};
+use crate::completion;
+use crate::config::{self, describe_settings, resolve_config_dir, SettingView};
+use crate::config_watch::ConfigWatch;
+use crate::dired;
+use crate::editorconfig;
+use crate::editor::dired::DiredMark;
+use crate::grep::{file_filter, grep_directory, GrepState};
+use crate::recentf;
+use crate::theme::{self, ThemeConfig, ThemeInfo};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct InsertPayload {
-    text: String,
+pub struct QueryReplacePayload {
+    query: String,
+    replace_with: String,
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct RegionPayload {
-    start: usize,
-    end: usize,
+pub struct QueryReplaceStepPayload {
+    action: String,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct SearchPayload {
-    query: String,
+pub struct GrepPayload {
+    directory: String,
+    pattern: String,
+    ignore_case: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct CursorPayload {
-    cursor: usize,
+pub struct RgrepPayload {
+    #[serde(flatten)]
+    grep: GrepPayload,
+    /// Whitespace-separated file name wildcards, e.g. `*.rs *.toml`.
+    files: String,
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(untagged)]
-pub enum CommandPayload {
-    Insert(InsertPayload),
-    Region(RegionPayload),
-    Search(SearchPayload),
-    Cursor(CursorPayload),
+#[serde(rename_all = "camelCase")]
+pub struct ProjectReplacePayload {
+    directory: String,
+    query: String,
+    replace_with: String,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct QueryReplacePayload {
-    query: String,
-    replace_with: String,
+pub struct DiredPayload {
+    target: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct QueryReplaceStepPayload {
-    action: String,
+#[serde(rename_all = "camelCase")]
+pub struct IbufferPayload {
+    target: Option<String>,
+    force: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KmacroPayload {
+    /// Times to run the macro; 0 repeats until an error or until nothing changes.
+    count: Option<usize>,
+    name: Option<String>,
+    counter: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct KillBufferPayload {
-    name: Option<String>,
+    id: Option<BufferId>,
     force: Option<bool>,
 }
 
@@ -69,134 +106,215 @@ pub struct QueryReplaceResponse {
     pub status: QueryReplaceStatus,
 }
 
-#[derive(Debug, Deserialize, Default)]
-struct RawConfig {
-    theme: Option<RawThemeConfig>,
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectReplaceResponse {
+    pub snapshot: EditorSnapshot,
+    pub status: ProjectReplaceStatus,
 }
 
-#[derive(Debug, Deserialize, Default)]
-struct RawThemeConfig {
-    background_color: Option<String>,
-    text_color: Option<String>,
-    cursor_color: Option<String>,
-    selection_bg: Option<String>,
-    current_line_bg: Option<String>,
-    current_line_highlight: Option<bool>,
-    statusbar_bg: Option<String>,
-    minibuffer_bg: Option<String>,
-    background_image: Option<String>,
-    font_family: Option<String>,
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveSomeResponse {
+    pub snapshot: EditorSnapshot,
+    pub status: SaveSomeStatus,
 }
 
-#[derive(Debug, Serialize, Default)]
-#[serde(rename_all = "camelCase")]
-pub struct ThemeConfig {
-    pub background_color: Option<String>,
-    pub text_color: Option<String>,
-    pub cursor_color: Option<String>,
-    pub selection_bg: Option<String>,
-    pub current_line_bg: Option<String>,
-    pub current_line_highlight: Option<bool>,
-    pub statusbar_bg: Option<String>,
-    pub minibuffer_bg: Option<String>,
-    pub background_image: Option<String>,
-    pub font_family: Option<String>,
-}
-
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Clone, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct AppConfigResponse {
     pub theme: ThemeConfig,
-    pub source_path: Option<String>,
+    /// Bindings from the `keybindings:` section, applied over the built-in keymaps.
+    pub keybindings: Vec<KeyBinding>,
+    /// Config files that were applied, in order: system, user, project.
+    pub source_paths: Vec<String>,
+}
+
+pub const CONFIG_CHANGED_EVENT: &str = "config-changed";
+
+/// Sent when a watched config file changes. On an error the previous
+/// config stays in effect and `config` is `None`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigChangedEvent {
+    pub config: Option<AppConfigResponse>,
+    pub theme_changed: bool,
+    pub keybindings_changed: bool,
+    /// Settings whose effective value changed, e.g. `editor.fill_column`.
+    pub changed_settings: Vec<String>,
+    pub error: Option<String>,
 }
 
 #[derive(Debug)]
-struct DecodedContent {
-    text: String,
-    encoding: String,
-    line_ending: String,
+pub(crate) struct DecodedContent {
+    pub(crate) text: String,
+    pub(crate) encoding: String,
+    pub(crate) line_ending: String,
 }
 
 #[tauri::command]
 pub fn initialize_editor(state: State<'_, Mutex<EditorState>>) -> EditorSnapshot {
-    let editor = state.lock().expect("state lock poisoned");
+    let mut editor = state.lock().expect("state lock poisoned");
     editor.snapshot()
 }
 
 #[tauri::command]
 pub fn editor_command(
     command: String,
-    payload: Option<CommandPayload>,
+    payload: Option<serde_json::Value>,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<EditorSnapshot, String> {
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    run_and_record(&mut editor, &command, payload, EditorState::run_command)?;
+    Ok(editor.snapshot())
+}
+
+/// Runs a command through `run` and, while a keyboard macro is being
+/// defined, records it as a step.
+fn run_and_record(
+    editor: &mut EditorState,
+    command: &str,
+    payload: Option<serde_json::Value>,
+    run: fn(&mut EditorState, &str, Option<serde_json::Value>) -> Result<(), String>,
+) -> Result<(), String> {
+    let recorded = editor.defining_kbd_macro().then(|| payload.clone());
+    let cursor_before = editor.current().cursor;
+    run(editor, command, payload)?;
+
+    // The frontend syncs point before most commands; only real moves belong in a macro.
+    let moved = editor.current().cursor != cursor_before;
+    if let Some(payload) = recorded {
+        if command != "noop" && (command != "set_cursor" || moved) {
+            editor.record_macro_step(command, payload);
+        }
+    }
+    Ok(())
+}
+
+/// `M-x` completion: registered commands matching `query` with their
+/// documentation and key bindings, recently used first.
+#[tauri::command]
+pub fn extended_command_candidates(
+    query: String,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<Vec<ExtendedCommandCandidate>, String> {
+    let editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    Ok(editor.extended_command_candidates(&query))
+}
+
+/// Runs a command chosen with `M-x` through the same dispatcher as `editor_command`.
+#[tauri::command]
+pub fn execute_extended_command(
+    command: String,
+    payload: Option<serde_json::Value>,
     state: State<'_, Mutex<EditorState>>,
 ) -> Result<EditorSnapshot, String> {
     let mut editor = state
         .lock()
         .map_err(|_| "state lock poisoned".to_string())?;
+    run_and_record(&mut editor, &command, payload, EditorState::execute_extended_command)?;
+    Ok(editor.snapshot())
+}
+
+/// Documentation and argument schema of one editor command.
+#[tauri::command]
+pub fn describe_command(name: String) -> Result<CommandSpec, String> {
+    command::lookup(&name).copied()
+}
 
+/// Every editor command `editor_command` accepts, for `M-x` completion.
+#[tauri::command]
+pub fn list_commands() -> Vec<CommandSpec> {
+    command::commands().to_vec()
+}
+
+/// Most runs a count of 0 replays before giving up on reaching a failure or
+/// a run that changes nothing; the editor stays locked while it replays.
+const MAX_KBD_MACRO_RUNS: usize = 10_000;
+
+/// Replays `steps` `count` times, stopping at the first failing command.
+fn execute_kbd_macro(
+    editor: &mut EditorState,
+    steps: &[MacroStep],
+    count: usize,
+) -> Result<(), String> {
+    let mut iteration = 0;
+    while count == 0 || iteration < count {
+        let before = (editor.current().id, editor.current().cursor, editor.current().buffer.text());
+        for step in steps {
+            editor
+                .run_command(&step.command, step.payload.clone())
+                .map_err(|err| format!("Keyboard macro stopped after {} runs: {}", iteration, err))?;
+        }
+        iteration += 1;
+        let after = (editor.current().id, editor.current().cursor, editor.current().buffer.text());
+        if count == 0 && after == before {
+            break;
+        }
+        if count == 0 && iteration == MAX_KBD_MACRO_RUNS {
+            return Err(format!("Keyboard macro stopped after {} runs", iteration));
+        }
+    }
+    Ok(())
+}
+
+/// Keyboard macros: `start` (C-x (), `end` (C-x )), `call` (C-x e) with an
+/// optional `count` and `name`, `name` to name and save the last macro, and
+/// `set_counter`.
+#[tauri::command]
+pub fn kmacro_command(
+    command: String,
+    payload: Option<KmacroPayload>,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<EditorSnapshot, String> {
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    let payload = payload.unwrap_or_default();
     match command.as_str() {
-        "noop" => {}
-        "keyboard_quit" => {
-            editor.current_mut().query_replace_session = None;
-            editor.current_mut().set_status_message(Some("Quit".to_string()));
-        }
-        "move_to_line_start" => editor.current_mut().move_to_line_start(),
-        "move_to_line_end" => editor.current_mut().move_to_line_end(),
-        "move_forward" => editor.current_mut().move_forward(),
-        "move_backward" => editor.current_mut().move_backward(),
-        "move_next_line" => editor.current_mut().move_next_line(),
-        "move_previous_line" => editor.current_mut().move_previous_line(),
-        "move_forward_word" => editor.current_mut().move_forward_word(),
-        "move_backward_word" => editor.current_mut().move_backward_word(),
-        "move_to_buffer_start" => editor.current_mut().move_to_buffer_start(),
-        "move_to_buffer_end" => editor.current_mut().move_to_buffer_end(),
-        "delete_char" => editor.current_mut().delete_char(),
-        "delete_backward_char" => editor.current_mut().delete_backward_char(),
-        "kill_line" => {
-            let (buf, kr) = editor.current_and_kill_ring();
-            buf.kill_line(kr);
-        }
-        "yank" => {
-            let kr = editor.kill_ring.clone();
-            editor.current_mut().yank(&kr);
-        }
-        "undo" => editor.current_mut().undo(),
-        "redo" => editor.current_mut().redo(),
-        "kill_region" => match payload {
-            Some(CommandPayload::Region(region)) => {
-                let (buf, kr) = editor.current_and_kill_ring();
-                buf.kill_region(region.start, region.end, kr);
-            }
-            _ => return Err("kill_region requires region payload".to_string()),
-        },
-        "copy_region" => match payload {
-            Some(CommandPayload::Region(region)) => {
-                let (buf, kr) = editor.current_and_kill_ring();
-                buf.copy_region(region.start, region.end, kr);
+        "start" => editor.start_kbd_macro()?,
+        "end" => editor.end_kbd_macro()?,
+        "call" => {
+            // Like C-x e, calling while defining finishes the definition first.
+            if editor.defining_kbd_macro() {
+                editor.end_kbd_macro()?;
             }
-            _ => return Err("copy_region requires region payload".to_string()),
-        },
-        "isearch_forward" => match payload {
-            Some(CommandPayload::Search(search)) => editor.current_mut().isearch_forward(&search.query)?,
-            _ => return Err("isearch_forward requires search payload".to_string()),
-        },
-        "isearch_backward" => match payload {
-            Some(CommandPayload::Search(search)) => editor.current_mut().isearch_backward(&search.query)?,
-            _ => return Err("isearch_backward requires search payload".to_string()),
-        },
-        "set_cursor" => match payload {
-            Some(CommandPayload::Cursor(cursor)) => editor.current_mut().set_cursor(cursor.cursor),
-            _ => return Err("set_cursor requires cursor payload".to_string()),
-        },
-        "insert_text" => match payload {
-            Some(CommandPayload::Insert(insert)) => editor.current_mut().insert_text(&insert.text),
-            _ => return Err("insert_text requires payload".to_string()),
-        },
-        _ => return Err(format!("unknown command: {command}")),
+            let steps = editor.kbd_macro(payload.name.as_deref())?;
+            execute_kbd_macro(&mut editor, &steps, payload.count.unwrap_or(1))?;
+        }
+        "name" => {
+            let name = payload
+                .name
+                .ok_or_else(|| "name requires a macro name".to_string())?;
+            editor.name_last_kbd_macro(&name)?;
+            if let Some(path) = kmacro_path() {
+                kmacro::save(&path, &editor.kmacro.named)?;
+            }
+        }
+        "set_counter" => {
+            let value = payload
+                .counter
+                .ok_or_else(|| "set_counter requires a counter value".to_string())?;
+            editor.kmacro_set_counter(value);
+        }
+        _ => return Err(format!("Unknown keyboard macro command: {}", command)),
     }
-
     Ok(editor.snapshot())
 }
 
+/// Names of the saved keyboard macros.
+#[tauri::command]
+pub fn kmacro_names(state: State<'_, Mutex<EditorState>>) -> Result<Vec<String>, String> {
+    let editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    Ok(editor.kmacro.named.keys().cloned().collect())
+}
+
 #[tauri::command]
 pub fn start_query_replace(
     payload: QueryReplacePayload,
@@ -205,7 +323,10 @@ pub fn start_query_replace(
     let mut editor = state
         .lock()
         .map_err(|_| "state lock poisoned".to_string())?;
-    let status = editor.current_mut().start_query_replace(payload.query, payload.replace_with)?;
+    let case_fold = editor.settings.case_fold;
+    let status = editor
+        .current_mut()
+        .start_query_replace(payload.query, payload.replace_with, case_fold)?;
     let snapshot = editor.snapshot();
     Ok(QueryReplaceResponse { snapshot, status })
 }
@@ -223,32 +344,259 @@ pub fn query_replace_step(
     Ok(QueryReplaceResponse { snapshot, status })
 }
 
+#[tauri::command]
+pub async fn start_project_query_replace(
+    payload: ProjectReplacePayload,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<ProjectReplaceResponse, String> {
+    if payload.query.is_empty() {
+        return Err("query must not be empty".to_string());
+    }
+    let root = PathBuf::from(&payload.directory);
+    if !fs::metadata(&root).await.map(|m| m.is_dir()).unwrap_or(false) {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+
+    let case_fold = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?
+        .settings
+        .case_fold;
+    let regex = RegexBuilder::new(&regex::escape(&payload.query))
+        .case_insensitive(folds_case(case_fold, &payload.query))
+        .build()
+        .map_err(|err| format!("invalid query: {err}"))?;
+    let query = payload.query.clone();
+    let scanned = tauri::async_runtime::spawn_blocking(move || {
+        let files = Override::empty();
+        grep_directory(&root, &query, &regex, &files, &AtomicBool::new(false), |_| {})
+    })
+    .await
+    .map_err(|err| format!("scan failed: {err}"))?;
+
+    let mut files: Vec<PathBuf> = scanned.matches.into_iter().map(|m| m.path).collect();
+    files.dedup();
+
+    {
+        let mut editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        editor.project_replace = Some(ProjectReplaceSession::new(
+            payload.query,
+            payload.replace_with,
+            files,
+        ));
+    }
+    advance_project_replace(&state).await
+}
+
+#[tauri::command]
+pub async fn project_query_replace_step(
+    payload: QueryReplaceStepPayload,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<ProjectReplaceResponse, String> {
+    {
+        let mut editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        let current = editor
+            .project_replace
+            .as_ref()
+            .ok_or_else(|| "project query replace is not active".to_string())?
+            .current
+            .clone();
+        let index = current.and_then(|path| editor.find_buffer_by_path(&path));
+        let replaced_count = match index {
+            Some(index) => {
+                editor.switch_to_index(index);
+                let status = editor.current_mut().query_replace_step(&payload.action)?;
+                if !status.done {
+                    return Ok(project_replace_response(&mut editor, status));
+                }
+                status.replaced_count
+            }
+            // The buffer went away; move on to the next file.
+            None => 0,
+        };
+
+        let session = editor
+            .project_replace
+            .as_mut()
+            .ok_or_else(|| "project query replace is not active".to_string())?;
+        session.finish_current(replaced_count);
+        if payload.action.eq_ignore_ascii_case("q") {
+            session.pending.clear();
+            return Ok(finish_project_replace(&mut editor, true));
+        }
+    }
+    advance_project_replace(&state).await
+}
+
+/// Opens the next pending file that still contains the query and starts a
+/// query replace session in it.
+async fn advance_project_replace(state: &Mutex<EditorState>) -> Result<ProjectReplaceResponse, String> {
+    loop {
+        let (path, query, replace_with, case_fold) = {
+            let mut editor = state
+                .lock()
+                .map_err(|_| "state lock poisoned".to_string())?;
+            let case_fold = editor.settings.case_fold;
+            let session = editor
+                .project_replace
+                .as_mut()
+                .ok_or_else(|| "project query replace is not active".to_string())?;
+            let Some(path) = session.pending.pop_front() else {
+                return Ok(finish_project_replace(&mut editor, false));
+            };
+            (path, session.query.clone(), session.replace_with.clone(), case_fold)
+        };
+        let fold = folds_case(case_fold, &query);
+
+        let open_index = {
+            let editor = state
+                .lock()
+                .map_err(|_| "state lock poisoned".to_string())?;
+            editor.find_buffer_by_path(&path)
+        };
+
+        let index = match open_index {
+            Some(index) => {
+                let editor = state
+                    .lock()
+                    .map_err(|_| "state lock poisoned".to_string())?;
+                if !contains_query(&editor.buffers[index].buffer.as_str(), &query, fold) {
+                    continue;
+                }
+                index
+            }
+            None => {
+                let Ok(bytes) = fs::read(&path).await else {
+                    continue;
+                };
+                let (decoded, settings) = decode_visited_file(&bytes, &path);
+                if !contains_query(&decoded.text, &query, fold) {
+                    continue;
+                }
+                let mut editor = state
+                    .lock()
+                    .map_err(|_| "state lock poisoned".to_string())?;
+                let mut buf = editor.new_buffer();
+                buf.load_content(
+                    decoded.text,
+                    decoded.encoding,
+                    decoded.line_ending,
+                    path.clone(),
+                );
+                buf.apply_file_settings(settings.or(editor.settings.file_defaults(false)));
+                editor.buffers.push(buf);
+                editor.buffers.len() - 1
+            }
+        };
+
+        let mut editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        editor.switch_to_index(index);
+        // Each file is searched from point-min; its point comes back once it is done.
+        let buf = editor.current_mut();
+        let point = buf.cursor;
+        let status = buf.start_query_replace(query, replace_with, case_fold)?;
+        if let Some(session) = buf.query_replace_session.as_mut() {
+            session.return_point = Some(point);
+        }
+        if let Some(session) = editor.project_replace.as_mut() {
+            session.current = Some(path);
+        }
+        if status.done {
+            if let Some(session) = editor.project_replace.as_mut() {
+                session.finish_current(status.replaced_count);
+            }
+            continue;
+        }
+        return Ok(project_replace_response(&mut editor, status));
+    }
+}
+
+fn project_replace_response(
+    editor: &mut EditorState,
+    status: QueryReplaceStatus,
+) -> ProjectReplaceResponse {
+    let session = editor.project_replace.as_ref();
+    let file = session
+        .and_then(|s| s.current.as_ref())
+        .map(|path| path.to_string_lossy().to_string());
+    let message = match &file {
+        Some(file) => format!("{}: {}", file, status.message),
+        None => status.message.clone(),
+    };
+    let total_replaced = session.map_or(0, |s| s.total_replaced()) + status.replaced_count;
+    let file_counts = session.map(|s| s.file_counts.clone()).unwrap_or_default();
+    ProjectReplaceResponse {
+        snapshot: editor.snapshot(),
+        status: ProjectReplaceStatus {
+            done: false,
+            file,
+            replaced_count: status.replaced_count,
+            total_replaced,
+            next_line: status.next_line,
+            next_col: status.next_col,
+            message,
+            file_counts,
+            modified_buffers: Vec::new(),
+        },
+    }
+}
+
+fn finish_project_replace(editor: &mut EditorState, cancelled: bool) -> ProjectReplaceResponse {
+    let session = editor.project_replace.take();
+    let message = session
+        .as_ref()
+        .map_or_else(|| "No active query replace".to_string(), |s| s.summary(cancelled));
+    editor
+        .current_mut()
+        .set_status_message(Some(message.clone()));
+    ProjectReplaceResponse {
+        snapshot: editor.snapshot(),
+        status: ProjectReplaceStatus {
+            done: true,
+            file: None,
+            replaced_count: 0,
+            total_replaced: session.as_ref().map_or(0, |s| s.total_replaced()),
+            next_line: None,
+            next_col: None,
+            message,
+            file_counts: session.map(|s| s.file_counts).unwrap_or_default(),
+            modified_buffers: editor.modified_file_buffer_ids(),
+        },
+    }
+}
+
 #[tauri::command]
 pub async fn open_file(
     path: String,
     state: State<'_, Mutex<EditorState>>,
 ) -> Result<EditorSnapshot, String> {
-    let path_buf = PathBuf::from(&path);
+    open_path(PathBuf::from(&path), &state).await
+}
+
+async fn open_path(path_buf: PathBuf, state: &Mutex<EditorState>) -> Result<EditorSnapshot, String> {
     let metadata = fs::metadata(&path_buf).await.ok();
     if metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false) {
-        let listing = build_directory_listing(&path_buf).await?;
+        let listing = dired::read_listing(&path_buf)?;
         let mut editor = state
             .lock()
             .map_err(|_| "state lock poisoned".to_string())?;
-        use crate::editor::state::BufferState;
-        let mut buf = BufferState::new();
-        buf.load_content(
-            listing,
-            "UTF-8".to_string(),
-            "CRLF".to_string(),
-            path_buf.clone(),
-        );
-        buf.file_path = None;
-        buf.mark_saved();
-        buf.set_status_message(Some(format!("Directory: {}", path_buf.display())));
-        editor.buffers.push(buf);
-        let new_idx = editor.buffers.len() - 1;
-        editor.switch_to_index(new_idx);
+        let index = match editor.find_dired_buffer(&path_buf) {
+            Some(index) => index,
+            None => {
+                editor.buffers.push(BufferState::new());
+                editor.buffers.len() - 1
+            }
+        };
+        editor.buffers[index].load_dired(listing);
+        editor.buffers[index]
+            .set_status_message(Some(format!("Directory: {}", path_buf.display())));
+        editor.switch_to_index(index);
         return Ok(editor.snapshot());
     }
 
@@ -270,13 +618,18 @@ pub async fn open_file(
     }
 
     enum OpenResult {
-        Existing(DecodedContent),
-        NewFile,
+        Existing(DecodedContent, FileSettings),
+        NewFile(FileSettings),
     }
 
     let open_result = match fs::read(&path_buf).await {
-        Ok(bytes) => OpenResult::Existing(decode_content(&bytes)),
-        Err(err) if err.kind() == std::io::ErrorKind::NotFound => OpenResult::NewFile,
+        Ok(bytes) => {
+            let (decoded, settings) = decode_visited_file(&bytes, &path_buf);
+            OpenResult::Existing(decoded, settings)
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            OpenResult::NewFile(editorconfig::resolve(&path_buf))
+        }
         Err(err) => return Err(format!("failed to read file: {err}")),
     };
 
@@ -284,25 +637,26 @@ pub async fn open_file(
         .lock()
         .map_err(|_| "state lock poisoned".to_string())?;
 
-    use crate::editor::state::BufferState;
-    let mut buf = BufferState::new();
+    let mut buf = editor.new_buffer();
     match open_result {
-        OpenResult::Existing(decoded) => {
+        OpenResult::Existing(decoded, settings) => {
             buf.load_content(
                 decoded.text,
                 decoded.encoding,
                 decoded.line_ending,
                 path_buf.clone(),
             );
+            buf.apply_file_settings(settings.or(editor.settings.file_defaults(false)));
             buf.set_status_message(Some(format!("Opened {}", path_buf.display())));
         }
-        OpenResult::NewFile => {
+        OpenResult::NewFile(settings) => {
             buf.load_content(
                 String::new(),
                 "UTF-8".to_string(),
                 "CRLF".to_string(),
                 path_buf.clone(),
             );
+            buf.apply_file_settings(settings.or(editor.settings.file_defaults(true)));
             buf.set_status_message(Some(format!("New file: {}", path_buf.display())));
         }
     }
@@ -310,70 +664,671 @@ pub async fn open_file(
     editor.buffers.push(buf);
     let new_idx = editor.buffers.len() - 1;
     editor.switch_to_index(new_idx);
+    editor.record_recent_file(&path_buf);
+    persist_recent_files(&mut editor);
     Ok(editor.snapshot())
 }
 
-async fn build_directory_listing(path: &Path) -> Result<String, String> {
-    let mut entries = fs::read_dir(path)
-        .await
-        .map_err(|err| format!("failed to read directory {}: {err}", path.display()))?;
-    let mut names: Vec<(String, bool)> = Vec::new();
-    while let Some(entry) = entries
-        .next_entry()
+#[tauri::command]
+pub async fn grep(
+    payload: GrepPayload,
+    app: AppHandle,
+    state: State<'_, Mutex<EditorState>>,
+    grep_state: State<'_, GrepState>,
+) -> Result<EditorSnapshot, String> {
+    run_grep(payload, None, app, &state, &grep_state).await
+}
+
+/// `grep` limited to files whose names match `files`, like Emacs' `rgrep`.
+#[tauri::command]
+pub async fn rgrep(
+    payload: RgrepPayload,
+    app: AppHandle,
+    state: State<'_, Mutex<EditorState>>,
+    grep_state: State<'_, GrepState>,
+) -> Result<EditorSnapshot, String> {
+    run_grep(payload.grep, Some(&payload.files), app, &state, &grep_state).await
+}
+
+async fn run_grep(
+    payload: GrepPayload,
+    files: Option<&str>,
+    app: AppHandle,
+    state: &Mutex<EditorState>,
+    grep_state: &GrepState,
+) -> Result<EditorSnapshot, String> {
+    let root = PathBuf::from(&payload.directory);
+    if !fs::metadata(&root).await.map(|m| m.is_dir()).unwrap_or(false) {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+    let case_fold = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?
+        .settings
+        .case_fold;
+    let regex = RegexBuilder::new(&payload.pattern)
+        .case_insensitive(
+            payload
+                .ignore_case
+                .unwrap_or_else(|| folds_case(case_fold, &payload.pattern)),
+        )
+        .build()
+        .map_err(|err| format!("invalid grep pattern: {err}"))?;
+    let files = file_filter(&root, files)?;
+
+    let token = grep_state.begin();
+    let cancel = token.clone();
+    let pattern = payload.pattern.clone();
+    let results = tauri::async_runtime::spawn_blocking(move || {
+        grep_directory(&root, &pattern, &regex, &files, &cancel, |progress| {
+            let _ = app.emit("grep-progress", progress.clone());
+        })
+    })
+    .await
+    .map_err(|err| format!("grep failed: {err}"));
+    grep_state.finish(&token);
+    let results = results?;
+    // C-g or a newer grep stopped this one; its partial results must not replace *grep*.
+    if token.load(Ordering::Relaxed) {
+        return Err("Grep cancelled".to_string());
+    }
+
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    let index = match editor.find_special_buffer(GREP_BUFFER_NAME) {
+        Some(index) => index,
+        None => {
+            editor.buffers.push(BufferState::new());
+            editor.buffers.len() - 1
+        }
+    };
+    editor.buffers[index].load_grep_results(results);
+    editor.switch_to_index(index);
+    Ok(editor.snapshot())
+}
+
+#[tauri::command]
+pub fn cancel_grep(grep_state: State<'_, GrepState>) -> bool {
+    grep_state.cancel()
+}
+
+#[tauri::command]
+pub async fn grep_goto(state: State<'_, Mutex<EditorState>>) -> Result<EditorSnapshot, String> {
+    let target = {
+        let editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        editor
+            .current()
+            .grep_match_at_cursor()
+            .cloned()
+            .ok_or_else(|| "No grep match on this line".to_string())?
+    };
+
+    open_path(target.path.clone(), &state).await?;
+
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    let buf = editor.current_mut();
+    if let Some(cursor) = buf.line_col_to_cursor(target.line, target.col) {
+        buf.set_cursor(cursor);
+    }
+    buf.set_status_message(Some(format!(
+        "{}:{}:{}",
+        target.path.display(),
+        target.line,
+        target.col
+    )));
+    Ok(editor.snapshot())
+}
+
+/// Filesystem work of a dired command, done with the editor unlocked.
+enum DiredOp {
+    Refresh,
+    Delete(Vec<PathBuf>),
+    Transfer {
+        copy: bool,
+        sources: Vec<(PathBuf, String)>,
+        dest: PathBuf,
+    },
+    CreateDirectory(PathBuf),
+    Rename(Vec<(PathBuf, PathBuf)>),
+}
+
+impl DiredOp {
+    /// Runs the operation and returns the status message.
+    fn run(self) -> Result<String, String> {
+        match self {
+            DiredOp::Refresh => Ok("Reverted".to_string()),
+            DiredOp::Delete(paths) => {
+                for path in &paths {
+                    dired::delete_path(path)?;
+                }
+                Ok(format!("Deleted {} files", paths.len()))
+            }
+            DiredOp::Transfer { copy, sources, dest } => {
+                if sources.len() > 1 && !dest.is_dir() {
+                    return Err(format!("{} is not a directory", dest.display()));
+                }
+                for (from, name) in &sources {
+                    let to = if dest.is_dir() { dest.join(name) } else { dest.clone() };
+                    if copy {
+                        dired::copy_path(from, &to)?;
+                    } else {
+                        dired::rename_path(from, &to)?;
+                    }
+                }
+                let verb = if copy { "Copied" } else { "Renamed" };
+                Ok(format!("{} {} files", verb, sources.len()))
+            }
+            DiredOp::CreateDirectory(path) => {
+                dired::create_directory(&path)?;
+                Ok(format!("Created {}", path.display()))
+            }
+            DiredOp::Rename(renames) => {
+                dired::rename_paths(&renames)?;
+                Ok(format!("Renamed {} files", renames.len()))
+            }
+        }
+    }
+
+    /// Whether marks survive the refresh that follows.
+    fn keeps_marks(&self) -> bool {
+        matches!(self, DiredOp::Refresh | DiredOp::CreateDirectory(_))
+    }
+}
+
+enum DiredNext {
+    Visit(PathBuf),
+    Snapshot,
+    Run(BufferId, DiredOp),
+}
+
+#[tauri::command]
+pub async fn dired_command(
+    command: String,
+    payload: Option<DiredPayload>,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<EditorSnapshot, String> {
+    let target = payload.and_then(|p| p.target).filter(|t| !t.trim().is_empty());
+
+    let next = {
+        let mut editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        let buf = editor.current_mut();
+        let id = buf.id;
+        let listing = buf
+            .dired_listing()
+            .cloned()
+            .ok_or_else(|| "Not a dired buffer".to_string())?;
+        let directory = listing.directory.clone();
+        let resolve = |name: &str| -> PathBuf {
+            let path = PathBuf::from(name);
+            if path.is_absolute() {
+                path
+            } else {
+                directory.join(path)
+            }
+        };
+
+        match command.as_str() {
+            "find_file" => {
+                let entry = buf
+                    .dired_entry_at_cursor()
+                    .ok_or_else(|| "No file on this line".to_string())?;
+                DiredNext::Visit(listing.path_of(entry))
+            }
+            "up" => DiredNext::Visit(
+                directory
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .ok_or_else(|| "Already at the root".to_string())?,
+            ),
+            "mark" => {
+                buf.dired_set_mark(DiredMark::Marked)?;
+                DiredNext::Snapshot
+            }
+            "unmark" => {
+                buf.dired_set_mark(DiredMark::None)?;
+                DiredNext::Snapshot
+            }
+            "flag_delete" => {
+                buf.dired_set_mark(DiredMark::Delete)?;
+                DiredNext::Snapshot
+            }
+            "unmark_all" => {
+                buf.dired_unmark_all();
+                DiredNext::Snapshot
+            }
+            "refresh" => DiredNext::Run(id, DiredOp::Refresh),
+            "execute" | "delete" => {
+                let entries = if command == "execute" {
+                    buf.dired_flagged()
+                } else {
+                    buf.dired_targets()
+                };
+                if entries.is_empty() {
+                    return Err("No files to delete".to_string());
+                }
+                let paths = entries.iter().map(|entry| listing.path_of(entry)).collect();
+                DiredNext::Run(id, DiredOp::Delete(paths))
+            }
+            "rename" | "copy" => {
+                let entries = buf.dired_targets();
+                if entries.is_empty() {
+                    return Err("No files selected".to_string());
+                }
+                let dest = resolve(
+                    target
+                        .as_deref()
+                        .ok_or_else(|| format!("{command} requires a target"))?,
+                );
+                let sources = entries
+                    .iter()
+                    .map(|entry| (listing.path_of(entry), entry.name.clone()))
+                    .collect();
+                DiredNext::Run(id, DiredOp::Transfer { copy: command == "copy", sources, dest })
+            }
+            "create_directory" => {
+                let path = resolve(
+                    target
+                        .as_deref()
+                        .ok_or_else(|| "create_directory requires a target".to_string())?,
+                );
+                DiredNext::Run(id, DiredOp::CreateDirectory(path))
+            }
+            "wdired_start" => {
+                buf.wdired_start()?;
+                DiredNext::Snapshot
+            }
+            "wdired_abort" => {
+                buf.wdired_abort();
+                DiredNext::Snapshot
+            }
+            "wdired_commit" => {
+                let renames = listing
+                    .wdired_renames(&buf.buffer.as_str())?
+                    .into_iter()
+                    .map(|(from, to)| (directory.join(from), directory.join(to)))
+                    .collect();
+                DiredNext::Run(id, DiredOp::Rename(renames))
+            }
+            _ => return Err(format!("unknown dired command: {command}")),
+        }
+    };
+
+    match next {
+        DiredNext::Visit(path) => open_path(path, &state).await,
+        DiredNext::Snapshot => {
+            let mut editor = state
+                .lock()
+                .map_err(|_| "state lock poisoned".to_string())?;
+            Ok(editor.snapshot())
+        }
+        DiredNext::Run(id, op) => {
+            let keep_marks = op.keeps_marks();
+            let result = tauri::async_runtime::spawn_blocking(move || op.run())
+                .await
+                .map_err(|err| format!("dired failed: {err}"))
+                .and_then(|result| result);
+            // Refresh even after a failure partway, so the listing shows what happened.
+            refresh_dired(id, keep_marks, &state).await?;
+            let status = result?;
+            let mut editor = state
+                .lock()
+                .map_err(|_| "state lock poisoned".to_string())?;
+            if let Some(index) = editor.index_of(id) {
+                editor.buffers[index].set_status_message(Some(status));
+            }
+            Ok(editor.snapshot())
+        }
+    }
+}
+
+/// Re-reads the directory shown by dired buffer `id`, reading it with the editor unlocked.
+async fn refresh_dired(id: BufferId, keep_marks: bool, state: &Mutex<EditorState>) -> Result<(), String> {
+    let directory = {
+        let editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        let Some(listing) = editor.index_of(id).and_then(|index| editor.buffers[index].dired_listing()) else {
+            return Ok(());
+        };
+        listing.directory.clone()
+    };
+    let mut listing = tauri::async_runtime::spawn_blocking(move || dired::read_listing(&directory))
         .await
-        .map_err(|err| format!("failed to read directory entry: {err}"))?
-    {
-        let file_name = entry.file_name().to_string_lossy().to_string();
-        let is_dir = entry
-            .metadata()
-            .await
-            .map(|m| m.is_dir())
-            .unwrap_or(false);
-        names.push((file_name, is_dir));
+        .map_err(|err| format!("dired failed: {err}"))??;
+
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    let Some(index) = editor.index_of(id) else {
+        return Ok(());
+    };
+    let buf = &mut editor.buffers[index];
+    if keep_marks {
+        if let Some(old) = buf.dired_listing() {
+            listing.inherit_marks(old);
+        }
+    }
+    buf.load_dired(listing);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn ibuffer_command(
+    command: String,
+    payload: Option<IbufferPayload>,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<EditorSnapshot, String> {
+    let (target, force) = match payload {
+        Some(p) => (
+            p.target.filter(|t| !t.trim().is_empty()),
+            p.force.unwrap_or(false),
+        ),
+        None => (None, false),
+    };
+
+    if command == "open" {
+        let mut editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        editor.show_buffer_list();
+        return Ok(editor.snapshot());
     }
 
-    names.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
+    let targets = {
+        let mut editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        let buf = editor.current_mut();
+        let filter = buf
+            .buffer_list()
+            .map(|list| list.filter.clone())
+            .ok_or_else(|| "Not a buffer list".to_string())?;
+        match command.as_str() {
+            "visit" => {
+                let id = buf
+                    .buffer_list_entry_at_cursor()
+                    .map(|e| e.info.id)
+                    .ok_or_else(|| "No buffer on this line".to_string())?;
+                editor.switch_to_buffer(id)?;
+                return Ok(editor.snapshot());
+            }
+            "mark" => buf.buffer_list_set_mark(true)?,
+            "unmark" => buf.buffer_list_set_mark(false)?,
+            "unmark_all" => buf.buffer_list_unmark_all(),
+            "refresh" => editor.refresh_buffer_list(),
+            "filter_modified" => editor.set_buffer_list_filter(BufferListFilter {
+                modified_only: !filter.modified_only,
+                ..filter
+            })?,
+            "filter_directory" => editor.set_buffer_list_filter(BufferListFilter {
+                directory: target.map(PathBuf::from),
+                ..filter
+            })?,
+            "clear_filters" => editor.set_buffer_list_filter(BufferListFilter::default())?,
+            "save_marked" | "kill_marked" | "revert_marked" => {
+                let targets = buf.buffer_list_targets();
+                if targets.is_empty() {
+                    return Err("No buffers selected".to_string());
+                }
+                if command == "kill_marked" && !force {
+                    let modified: Vec<String> = targets
+                        .iter()
+                        .filter_map(|id| editor.index_of(*id))
+                        .map(|i| &editor.buffers[i])
+                        .filter(|b| b.is_modified() && !b.is_indirect())
+                        .map(|b| b.name())
+                        .collect();
+                    if !modified.is_empty() {
+                        return Err(format!(
+                            "Modified buffers: {}. Confirmation required.",
+                            modified.join(", ")
+                        ));
+                    }
+                }
+            }
+            _ => return Err(format!("unknown ibuffer command: {command}")),
+        }
+        if !command.ends_with("_marked") {
+            return Ok(editor.snapshot());
+        }
+        editor.current().buffer_list_targets()
+    };
 
-    let mut out = String::new();
-    out.push_str(&format!("Directory: {}\n\n", path.display()));
-    for (name, is_dir) in names {
-        if is_dir {
-            out.push_str(&format!("{name}/\n"));
-        } else {
-            out.push_str(&format!("{name}\n"));
+    let mut done = 0;
+    for id in targets {
+        let index = {
+            let editor = state
+                .lock()
+                .map_err(|_| "state lock poisoned".to_string())?;
+            editor.index_of(id)
+        };
+        // Killing a base buffer also kills its indirect buffers, which may be later targets.
+        let Some(index) = index else {
+            continue;
+        };
+        match command.as_str() {
+            "save_marked" => {
+                save_buffer_at(index, &state).await?;
+            }
+            "revert_marked" => {
+                revert_buffer_at(index, &state).await?;
+            }
+            _ => {
+                let mut editor = state
+                    .lock()
+                    .map_err(|_| "state lock poisoned".to_string())?;
+                editor.kill_buffer(Some(id), true)?;
+            }
         }
+        done += 1;
+    }
+
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    editor.refresh_buffer_list();
+    let verb = match command.as_str() {
+        "save_marked" => "Saved",
+        "revert_marked" => "Reverted",
+        _ => "Killed",
+    };
+    editor
+        .current_mut()
+        .set_status_message(Some(format!("{} {} buffers", verb, done)));
+    Ok(editor.snapshot())
+}
+
+/// Re-reads the file visited by the buffer at `index`, discarding unsaved changes.
+async fn revert_buffer_at(index: usize, state: &Mutex<EditorState>) -> Result<(), String> {
+    let path = {
+        let editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        let buf = editor
+            .buffers
+            .get(index)
+            .ok_or_else(|| "No such buffer".to_string())?;
+        buf.file_path
+            .clone()
+            .ok_or_else(|| format!("{} is not visiting a file", buf.name()))?
+    };
+
+    let bytes = fs::read(&path)
+        .await
+        .map_err(|err| format!("failed to read file: {err}"))?;
+    let (decoded, settings) = decode_visited_file(&bytes, &path);
+
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    let defaults = editor.settings.file_defaults(false);
+    if let Some(buf) = editor.buffers.get_mut(index) {
+        let cursor = buf.cursor;
+        buf.load_content(decoded.text, decoded.encoding, decoded.line_ending, path);
+        buf.apply_file_settings(settings.or(defaults));
+        buf.cursor = cursor.min(buf.buffer.char_len());
     }
-    Ok(out)
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn save_file(state: State<'_, Mutex<EditorState>>) -> Result<EditorSnapshot, String> {
-    let (path, text, line_ending) = {
+    let index = {
         let editor = state
             .lock()
             .map_err(|_| "state lock poisoned".to_string())?;
-        let buf = editor.current();
+        editor.current_index
+    };
+
+    save_buffer_at(index, &state).await?;
+
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    Ok(editor.snapshot())
+}
+
+/// Writes the buffer at `index` to its visited file and marks it saved.
+async fn save_buffer_at(index: usize, state: &Mutex<EditorState>) -> Result<PathBuf, String> {
+    let (path, original, text, line_ending, charset, backup) = {
+        let editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        let backup = editor.settings.backup;
+        let buf = editor
+            .buffers
+            .get(index)
+            .ok_or_else(|| "No such buffer".to_string())?;
         let path = buf
             .file_path
             .clone()
             .ok_or_else(|| "No file path. Use save as.".to_string())?;
         (
             path,
-            buf.buffer.as_str().to_string(),
+            buf.buffer.text(),
+            buf.text_to_save(),
             buf.line_ending.clone(),
+            buf.file_settings.charset,
+            backup,
         )
     };
 
-    write_content(&path, &text, &line_ending).await?;
+    write_content(&path, &text, &line_ending, charset, backup).await?;
 
     let mut editor = state
         .lock()
         .map_err(|_| "state lock poisoned".to_string())?;
-    editor.current_mut().mark_saved();
-    editor.current_mut().set_status_message(Some(format!("Saved {}", path.display())));
+    if let Some(buf) = editor.buffers.get_mut(index) {
+        buf.apply_saved_text(&original, text);
+        buf.mark_saved();
+        buf.set_status_message(Some(format!("Saved {}", path.display())));
+    }
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn save_buffers(
+    ids: Vec<BufferId>,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<EditorSnapshot, String> {
+    let mut saved = 0;
+    for id in &ids {
+        let index = {
+            let editor = state
+                .lock()
+                .map_err(|_| "state lock poisoned".to_string())?;
+            editor.index_of(*id)
+        };
+        let Some(index) = index else {
+            return Err(format!("No buffer with id {}", id));
+        };
+        save_buffer_at(index, &state).await?;
+        saved += 1;
+    }
+
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    editor
+        .current_mut()
+        .set_status_message(Some(format!("Saved {} buffers", saved)));
     Ok(editor.snapshot())
 }
 
+#[tauri::command]
+pub fn save_some_buffers(state: State<'_, Mutex<EditorState>>) -> Result<SaveSomeResponse, String> {
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    let status = editor.start_save_some_buffers();
+    Ok(SaveSomeResponse {
+        snapshot: editor.snapshot(),
+        status,
+    })
+}
+
+#[tauri::command]
+pub async fn save_some_buffers_step(
+    payload: QueryReplaceStepPayload,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<SaveSomeResponse, String> {
+    let to_save = {
+        let mut editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        editor.save_some_step(&payload.action)?
+    };
+
+    for id in to_save {
+        let index = {
+            let editor = state
+                .lock()
+                .map_err(|_| "state lock poisoned".to_string())?;
+            editor.index_of(id)
+        };
+        let Some(index) = index else {
+            continue;
+        };
+        save_buffer_at(index, &state).await?;
+        let mut editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        editor.save_some_record_saved();
+    }
+
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    let status = editor.save_some_next_status();
+    Ok(SaveSomeResponse {
+        snapshot: editor.snapshot(),
+        status,
+    })
+}
+
+/// Exits the application; refuses while modified file buffers remain unless `force` is set.
+#[tauri::command]
+pub fn quit_editor(
+    force: bool,
+    app: AppHandle,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<(), String> {
+    let editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    editor.check_quit(force)?;
+    drop(editor);
+    app.exit(0);
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn save_file_as(
     path: String,
@@ -385,23 +1340,32 @@ pub async fn save_file_as(
         return Err("File exists. Confirmation required.".to_string());
     }
 
-    let (text, line_ending) = {
+    let (original, text, line_ending, charset, backup) = {
         let editor = state
             .lock()
             .map_err(|_| "state lock poisoned".to_string())?;
+        let backup = editor.settings.backup;
         let buf = editor.current();
         (
-            buf.buffer.as_str().to_string(),
+            buf.buffer.text(),
+            buf.text_to_save(),
             buf.line_ending.clone(),
+            buf.file_settings.charset,
+            backup,
         )
     };
 
-    write_content(&target_path, &text, &line_ending).await?;
+    write_content(&target_path, &text, &line_ending, charset, backup).await?;
 
     let mut editor = state
         .lock()
         .map_err(|_| "state lock poisoned".to_string())?;
+    editor.current_mut().apply_saved_text(&original, text);
     editor.current_mut().set_file_path(target_path.clone());
+    editor.current_mut().set_auto_mode();
+    editor.uniquify_buffer_names();
+    editor.record_recent_file(&target_path);
+    persist_recent_files(&mut editor);
     editor.current_mut().mark_saved();
     editor.current_mut().set_status_message(Some(format!("Saved {}", target_path.display())));
     Ok(editor.snapshot())
@@ -421,6 +1385,12 @@ pub fn default_save_directory() -> Result<String, String> {
         }
     }
 
+    if let Ok(home) = std::env::var("HOME") {
+        if !home.trim().is_empty() {
+            return Ok(home);
+        }
+    }
+
     if let Ok(home_drive) = std::env::var("HOMEDRIVE") {
         let home_path = std::env::var("HOMEPATH").unwrap_or_default();
         let combined = format!("{home_drive}{home_path}");
@@ -435,88 +1405,204 @@ pub fn default_save_directory() -> Result<String, String> {
 }
 
 #[tauri::command]
-pub fn path_completions(input: String) -> Result<Vec<String>, String> {
-    let normalized = input.replace('/', "\\");
-    let (base_dir, partial) = split_path_prefix(&normalized);
-    let target_dir = if base_dir.is_empty() {
-        std::env::current_dir().map_err(|err| format!("failed to resolve current dir: {err}"))?
-    } else {
-        PathBuf::from(base_dir)
+pub fn path_completions(
+    input: String,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<Vec<String>, String> {
+    let recent_files = {
+        let editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        editor.recent_files.clone()
     };
+    let current_dir =
+        std::env::current_dir().map_err(|err| format!("failed to resolve current dir: {err}"))?;
+    completion::complete_path(&input, current_dir, &recent_files)
+}
 
-    let entries = std::fs::read_dir(&target_dir)
-        .map_err(|err| format!("failed to read directory {}: {err}", target_dir.display()))?;
+#[tauri::command]
+pub fn recent_files(
+    existing_only: Option<bool>,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<Vec<String>, String> {
+    let editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    Ok(editor
+        .recent_files
+        .iter()
+        .filter(|p| !existing_only.unwrap_or(false) || p.exists())
+        .map(|p| p.to_string_lossy().to_string())
+        .collect())
+}
 
-    let partial_lower = partial.to_lowercase();
-    let mut matches: Vec<String> = Vec::new();
+#[tauri::command]
+pub fn recentf_cleanup(state: State<'_, Mutex<EditorState>>) -> Result<EditorSnapshot, String> {
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    let removed = editor.remove_missing_recent_files();
+    persist_recent_files(&mut editor);
+    editor
+        .current_mut()
+        .set_status_message(Some(format!("Removed {} missing recent files", removed)));
+    Ok(editor.snapshot())
+}
 
-    for entry in entries.flatten() {
-        let file_name = entry.file_name().to_string_lossy().to_string();
-        if !partial_lower.is_empty() && !file_name.to_lowercase().starts_with(&partial_lower) {
-            continue;
-        }
+/// Effective value and source of every setting in the config schema.
+#[tauri::command]
+pub fn describe_config(state: State<'_, Mutex<EditorState>>) -> Result<Vec<SettingView>, String> {
+    let editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    Ok(describe_settings(&editor.settings, &editor.settings_sources))
+}
 
-        let mut full = target_dir.join(&file_name).to_string_lossy().to_string();
-        if entry.path().is_dir() && !full.ends_with('\\') {
-            full.push('\\');
-        }
-        matches.push(full);
-    }
+/// Built-in themes and the theme files in the config directory.
+#[tauri::command]
+pub fn list_themes() -> Vec<ThemeInfo> {
+    theme::list_themes(theme::themes_dir().as_deref())
+}
 
-    matches.sort();
-    if matches.len() > 100 {
-        matches.truncate(100);
-    }
-    Ok(matches)
+/// Resolves the named theme for the frontend to switch to.
+#[tauri::command]
+pub fn load_theme(name: String) -> Result<ThemeConfig, String> {
+    theme::load_theme(&name, theme::themes_dir().as_deref())
+}
+
+/// Loads the config layers for `project_dir` and applies them to the
+/// editor. Returns the config for the frontend and the settings whose
+/// effective value changed. Nothing is applied when a layer fails to load.
+fn apply_config(
+    project_dir: Option<&Path>,
+    state: &Mutex<EditorState>,
+) -> Result<(AppConfigResponse, Vec<String>), String> {
+    let loaded = config::load_layers(&config::config_layers(project_dir))?;
+    let theme = theme::resolve_config_theme(loaded.theme, theme::themes_dir().as_deref())?;
+
+    let changed_settings = {
+        let mut editor = state
+            .lock()
+            .map_err(|_| "state lock poisoned".to_string())?;
+        let before = describe_settings(&editor.settings, &editor.settings_sources);
+        let after = describe_settings(&loaded.settings, &loaded.sources);
+        editor.keymap = loaded.keymap;
+        editor.apply_settings(loaded.settings, loaded.sources);
+        before
+            .into_iter()
+            .zip(after)
+            .filter(|(old, new)| old.value != new.value)
+            .map(|(_, new)| new.name)
+            .collect()
+    };
+
+    let response = AppConfigResponse {
+        theme,
+        keybindings: loaded.keybindings,
+        source_paths: loaded.paths,
+    };
+    Ok((response, changed_settings))
 }
 
+/// Loads the system, user and project config layers, applies them and
+/// starts watching them for changes. The project layer is the
+/// `.femto.yaml` nearest to `directory`, or to the current buffer's
+/// directory when none is given. The frontend calls this whenever the
+/// current buffer's directory changes; when that resolves to the project
+/// layer already in effect, the applied config is returned as is.
 #[tauri::command]
-pub fn load_app_config() -> Result<AppConfigResponse, String> {
-    let Some(path) = resolve_config_path() else {
-        return Ok(AppConfigResponse::default());
+pub fn load_app_config(
+    directory: Option<String>,
+    state: State<'_, Mutex<EditorState>>,
+    watch: State<'_, ConfigWatch>,
+) -> Result<AppConfigResponse, String> {
+    let project_dir = match directory {
+        Some(directory) => Some(PathBuf::from(directory)),
+        None => {
+            let editor = state
+                .lock()
+                .map_err(|_| "state lock poisoned".to_string())?;
+            editor
+                .current()
+                .default_directory()
+                .or_else(|| std::env::current_dir().ok())
+        }
     };
+    if let Some(applied) = watch.applied_for(project_dir.as_deref()) {
+        return Ok(applied);
+    }
+    let (response, _) = apply_config(project_dir.as_deref(), &state)?;
+    watch.watch(project_dir, response.clone());
+    Ok(response)
+}
 
-    if !path.exists() {
-        return Ok(AppConfigResponse::default());
-    }
-
-    let content = std::fs::read_to_string(&path)
-        .map_err(|err| format!("failed to read config {}: {err}", path.display()))?;
-    let parsed: RawConfig = serde_yaml::from_str(&content)
-        .map_err(|err| format!("failed to parse yaml config {}: {err}", path.display()))?;
-
-    let theme = parsed.theme.unwrap_or_default();
-    Ok(AppConfigResponse {
-        theme: ThemeConfig {
-            background_color: theme.background_color,
-            text_color: theme.text_color,
-            cursor_color: theme.cursor_color,
-            selection_bg: theme.selection_bg,
-            current_line_bg: theme.current_line_bg,
-            current_line_highlight: theme.current_line_highlight,
-            statusbar_bg: theme.statusbar_bg,
-            minibuffer_bg: theme.minibuffer_bg,
-            background_image: theme.background_image,
-            font_family: theme.font_family,
-        },
-        source_path: Some(path.to_string_lossy().to_string()),
-    })
+/// Re-applies the config after a watched file changed and tells the
+/// frontend what changed. A config that fails to load leaves the previous
+/// one in effect and shows the error in the status message.
+pub fn reload_app_config(
+    app: &AppHandle,
+    state: &Mutex<EditorState>,
+    watch: &ConfigWatch,
+    project_dir: Option<PathBuf>,
+) {
+    let event = match apply_config(project_dir.as_deref(), state) {
+        Ok((config, changed_settings)) => {
+            let previous = watch.replace_applied(config.clone()).unwrap_or_default();
+            ConfigChangedEvent {
+                theme_changed: previous.theme != config.theme,
+                keybindings_changed: previous.keybindings != config.keybindings,
+                changed_settings,
+                config: Some(config),
+                error: None,
+            }
+        }
+        Err(err) => {
+            if let Ok(mut editor) = state.lock() {
+                editor
+                    .current_mut()
+                    .set_status_message(Some(format!("Config not reloaded: {err}")));
+            }
+            ConfigChangedEvent {
+                config: None,
+                theme_changed: false,
+                keybindings_changed: false,
+                changed_settings: Vec::new(),
+                error: Some(err),
+            }
+        }
+    };
+    let _ = app.emit(CONFIG_CHANGED_EVENT, event);
 }
 
-async fn write_content(path: &Path, text: &str, line_ending: &str) -> Result<(), String> {
-    create_backup_if_exists(path).await?;
+/// Writes `text` with the buffer's line endings, encoded in `charset` when one
+/// was declared and as UTF-8 otherwise, after backing up the old file as
+/// `backup` says.
+async fn write_content(
+    path: &Path,
+    text: &str,
+    line_ending: &str,
+    charset: Option<Charset>,
+    backup: BackupPolicy,
+) -> Result<(), String> {
     let content = normalize_line_endings(text, line_ending);
-    fs::write(path, content)
+    let bytes = match charset {
+        Some(charset) => charset.encode(&content)?,
+        None => content.into_bytes(),
+    };
+    create_backup_if_exists(path, backup).await?;
+    fs::write(path, bytes)
         .await
         .map_err(|err| format!("failed to write file: {err}"))
 }
 
-async fn create_backup_if_exists(path: &Path) -> Result<(), String> {
+async fn create_backup_if_exists(path: &Path, policy: BackupPolicy) -> Result<(), String> {
     if !path_exists(path).await {
         return Ok(());
     }
 
-    let backup = PathBuf::from(format!("{}~", path.to_string_lossy()));
+    let Some(backup) = policy.backup_path(path, Path::exists) else {
+        return Ok(());
+    };
     fs::copy(path, &backup)
         .await
         .map_err(|err| format!("failed to create backup {}: {err}", backup.display()))?;
@@ -527,7 +1613,21 @@ async fn path_exists(path: &Path) -> bool {
     fs::metadata(path).await.is_ok()
 }
 
-fn decode_content(bytes: &[u8]) -> DecodedContent {
+/// Decodes a file being visited and resolves its settings: file-local variables
+/// win over EditorConfig. A declared charset overrides the detected one.
+fn decode_visited_file(bytes: &[u8], path: &Path) -> (DecodedContent, FileSettings) {
+    let mut decoded = decode_content(bytes);
+    let settings = file_local_variables(&decoded.text).or(editorconfig::resolve(path));
+    if let Some(charset) = settings.charset {
+        if charset.label() != decoded.encoding {
+            decoded.text = normalize_loaded_text(&charset.decode(bytes));
+            decoded.encoding = charset.label().to_string();
+        }
+    }
+    (decoded, settings)
+}
+
+pub(crate) fn decode_content(bytes: &[u8]) -> DecodedContent {
     let line_ending = detect_line_ending(bytes);
 
     if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
@@ -621,49 +1721,42 @@ fn normalize_loaded_text(text: &str) -> String {
     text.replace("\r\n", "\n").replace('\r', "\n")
 }
 
-fn split_path_prefix(input: &str) -> (&str, &str) {
-    if input.is_empty() {
-        return ("", "");
-    }
-
-    if input.ends_with('\\') {
-        return (input, "");
-    }
-
-    if let Some(index) = input.rfind('\\') {
-        return (&input[..=index], &input[index + 1..]);
-    }
+pub fn recentf_path() -> Option<PathBuf> {
+    resolve_config_dir().map(|dir| dir.join(recentf::RECENTF_FILE_NAME))
+}
 
-    ("", input)
+pub fn kmacro_path() -> Option<PathBuf> {
+    resolve_config_dir().map(|dir| dir.join(kmacro::KMACRO_FILE_NAME))
 }
 
-fn resolve_config_path() -> Option<PathBuf> {
-    if let Ok(appdata) = std::env::var("APPDATA") {
-        if !appdata.trim().is_empty() {
-            return Some(PathBuf::from(appdata).join("Femto").join("config.yaml"));
-        }
+/// Writes the recent-files list to disk. Failures only produce a status message.
+fn persist_recent_files(editor: &mut EditorState) {
+    let Some(path) = recentf_path() else {
+        return;
+    };
+    if let Err(err) = recentf::save(&path, &editor.recent_files) {
+        editor.current_mut().set_status_message(Some(err));
     }
-    None
 }
 
 #[tauri::command]
 pub fn switch_buffer(
-    name: String,
+    id: BufferId,
     state: State<'_, Mutex<EditorState>>,
 ) -> Result<EditorSnapshot, String> {
     let mut editor = state
         .lock()
         .map_err(|_| "state lock poisoned".to_string())?;
-    editor.switch_to_buffer(&name)?;
+    editor.switch_to_buffer(id)?;
     Ok(editor.snapshot())
 }
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BufferListResponse {
-    pub names: Vec<String>,
-    pub current: String,
-    pub default_switch: String,
+    pub buffers: Vec<BufferInfo>,
+    pub current: BufferId,
+    pub default_switch: BufferId,
 }
 
 #[tauri::command]
@@ -674,9 +1767,37 @@ pub fn list_buffers(
         .lock()
         .map_err(|_| "state lock poisoned".to_string())?;
     Ok(BufferListResponse {
-        names: editor.buffer_names(),
-        current: editor.current().name(),
-        default_switch: editor.default_switch_name(),
+        buffers: editor.buffer_infos(),
+        current: editor.current().id,
+        default_switch: editor.default_switch_id(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HighlightResponse {
+    pub buffer_id: BufferId,
+    pub major_mode: &'static str,
+    pub lines: Vec<LineSpans>,
+}
+
+/// Style spans for lines `first_line..end_line` of the current buffer's accessible text.
+#[tauri::command]
+pub fn highlight_lines(
+    first_line: usize,
+    end_line: usize,
+    state: State<'_, Mutex<EditorState>>,
+) -> Result<HighlightResponse, String> {
+    let mut editor = state
+        .lock()
+        .map_err(|_| "state lock poisoned".to_string())?;
+    // Edits not yet synced would leave stale lines in the cache.
+    editor.sync_text_edits();
+    let buf = editor.current_mut();
+    Ok(HighlightResponse {
+        buffer_id: buf.id,
+        major_mode: buf.major_mode.id,
+        lines: buf.highlight_lines(first_line, end_line),
     })
 }
 
@@ -689,7 +1810,6 @@ pub fn kill_buffer(
         .lock()
         .map_err(|_| "state lock poisoned".to_string())?;
     let force = payload.force.unwrap_or(false);
-    let name = payload.name.as_deref();
-    editor.kill_buffer(name, force)?;
+    editor.kill_buffer(payload.id, force)?;
     Ok(editor.snapshot())
 }
@@ -0,0 +1,239 @@
+use std::path::{PathBuf, MAIN_SEPARATOR};
+
+/// Maximum number of candidates returned to the minibuffer.
+const MAX_CANDIDATES: usize = 100;
+/// Score bonus that lets recently visited files outrank equally good directory entries.
+const RECENT_BONUS: i64 = 5;
+
+fn is_separator(ch: char) -> bool {
+    ch == '/' || (cfg!(windows) && ch == '\\')
+}
+
+fn home_dir() -> Option<String> {
+    ["HOME", "USERPROFILE"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find(|value| !value.trim().is_empty())
+}
+
+/// Applies Emacs `substitute-in-file-name` rules: `//` and `/~` restart the
+/// path, `~` expands to the home directory and `$VAR`, `${VAR}` (and `%VAR%`
+/// on Windows) expand environment variables.
+pub fn substitute_in_file_name(input: &str) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    let mut start = 0;
+    for i in 1..chars.len() {
+        // A leading `\\` on Windows is a UNC path, not a restart.
+        if i == 1 && cfg!(windows) {
+            continue;
+        }
+        if is_separator(chars[i - 1]) && (is_separator(chars[i]) || chars[i] == '~') {
+            start = i;
+        }
+    }
+    let restarted: String = chars[start..].iter().collect();
+
+    let expanded = expand_env_vars(&restarted);
+    match expanded.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(is_separator) => match home_dir() {
+            Some(home) => format!("{home}{rest}"),
+            None => expanded,
+        },
+        _ => expanded,
+    }
+}
+
+fn expand_env_vars(input: &str) -> String {
+    let mut out = String::new();
+    let mut rest = input;
+    while let Some(pos) = rest.find(['$', '%']) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+        let (name, consumed) = if let Some(braced) = tail.strip_prefix("${") {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 3),
+                None => ("", 0),
+            }
+        } else if let Some(unix) = tail.strip_prefix('$') {
+            let end = unix
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(unix.len());
+            (&unix[..end], end + 1)
+        } else if cfg!(windows) {
+            let windows = &tail[1..];
+            match windows.find('%') {
+                Some(end) => (&windows[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            ("", 0)
+        };
+
+        match std::env::var(name).ok().filter(|_| !name.is_empty()) {
+            Some(value) => {
+                out.push_str(&value);
+                rest = &tail[consumed..];
+            }
+            None => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Normalizes separators to the platform's own.
+pub fn normalize_separators(input: &str) -> String {
+    input
+        .chars()
+        .map(|ch| if is_separator(ch) { MAIN_SEPARATOR } else { ch })
+        .collect()
+}
+
+/// Splits `input` into the directory part (with trailing separator) and the partial name.
+pub fn split_path_prefix(input: &str) -> (&str, &str) {
+    match input.rfind(MAIN_SEPARATOR) {
+        Some(index) => (&input[..=index], &input[index + 1..]),
+        None => ("", input),
+    }
+}
+
+/// Scores `candidate` against `pattern`: prefix beats substring beats a fuzzy
+/// subsequence match. Returns `None` when the pattern does not match at all.
+pub fn match_score(pattern: &str, candidate: &str) -> Option<i64> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let pattern_lower = pattern.to_lowercase();
+    let candidate_lower = candidate.to_lowercase();
+    let length_penalty = candidate.chars().count() as i64;
+
+    if candidate.starts_with(pattern) {
+        return Some(1000 - length_penalty);
+    }
+    if candidate_lower.starts_with(&pattern_lower) {
+        return Some(900 - length_penalty);
+    }
+    if let Some(pos) = candidate_lower.find(&pattern_lower) {
+        let pos = candidate_lower[..pos].chars().count() as i64;
+        return Some(600 - pos * 4 - length_penalty);
+    }
+
+    let pattern_chars: Vec<char> = pattern_lower.chars().collect();
+    let candidate_chars: Vec<char> = candidate_lower.chars().collect();
+    let mut score = 300 - length_penalty;
+    let mut pi = 0;
+    let mut last_match: Option<usize> = None;
+    for (ci, ch) in candidate_chars.iter().enumerate() {
+        if pi == pattern_chars.len() {
+            break;
+        }
+        if *ch != pattern_chars[pi] {
+            continue;
+        }
+        if last_match.is_some_and(|last| last + 1 == ci) {
+            score += 8;
+        }
+        if ci == 0 || !candidate_chars[ci - 1].is_alphanumeric() {
+            score += 6;
+        }
+        if let Some(last) = last_match {
+            score -= (ci - last - 1) as i64;
+        }
+        last_match = Some(ci);
+        pi += 1;
+    }
+    (pi == pattern_chars.len()).then_some(score)
+}
+
+/// Completes `input` against the file system and the recent-files list.
+pub fn complete_path(
+    input: &str,
+    current_dir: PathBuf,
+    recent_files: &[PathBuf],
+) -> Result<Vec<String>, String> {
+    let expanded = normalize_separators(&substitute_in_file_name(input));
+    let (base_dir, partial) = split_path_prefix(&expanded);
+    let target_dir = if base_dir.is_empty() {
+        current_dir
+    } else {
+        PathBuf::from(base_dir)
+    };
+
+    let mut scored: Vec<(i64, String)> = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(&target_dir) {
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(score) = match_score(partial, &file_name) else {
+                continue;
+            };
+            let mut full = target_dir.join(&file_name).to_string_lossy().to_string();
+            // `file_type` comes from the directory entry and avoids an extra stat per file.
+            let is_dir = entry
+                .file_type()
+                .map(|t| t.is_dir() || (t.is_symlink() && entry.path().is_dir()))
+                .unwrap_or(false);
+            if is_dir && !full.ends_with(MAIN_SEPARATOR) {
+                full.push(MAIN_SEPARATOR);
+            }
+            scored.push((score, full));
+        }
+    } else if !base_dir.is_empty() {
+        return Err(format!(
+            "failed to read directory {}",
+            target_dir.display()
+        ));
+    }
+
+    if !expanded.is_empty() {
+        for recent in recent_files {
+            let full = recent.to_string_lossy().to_string();
+            if scored.iter().any(|(_, existing)| *existing == full) {
+                continue;
+            }
+            let name = recent
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let in_dir = recent.parent() == Some(target_dir.as_path());
+            let score = if in_dir {
+                match_score(partial, &name)
+            } else {
+                match_score(&expanded, &full).or_else(|| match_score(partial, &name))
+            };
+            if let Some(score) = score {
+                scored.push((score + RECENT_BONUS, full));
+            }
+        }
+    }
+
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    scored.truncate(MAX_CANDIDATES);
+    Ok(scored.into_iter().map(|(_, path)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_score, substitute_in_file_name};
+
+    #[test]
+    fn prefix_beats_substring_beats_fuzzy() {
+        let prefix = match_score("rea", "README.md").unwrap();
+        let substring = match_score("me", "README.md").unwrap();
+        let fuzzy = match_score("rdm", "README.md").unwrap();
+        assert!(prefix > substring && substring > fuzzy);
+        assert_eq!(match_score("xyz", "README.md"), None);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn double_slash_restarts_from_root_and_expands_vars() {
+        assert_eq!(substitute_in_file_name("/home/me//etc/hosts"), "/etc/hosts");
+        std::env::set_var("FEMTO_TEST_DIR", "/tmp/notes");
+        assert_eq!(substitute_in_file_name("$FEMTO_TEST_DIR/a.md"), "/tmp/notes/a.md");
+        assert_eq!(substitute_in_file_name("${FEMTO_TEST_DIR}/b"), "/tmp/notes/b");
+        assert_eq!(substitute_in_file_name("$NO_SUCH_FEMTO_VAR/x"), "$NO_SUCH_FEMTO_VAR/x");
+    }
+}
@@ -0,0 +1,577 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use serde_yaml::Value as YamlValue;
+
+use crate::editor::command;
+use crate::editor::file_settings::{parse_line_ending, Charset};
+use crate::editor::keymap::{parse_key_sequence, KeyBinding, Keymap, GLOBAL_KEYMAP};
+use crate::editor::mode::find_mode;
+use crate::editor::settings::{BackupPolicy, Settings};
+use crate::theme::{self, RawThemeConfig};
+
+pub const CONFIG_FILE_NAME: &str = "config.yaml";
+/// Project-local config, found by walking up from the buffer's directory.
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".femto.yaml";
+
+/// Schema version this build reads; a config without `version:` is taken to be this one.
+pub const CONFIG_VERSION: u64 = 1;
+
+/// A setting section as written in YAML; checked against `SETTINGS`.
+type RawSection = BTreeMap<String, YamlValue>;
+
+#[derive(Debug, Deserialize, Default)]
+pub struct RawConfig {
+    pub version: Option<u64>,
+    pub editor: Option<RawSection>,
+    pub files: Option<RawSection>,
+    pub search: Option<RawSection>,
+    pub theme: Option<RawThemeConfig>,
+    /// Keymap name (`global` or a major mode) to key sequence to command;
+    /// a null command unbinds the keys.
+    pub keybindings: Option<BTreeMap<String, BTreeMap<String, Option<String>>>>,
+    /// Anything else at the top level, reported as an unknown section.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, YamlValue>,
+}
+
+/// Per-user config directory: `%APPDATA%\Femto` on Windows,
+/// `$XDG_CONFIG_HOME/femto` when set, otherwise
+/// `~/Library/Application Support/Femto` on macOS and `~/.config/femto`.
+fn user_config_dir_for(os: &str, env: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    let var = |name: &str| env(name).filter(|value| !value.trim().is_empty()).map(PathBuf::from);
+    if os == "windows" {
+        return var("APPDATA").map(|dir| dir.join("Femto"));
+    }
+    if let Some(dir) = var("XDG_CONFIG_HOME") {
+        return Some(dir.join("femto"));
+    }
+    let home = var("HOME")?;
+    Some(match os {
+        "macos" => home.join("Library").join("Application Support").join("Femto"),
+        _ => home.join(".config").join("femto"),
+    })
+}
+
+/// System-wide config directory: `%PROGRAMDATA%\Femto` on Windows,
+/// `/Library/Application Support/Femto` on macOS, and the first
+/// `$XDG_CONFIG_DIRS` entry (default `/etc/xdg`) joined with `femto` elsewhere.
+fn system_config_dir_for(os: &str, env: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    match os {
+        "windows" => env("PROGRAMDATA")
+            .filter(|dir| !dir.trim().is_empty())
+            .map(|dir| PathBuf::from(dir).join("Femto")),
+        "macos" => Some(PathBuf::from("/Library/Application Support/Femto")),
+        _ => {
+            let dirs = env("XDG_CONFIG_DIRS").unwrap_or_default();
+            let first = dirs.split(':').find(|dir| !dir.trim().is_empty()).unwrap_or("/etc/xdg");
+            Some(PathBuf::from(first).join("femto"))
+        }
+    }
+}
+
+/// Directory of the user's config.yaml, recent files and saved macros.
+pub fn resolve_config_dir() -> Option<PathBuf> {
+    user_config_dir_for(std::env::consts::OS, |name| std::env::var(name).ok())
+}
+
+/// Nearest `.femto.yaml` in `dir` or one of its ancestors.
+pub fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// The system and user config.yaml paths, whether or not they exist.
+fn global_config_paths() -> Vec<PathBuf> {
+    let env = |name: &str| std::env::var(name).ok();
+    let os = std::env::consts::OS;
+    [system_config_dir_for(os, env), user_config_dir_for(os, env)]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .collect()
+}
+
+/// Existing config files in the order they apply: system, user, then the
+/// project config for `project_dir`. Later files override earlier ones.
+pub fn config_layers(project_dir: Option<&Path>) -> Vec<PathBuf> {
+    let project = project_dir.and_then(find_project_config);
+    global_config_paths()
+        .into_iter()
+        .filter(|path| path.is_file())
+        .chain(project)
+        .collect()
+}
+
+/// Every path whose creation, change or removal can change the layers for
+/// `project_dir`, including a `.femto.yaml` in each of its ancestors and the
+/// theme files a `theme:` section may extend.
+pub fn watched_paths(project_dir: Option<&Path>) -> Vec<PathBuf> {
+    let project = project_dir
+        .into_iter()
+        .flat_map(Path::ancestors)
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME));
+    let themes = theme::list_themes(theme::themes_dir().as_deref())
+        .into_iter()
+        .filter_map(|info| info.path.map(PathBuf::from));
+    global_config_paths()
+        .into_iter()
+        .chain(project)
+        .chain(themes)
+        .collect()
+}
+
+/// The merged result of every config layer.
+#[derive(Debug, Default)]
+pub struct LoadedConfig {
+    pub settings: Settings,
+    pub sources: BTreeMap<String, String>,
+    pub keymap: Keymap,
+    /// Configured bindings of all layers, in the order they were applied.
+    pub keybindings: Vec<KeyBinding>,
+    pub theme: RawThemeConfig,
+    /// Every file that was applied, in order.
+    pub paths: Vec<String>,
+}
+
+impl LoadedConfig {
+    /// Applies one layer on top of what has been loaded so far.
+    pub fn apply(&mut self, source: &ConfigSource) -> Result<(), String> {
+        let raw = source.parse()?;
+        source.settings(&raw, &mut self.settings, &mut self.sources)?;
+        let bindings = source.keybindings(&raw, &mut self.keymap)?;
+        self.keybindings.extend(bindings);
+        if let Some(theme) = raw.theme {
+            theme.check(source, &["theme"])?;
+            if let Some(name) = &theme.extends {
+                theme::load_theme(name, theme::themes_dir().as_deref())
+                    .map_err(|err| source.error_at(&["theme", "extends"], err))?;
+            }
+            self.theme.overlay(theme);
+        }
+        self.paths.push(source.origin.to_string());
+        Ok(())
+    }
+}
+
+/// Reads and merges the given config files.
+pub fn load_layers(paths: &[PathBuf]) -> Result<LoadedConfig, String> {
+    let mut loaded = LoadedConfig::default();
+    for path in paths {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read config {}: {err}", path.display()))?;
+        let origin = path.to_string_lossy();
+        loaded.apply(&ConfigSource { origin: &origin, text: &text })?;
+    }
+    Ok(loaded)
+}
+
+/// One setting of the `editor`, `files` or `search` section.
+pub struct SettingSpec {
+    pub section: &'static str,
+    pub key: &'static str,
+    pub doc: &'static str,
+    /// Reads the YAML value into the settings, or says what was expected.
+    set: fn(&mut Settings, &YamlValue) -> Result<(), String>,
+    get: fn(&Settings) -> Value,
+}
+
+/// A setting's effective value and where it came from, for `describe_config`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingView {
+    /// `section.key`, e.g. `editor.tab_width`.
+    pub name: String,
+    pub doc: &'static str,
+    pub value: Value,
+    pub default: Value,
+    /// Path of the config file that set it, or `default`.
+    pub source: String,
+}
+
+fn positive(value: &YamlValue) -> Result<usize, String> {
+    value
+        .as_u64()
+        .filter(|n| *n > 0)
+        .map(|n| n as usize)
+        .ok_or_else(|| "expected a positive integer".to_string())
+}
+
+fn string(value: &YamlValue) -> Result<&str, String> {
+    value.as_str().ok_or_else(|| "expected a string".to_string())
+}
+
+static SETTINGS: &[SettingSpec] = &[
+    SettingSpec {
+        section: "editor",
+        key: "tab_width",
+        doc: "Columns a tab advances to; null uses the major mode's indent width.",
+        set: |s, v| {
+            s.tab_width = if v.is_null() { None } else { Some(positive(v)?) };
+            Ok(())
+        },
+        get: |s| json!(s.tab_width),
+    },
+    SettingSpec {
+        section: "editor",
+        key: "fill_column",
+        doc: "Column Auto-Fill mode breaks lines at.",
+        set: |s, v| {
+            s.fill_column = positive(v)?;
+            Ok(())
+        },
+        get: |s| json!(s.fill_column),
+    },
+    SettingSpec {
+        section: "editor",
+        key: "undo_limit",
+        doc: "Undo steps kept per buffer.",
+        set: |s, v| {
+            s.undo_limit = positive(v)?;
+            Ok(())
+        },
+        get: |s| json!(s.undo_limit),
+    },
+    SettingSpec {
+        section: "editor",
+        key: "kill_ring_size",
+        doc: "Kills kept in the kill ring.",
+        set: |s, v| {
+            s.kill_ring_max = positive(v)?;
+            Ok(())
+        },
+        get: |s| json!(s.kill_ring_max),
+    },
+    SettingSpec {
+        section: "files",
+        key: "line_ending",
+        doc: "Line ending of new files: LF, CRLF or CR.",
+        set: |s, v| {
+            s.line_ending = parse_line_ending(string(v)?).ok_or("expected LF, CRLF or CR")?;
+            Ok(())
+        },
+        get: |s| json!(s.line_ending),
+    },
+    SettingSpec {
+        section: "files",
+        key: "encoding",
+        doc: "Encoding of new files, e.g. utf-8, shift-jis or latin-1.",
+        set: |s, v| {
+            let name = string(v)?;
+            s.encoding = Charset::from_name(name).ok_or_else(|| format!("unknown encoding `{}`", name))?;
+            Ok(())
+        },
+        get: |s| json!(s.encoding.label()),
+    },
+    SettingSpec {
+        section: "files",
+        key: "backup",
+        doc: "Backup made before overwriting a file: none, simple (file~) or numbered (file.~N~).",
+        set: |s, v| {
+            s.backup = BackupPolicy::from_name(string(v)?).ok_or("expected none, simple or numbered")?;
+            Ok(())
+        },
+        get: |s| json!(s.backup.name()),
+    },
+    SettingSpec {
+        section: "search",
+        key: "case_fold",
+        doc: "Search ignores case unless the query has an uppercase letter.",
+        set: |s, v| {
+            s.case_fold = v.as_bool().ok_or("expected true or false")?;
+            Ok(())
+        },
+        get: |s| json!(s.case_fold),
+    },
+];
+
+/// Every setting with its effective value and source.
+pub fn describe_settings(settings: &Settings, sources: &BTreeMap<String, String>) -> Vec<SettingView> {
+    let defaults = Settings::default();
+    SETTINGS
+        .iter()
+        .map(|spec| {
+            let name = format!("{}.{}", spec.section, spec.key);
+            SettingView {
+                doc: spec.doc,
+                value: (spec.get)(settings),
+                default: (spec.get)(&defaults),
+                source: sources.get(&name).cloned().unwrap_or_else(|| "default".to_string()),
+                name,
+            }
+        })
+        .collect()
+}
+
+/// Parsed `config.yaml` with the source it came from, for error positions.
+pub struct ConfigSource<'a> {
+    pub origin: &'a str,
+    pub text: &'a str,
+}
+
+impl ConfigSource<'_> {
+    pub fn parse(&self) -> Result<RawConfig, String> {
+        serde_yaml::from_str(self.text)
+            .map_err(|err| format!("failed to parse yaml config {}: {err}", self.origin))
+    }
+
+    /// `origin:line:column: message`, pointing at the key at `path` or at
+    /// its nearest enclosing key that can be found.
+    pub fn error_at(&self, path: &[&str], message: String) -> String {
+        match locate_key(self.text, path) {
+            Some((line, column)) => format!("{}:{}:{}: {}", self.origin, line, column, message),
+            None => format!("{}: {}", self.origin, message),
+        }
+    }
+
+    /// Checks the version and top-level sections, then reads the `editor`,
+    /// `files` and `search` sections into `settings`, recording this file
+    /// in `sources` for each setting it sets.
+    pub fn settings(
+        &self,
+        raw: &RawConfig,
+        settings: &mut Settings,
+        sources: &mut BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        if let Some(version) = raw.version.filter(|v| *v != CONFIG_VERSION) {
+            return Err(self.error_at(
+                &["version"],
+                format!("unsupported config version {}; expected {}", version, CONFIG_VERSION),
+            ));
+        }
+        if let Some(section) = raw.unknown.keys().next() {
+            return Err(self.error_at(&[section], format!("unknown section `{}`", section)));
+        }
+        let sections = [("editor", &raw.editor), ("files", &raw.files), ("search", &raw.search)];
+        for (section, values) in sections {
+            for (key, value) in values.iter().flatten() {
+                let path = [section, key.as_str()];
+                let spec = SETTINGS
+                    .iter()
+                    .find(|spec| spec.section == section && spec.key == key)
+                    .ok_or_else(|| self.error_at(&path, format!("unknown setting `{}.{}`", section, key)))?;
+                (spec.set)(settings, value)
+                    .map_err(|err| self.error_at(&path, format!("`{}.{}`: {}", section, key, err)))?;
+                sources.insert(format!("{}.{}", section, key), self.origin.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the `keybindings:` section and applies it to `keymap`:
+    /// known keymaps, well-formed key sequences, registered commands, and no
+    /// key sequence that is both bound and a prefix of another binding in
+    /// its keymap.
+    pub fn keybindings(&self, raw: &RawConfig, keymap: &mut Keymap) -> Result<Vec<KeyBinding>, String> {
+        let Some(keymaps) = &raw.keybindings else {
+            return Ok(Vec::new());
+        };
+        let mut bindings = Vec::new();
+        for (keymap, entries) in keymaps {
+            let keymap_name = if keymap == GLOBAL_KEYMAP {
+                GLOBAL_KEYMAP
+            } else {
+                find_mode(keymap)
+                    .map(|mode| mode.id)
+                    .ok_or_else(|| {
+                        self.error_at(
+                            &["keybindings", keymap],
+                            format!("unknown keymap `{}`; use `global` or a major mode", keymap),
+                        )
+                    })?
+            };
+            for (keys, bound) in entries {
+                let path = ["keybindings", keymap.as_str(), keys.as_str()];
+                let canonical = parse_key_sequence(keys).map_err(|err| self.error_at(&path, err))?;
+                if let Some(name) = bound {
+                    command::lookup(name).map_err(|err| self.error_at(&path, err))?;
+                }
+                bindings.push((
+                    path,
+                    KeyBinding {
+                        keymap: keymap_name.to_string(),
+                        keys: canonical,
+                        command: bound.clone(),
+                    },
+                ));
+            }
+        }
+
+        keymap.apply(&bindings.iter().map(|(_, binding)| binding.clone()).collect::<Vec<_>>());
+        for (path, binding) in &bindings {
+            if binding.command.is_none() {
+                continue;
+            }
+            if let Some(other) = keymap.prefix_conflict(binding) {
+                return Err(self.error_at(
+                    path,
+                    format!(
+                        "`{}` conflicts with `{}` ({}): a key sequence cannot be both a command and a prefix",
+                        binding.keys,
+                        other.keys,
+                        other.command.as_deref().unwrap_or_default()
+                    ),
+                ));
+            }
+        }
+        Ok(bindings.into_iter().map(|(_, binding)| binding).collect())
+    }
+}
+
+/// Finds a key of a block-style YAML mapping by its path and returns its
+/// 1-based line and column. When the full path is missing, the deepest
+/// enclosing key found is returned instead.
+pub fn locate_key(text: &str, path: &[&str]) -> Option<(usize, usize)> {
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut best: Option<(usize, (usize, usize))> = None;
+    for (index, line) in text.lines().enumerate() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') || content.starts_with("---") {
+            continue;
+        }
+        let indent = line.len() - content.len();
+        let Some(key) = mapping_key(content) else {
+            continue;
+        };
+        while stack.last().is_some_and(|(level, _)| *level >= indent) {
+            stack.pop();
+        }
+        stack.push((indent, key));
+
+        let depth = stack.len();
+        let matches = depth <= path.len() && stack.iter().zip(path).all(|((_, key), want)| key == want);
+        if matches && best.is_none_or(|(best_depth, _)| depth > best_depth) {
+            best = Some((depth, (index + 1, indent + 1)));
+            if depth == path.len() {
+                break;
+            }
+        }
+    }
+    best.map(|(_, position)| position)
+}
+
+/// The key of a `key: value` line, unquoted.
+fn mapping_key(content: &str) -> Option<String> {
+    let quote = content.chars().next().filter(|ch| *ch == '"' || *ch == '\'');
+    if let Some(quote) = quote {
+        let end = content[1..].find(quote)? + 1;
+        let rest = content[end + 1..].trim_start();
+        return rest.starts_with(':').then(|| content[1..end].to_string());
+    }
+    let end = content.find(": ").or_else(|| content.strip_suffix(':').map(str::len))?;
+    Some(content[..end].trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        describe_settings, system_config_dir_for, user_config_dir_for, ConfigSource, LoadedConfig,
+    };
+    use crate::editor::keymap::Keymap;
+    use crate::editor::settings::{BackupPolicy, Settings};
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn keybinding_errors_point_at_the_yaml_key() {
+        let text = "theme:\n  text_color: \"#000\"\nkeybindings:\n  global:\n    \"C-x C-g\": widen\n    C-h: null\n  markdown:\n    \"C-c C-p\": markdown_promote_heading\n";
+        let source = ConfigSource { origin: "config.yaml", text };
+        let bindings = source.keybindings(&source.parse().unwrap(), &mut Keymap::default()).unwrap();
+        assert_eq!(bindings.len(), 3);
+        assert!(bindings.iter().any(|b| b.keys == "C-h" && b.command.is_none()));
+
+        let unknown = "keybindings:\n  global:\n    C-a: widen\n    C-z: no_such_command\n";
+        let source = ConfigSource { origin: "config.yaml", text: unknown };
+        assert_eq!(
+            source.keybindings(&source.parse().unwrap(), &mut Keymap::default()).unwrap_err(),
+            "config.yaml:4:5: unknown command: no_such_command"
+        );
+
+        let conflict = "keybindings:\n  global:\n\n    # shadows C-x 2\n    C-x: widen\n";
+        let source = ConfigSource { origin: "config.yaml", text: conflict };
+        let err = source.keybindings(&source.parse().unwrap(), &mut Keymap::default()).unwrap_err();
+        assert!(err.starts_with("config.yaml:5:5: `C-x` conflicts with `C-x 2`"), "{err}");
+
+        let mode = "keybindings:\n  nosuch:\n    C-a: widen\n";
+        let source = ConfigSource { origin: "config.yaml", text: mode };
+        assert!(source.keybindings(&source.parse().unwrap(), &mut Keymap::default()).unwrap_err().starts_with("config.yaml:2:3: unknown keymap"));
+    }
+
+    #[test]
+    fn settings_are_validated_and_described_with_their_source() {
+        let text = "version: 1\neditor:\n  tab_width: 8\nfiles:\n  line_ending: lf\n  backup: numbered\nsearch:\n  case_fold: true\n";
+        let source = ConfigSource { origin: "config.yaml", text };
+        let (mut settings, mut sources) = (Settings::default(), BTreeMap::new());
+        source.settings(&source.parse().unwrap(), &mut settings, &mut sources).unwrap();
+        assert_eq!(settings.tab_width, Some(8));
+        assert_eq!(settings.line_ending, "LF");
+        assert_eq!(settings.backup, BackupPolicy::Numbered);
+        assert!(settings.case_fold);
+
+        let described = describe_settings(&settings, &sources);
+        let fill = described.iter().find(|s| s.name == "editor.fill_column").unwrap();
+        assert_eq!((fill.value.as_u64(), fill.source.as_str()), (Some(70), "default"));
+        let tab = described.iter().find(|s| s.name == "editor.tab_width").unwrap();
+        assert_eq!((tab.value.as_u64(), tab.source.as_str()), (Some(8), "config.yaml"));
+
+        let errors = [
+            ("editor:\n  fill_column: -3\n", "config.yaml:2:3: `editor.fill_column`: expected a positive integer"),
+            ("files:\n  encoding: ebcdic\n", "config.yaml:2:3: `files.encoding`: unknown encoding `ebcdic`"),
+            ("search:\n  case_fold: true\n  regexp: true\n", "config.yaml:3:3: unknown setting `search.regexp`"),
+            ("version: 2\n", "config.yaml:1:1: unsupported config version 2; expected 1"),
+            ("theme: {}\nwindows:\n  split: 2\n", "config.yaml:2:1: unknown section `windows`"),
+        ];
+        for (text, expected) in errors {
+            let source = ConfigSource { origin: "config.yaml", text };
+            let err = source
+                .settings(&source.parse().unwrap(), &mut Settings::default(), &mut BTreeMap::new())
+                .unwrap_err();
+            assert_eq!(err, expected);
+        }
+        let source = ConfigSource { origin: "config.yaml", text: "theme:\n  text_colour: red\n" };
+        let err = LoadedConfig::default().apply(&source).unwrap_err();
+        assert_eq!(err, "config.yaml:2:3: unknown theme key `text_colour`");
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let env = |name: &str| match name {
+            "APPDATA" => Some("C:\\Users\\me\\AppData\\Roaming".to_string()),
+            "HOME" => Some("/home/me".to_string()),
+            "XDG_CONFIG_DIRS" => Some(":/opt/xdg:/etc/xdg".to_string()),
+            _ => None,
+        };
+        assert_eq!(user_config_dir_for("linux", env), Some(PathBuf::from("/home/me/.config/femto")));
+        assert_eq!(
+            user_config_dir_for("macos", env),
+            Some(PathBuf::from("/home/me/Library/Application Support/Femto"))
+        );
+        assert_eq!(
+            user_config_dir_for("windows", env),
+            Some(PathBuf::from("C:\\Users\\me\\AppData\\Roaming").join("Femto"))
+        );
+        let xdg = |name: &str| (name == "XDG_CONFIG_HOME").then(|| "/xdg".to_string());
+        assert_eq!(user_config_dir_for("linux", xdg), Some(PathBuf::from("/xdg/femto")));
+        assert_eq!(system_config_dir_for("linux", env), Some(PathBuf::from("/opt/xdg/femto")));
+
+        let layers = [
+            ("/etc/xdg/femto/config.yaml", "editor:\n  fill_column: 72\ntheme:\n  text_color: black\n  cursor_color: red\n"),
+            ("/home/me/.config/femto/config.yaml", "theme:\n  cursor_color: blue\nkeybindings:\n  global:\n    \"C-z a\": widen\n"),
+            ("/work/app/.femto.yaml", "editor:\n  fill_column: 100\nkeybindings:\n  global:\n    \"C-z a\": null\n    C-z: undo\n"),
+        ];
+        let mut loaded = LoadedConfig::default();
+        for (origin, text) in layers {
+            loaded.apply(&ConfigSource { origin, text }).unwrap();
+        }
+        assert_eq!(loaded.paths, layers.map(|(origin, _)| origin.to_string()));
+        assert_eq!(loaded.settings.fill_column, 100);
+        assert_eq!(loaded.sources["editor.fill_column"], "/work/app/.femto.yaml");
+        assert_eq!(loaded.theme.text_color.as_deref(), Some("black"));
+        assert_eq!(loaded.theme.cursor_color.as_deref(), Some("blue"));
+        assert_eq!(loaded.keymap.keys_for("undo")[..], ["C-/", "C-_", "C-z"]);
+        assert!(loaded.keymap.keys_for("widen").iter().all(|keys| keys != "C-z a"));
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use tauri::{AppHandle, Manager};
+
+use crate::commands::{self, AppConfigResponse};
+use crate::config;
+use crate::editor::state::EditorState;
+
+/// How often the config files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Modification time and size of a file, or `None` when it does not exist.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Stamps of the config files for `project_dir`.
+fn stamps(project_dir: Option<&Path>) -> Vec<(PathBuf, Stamp)> {
+    config::watched_paths(project_dir)
+        .into_iter()
+        .map(|path| {
+            let stamp = stamp(&path);
+            (path, stamp)
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct Watched {
+    /// Directory the project layer was resolved from.
+    project_dir: Option<PathBuf>,
+    stamps: Vec<(PathBuf, Stamp)>,
+    /// Last config applied, to tell what a reload changed.
+    applied: Option<AppConfigResponse>,
+}
+
+/// Config files that can change the effective config, polled by `spawn`.
+#[derive(Debug, Default)]
+pub struct ConfigWatch {
+    watched: Mutex<Watched>,
+}
+
+impl ConfigWatch {
+    /// Starts watching the config files for `project_dir`, as they are now.
+    pub fn watch(&self, project_dir: Option<PathBuf>, applied: AppConfigResponse) {
+        let stamps = stamps(project_dir.as_deref());
+        if let Ok(mut watched) = self.watched.lock() {
+            *watched = Watched {
+                project_dir,
+                stamps,
+                applied: Some(applied),
+            };
+        }
+    }
+
+    /// The project directory to reload for when a watched file was created,
+    /// changed or removed since the last check. The paths are listed afresh
+    /// each time so theme files added later are picked up too.
+    fn poll(&self) -> Option<Option<PathBuf>> {
+        let mut watched = self.watched.lock().ok()?;
+        let stamps = stamps(watched.project_dir.as_deref());
+        let changed = stamps != watched.stamps;
+        watched.stamps = stamps;
+        changed.then(|| watched.project_dir.clone())
+    }
+
+    /// The config applied last, if `project_dir` resolves to the same project layer
+    /// as the watched directory, so visiting another file of the project reloads nothing.
+    pub fn applied_for(&self, project_dir: Option<&Path>) -> Option<AppConfigResponse> {
+        let watched = self.watched.lock().ok()?;
+        let layer = |dir: Option<&Path>| dir.and_then(config::find_project_config);
+        if layer(watched.project_dir.as_deref()) != layer(project_dir) {
+            return None;
+        }
+        watched.applied.clone()
+    }
+
+    /// Records a newly applied config and returns the previous one.
+    pub fn replace_applied(&self, applied: AppConfigResponse) -> Option<AppConfigResponse> {
+        self.watched.lock().ok()?.applied.replace(applied)
+    }
+}
+
+/// Polls the watched config files on a background thread and reloads the
+/// config when one of them changes.
+pub fn spawn(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let watch = app.state::<ConfigWatch>();
+        if let Some(project_dir) = watch.poll() {
+            let state = app.state::<Mutex<EditorState>>();
+            commands::reload_app_config(&app, &state, &watch, project_dir);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigWatch;
+    use crate::commands::AppConfigResponse;
+
+    #[test]
+    fn creating_a_project_config_triggers_a_reload_once() {
+        let base = std::env::temp_dir().join(format!("femto-config-watch-{}", std::process::id()));
+        let nested = base.join("project").join("src");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let watch = ConfigWatch::default();
+        watch.watch(Some(nested.clone()), AppConfigResponse::default());
+        assert_eq!(watch.poll(), None);
+
+        std::fs::write(base.join("project").join(".femto.yaml"), "editor:\n  fill_column: 80\n").unwrap();
+        assert_eq!(watch.poll(), Some(Some(nested.clone())));
+        assert_eq!(watch.poll(), None);
+
+        std::fs::remove_file(base.join("project").join(".femto.yaml")).unwrap();
+        assert_eq!(watch.poll(), Some(Some(nested)));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::editor::dired::{DiredEntry, DiredListing, DiredMark};
+
+pub fn read_listing(directory: &Path) -> Result<DiredListing, String> {
+    let entries = fs::read_dir(directory)
+        .map_err(|err| format!("failed to read directory {}: {err}", directory.display()))?;
+
+    let mut listing = Vec::new();
+    if directory.parent().is_some() {
+        listing.push(DiredEntry {
+            name: "..".to_string(),
+            is_dir: true,
+            is_symlink: false,
+            size: 0,
+            modified: None,
+            mark: DiredMark::None,
+        });
+    }
+
+    for entry in entries.flatten() {
+        let is_symlink = entry
+            .file_type()
+            .map(|t| t.is_symlink())
+            .unwrap_or(false);
+        // Follow symlinks so a link to a directory can be descended into.
+        let metadata = fs::metadata(entry.path()).or_else(|_| entry.metadata());
+        let (is_dir, size, modified) = match metadata {
+            Ok(meta) => (
+                meta.is_dir(),
+                meta.len(),
+                meta.modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs()),
+            ),
+            Err(_) => (false, 0, None),
+        };
+        listing.push(DiredEntry {
+            name: entry.file_name().to_string_lossy().to_string(),
+            is_dir,
+            is_symlink,
+            size,
+            modified,
+            mark: DiredMark::None,
+        });
+    }
+
+    Ok(DiredListing::new(directory.to_path_buf(), listing))
+}
+
+pub fn delete_path(path: &Path) -> Result<(), String> {
+    let result = if fs::symlink_metadata(path).map(|m| m.is_dir()).unwrap_or(false) {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    result.map_err(|err| format!("failed to delete {}: {err}", path.display()))
+}
+
+pub fn rename_path(from: &Path, to: &Path) -> Result<(), String> {
+    if to.exists() {
+        return Err(format!("{} already exists", to.display()));
+    }
+    fs::rename(from, to)
+        .map_err(|err| format!("failed to rename {}: {err}", from.display()))
+}
+
+/// Applies wdired renames as a batch. Every file is first moved to a
+/// temporary name, so swaps and rotations (`a` -> `b`, `b` -> `a`) work.
+pub fn rename_paths(renames: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    for (_, to) in renames {
+        let freed = renames.iter().any(|(from, _)| from == to);
+        if !freed && fs::symlink_metadata(to).is_ok() {
+            return Err(format!("{} already exists", to.display()));
+        }
+    }
+    let temporary = |from: &Path, i: usize| {
+        from.with_file_name(format!(".femto-rename-{}-{}", std::process::id(), i))
+    };
+    for (i, (from, _)) in renames.iter().enumerate() {
+        if let Err(err) = fs::rename(from, temporary(from, i)) {
+            for (j, (from, _)) in renames.iter().enumerate().take(i) {
+                let _ = fs::rename(temporary(from, j), from);
+            }
+            return Err(format!("failed to rename {}: {err}", from.display()));
+        }
+    }
+    for (i, (from, to)) in renames.iter().enumerate() {
+        fs::rename(temporary(from, i), to)
+            .map_err(|err| format!("failed to rename {}: {err}", from.display()))?;
+    }
+    Ok(())
+}
+
+/// Copies a file, a symlink (as a link) or a directory tree. A directory
+/// cannot be copied into itself.
+pub fn copy_path(from: &Path, to: &Path) -> Result<(), String> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(format!("{} already exists", to.display()));
+    }
+    let is_dir = fs::symlink_metadata(from).map(|m| m.is_dir()).unwrap_or(false);
+    if is_dir {
+        let source = fs::canonicalize(from)
+            .map_err(|err| format!("failed to copy {}: {err}", from.display()))?;
+        let target_parent = to
+            .parent()
+            .and_then(|parent| fs::canonicalize(parent).ok())
+            .unwrap_or_default();
+        if target_parent.starts_with(&source) {
+            return Err(format!("cannot copy {} into itself", from.display()));
+        }
+    }
+    copy_tree(from, to)
+}
+
+fn copy_tree(from: &Path, to: &Path) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(from)
+        .map_err(|err| format!("failed to copy {}: {err}", from.display()))?;
+    if metadata.file_type().is_symlink() {
+        let link = fs::read_link(from)
+            .map_err(|err| format!("failed to read link {}: {err}", from.display()))?;
+        return symlink(&link, to, fs::metadata(from).map(|m| m.is_dir()).unwrap_or(false))
+            .map_err(|err| format!("failed to copy {}: {err}", from.display()));
+    }
+    if metadata.is_dir() {
+        fs::create_dir_all(to)
+            .map_err(|err| format!("failed to create {}: {err}", to.display()))?;
+        let entries = fs::read_dir(from)
+            .map_err(|err| format!("failed to read directory {}: {err}", from.display()))?;
+        for entry in entries.flatten() {
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to)
+            .map(|_| ())
+            .map_err(|err| format!("failed to copy {}: {err}", from.display()))
+    }
+}
+
+#[cfg(unix)]
+fn symlink(link: &Path, to: &Path, _is_dir: bool) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(link, to)
+}
+
+#[cfg(windows)]
+fn symlink(link: &Path, to: &Path, is_dir: bool) -> std::io::Result<()> {
+    if is_dir {
+        std::os::windows::fs::symlink_dir(link, to)
+    } else {
+        std::os::windows::fs::symlink_file(link, to)
+    }
+}
+
+pub fn create_directory(path: &Path) -> Result<(), String> {
+    fs::create_dir_all(path)
+        .map_err(|err| format!("failed to create directory {}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{copy_path, rename_paths};
+    use std::fs;
+
+    #[test]
+    fn copies_refuse_recursion_and_renames_can_swap() {
+        let dir = std::env::temp_dir().join(format!("femto-dired-{}", std::process::id()));
+        let src = dir.join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("a"), "A").unwrap();
+        fs::write(src.join("b"), "B").unwrap();
+
+        let err = copy_path(&src, &src.join("nested")).unwrap_err();
+        assert!(err.contains("into itself"), "{err}");
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&dir, src.join("up")).unwrap();
+            copy_path(&src, &dir.join("copy")).unwrap();
+            assert!(fs::symlink_metadata(dir.join("copy/up")).unwrap().file_type().is_symlink());
+        }
+
+        rename_paths(&[(src.join("a"), src.join("b")), (src.join("b"), src.join("a"))]).unwrap();
+        assert_eq!(fs::read_to_string(src.join("a")).unwrap(), "B");
+        assert_eq!(fs::read_to_string(src.join("b")).unwrap(), "A");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
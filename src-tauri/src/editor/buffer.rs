@@ -1,39 +1,216 @@
-#[derive(Debug, Clone)]
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+/// Buffer text. Indirect buffers hold further handles to the same text (see `share`),
+/// so an edit through any handle is visible through all of them.
+///
+/// Every edit is also journaled so that positions kept outside the text (other buffers'
+/// points, narrowing bounds, window points) can be moved like markers; see `take_edits`.
+#[derive(Debug)]
 pub struct TextBuffer {
-    text: String,
+    text: Arc<RwLock<String>>,
+    journal: Arc<Mutex<Journal>>,
+    handle: u64,
+}
+
+/// One change to the text, in character positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub removed: usize,
+    pub inserted: usize,
+    /// Whether the edit was made through the handle that took it.
+    pub own: bool,
+}
+
+impl TextEdit {
+    /// Where `pos`, a position from before the edit, is afterwards. A position at the
+    /// insertion point stays before the inserted text unless `advance` is set.
+    pub fn adjust(&self, pos: usize, advance: bool) -> usize {
+        let end = self.start + self.removed;
+        if pos < self.start || (pos == self.start && !advance) {
+            pos
+        } else if pos >= end {
+            pos + self.inserted - self.removed
+        } else if advance {
+            self.start + self.inserted
+        } else {
+            self.start
+        }
+    }
+
+    /// The change in text length.
+    pub fn delta(&self) -> isize {
+        self.inserted as isize - self.removed as isize
+    }
+}
+
+#[derive(Debug, Default)]
+struct Journal {
+    /// Sequence number of `edits[0]`.
+    first: u64,
+    /// Edits with the handle that made them.
+    edits: Vec<(u64, TextEdit)>,
+    /// The next sequence number each live handle has not taken yet.
+    readers: HashMap<u64, u64>,
+}
+
+impl Journal {
+    fn end(&self) -> u64 {
+        self.first + self.edits.len() as u64
+    }
+
+    fn register(&mut self) -> u64 {
+        let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+        let end = self.end();
+        self.readers.insert(handle, end);
+        handle
+    }
+
+    fn record(&mut self, handle: u64, edit: TextEdit) {
+        self.edits.push((handle, edit));
+    }
+
+    /// Drops edits every handle has taken.
+    fn prune(&mut self) {
+        let oldest = self.readers.values().copied().min().unwrap_or(self.end());
+        let done = (oldest - self.first) as usize;
+        self.edits.drain(..done);
+        self.first = oldest;
+    }
 }
 
 impl TextBuffer {
     pub fn new() -> Self {
+        Self::from_text(String::new())
+    }
+
+    pub fn from_text(text: String) -> Self {
+        let mut journal = Journal::default();
+        let handle = journal.register();
         Self {
-            text: String::new(),
+            text: Arc::new(RwLock::new(text)),
+            journal: Arc::new(Mutex::new(journal)),
+            handle,
         }
     }
 
-    pub fn from_text(text: String) -> Self {
-        Self { text }
+    /// Another handle to the same text.
+    pub fn share(&self) -> Self {
+        Self {
+            text: Arc::clone(&self.text),
+            journal: Arc::clone(&self.journal),
+            handle: self.journal().register(),
+        }
+    }
+
+    /// The edits made through any handle since this handle last took them, oldest first.
+    pub fn take_edits(&mut self) -> Vec<TextEdit> {
+        let mut journal = self.journal();
+        let end = journal.end();
+        let seen = journal.readers.insert(self.handle, end).unwrap_or(end);
+        let edits = journal.edits[(seen - journal.first) as usize..]
+            .iter()
+            .map(|&(handle, edit)| TextEdit {
+                own: handle == self.handle,
+                ..edit
+            })
+            .collect();
+        journal.prune();
+        edits
+    }
+
+    pub fn shares_text_with(&self, other: &TextBuffer) -> bool {
+        Arc::ptr_eq(&self.text, &other.text)
+    }
+
+    /// Read access to the text. Do not hold the guard across a write to the same buffer.
+    pub fn as_str(&self) -> RwLockReadGuard<'_, String> {
+        self.text.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// A copy of the whole text.
+    pub fn text(&self) -> String {
+        self.as_str().clone()
     }
 
-    pub fn as_str(&self) -> &str {
-        &self.text
+    /// Replaces the text in place, keeping it shared with other handles. The journal gets
+    /// the smallest edit that turns the old text into the new one.
+    pub fn set_text(&mut self, text: String) {
+        let mut current = self.text_mut();
+        let prefix = current
+            .chars()
+            .zip(text.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let (old_len, new_len) = (current.chars().count(), text.chars().count());
+        let suffix = current
+            .chars()
+            .rev()
+            .zip(text.chars().rev())
+            .take(old_len.min(new_len) - prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        *current = text;
+        drop(current);
+        self.record(prefix, old_len - prefix - suffix, new_len - prefix - suffix);
     }
 
     pub fn char_len(&self) -> usize {
-        self.text.chars().count()
+        self.as_str().chars().count()
     }
 
     pub fn insert_str(&mut self, index: usize, s: &str) {
-        let byte = char_to_byte_index(&self.text, index);
-        self.text.insert_str(byte, s);
+        let mut text = self.text_mut();
+        let byte = char_to_byte_index(&text, index);
+        text.insert_str(byte, s);
+        drop(text);
+        self.record(index, 0, s.chars().count());
     }
 
     pub fn remove_range(&mut self, start: usize, end: usize) {
         if start >= end {
             return;
         }
-        let start_byte = char_to_byte_index(&self.text, start);
-        let end_byte = char_to_byte_index(&self.text, end);
-        self.text.replace_range(start_byte..end_byte, "");
+        let mut text = self.text_mut();
+        let start_byte = char_to_byte_index(&text, start);
+        let end_byte = char_to_byte_index(&text, end);
+        let removed = text[start_byte..end_byte].chars().count();
+        text.replace_range(start_byte..end_byte, "");
+        drop(text);
+        self.record(start, removed, 0);
+    }
+
+    fn record(&self, start: usize, removed: usize, inserted: usize) {
+        if removed == 0 && inserted == 0 {
+            return;
+        }
+        let edit = TextEdit {
+            start,
+            removed,
+            inserted,
+            own: false,
+        };
+        self.journal().record(self.handle, edit);
+    }
+
+    fn text_mut(&self) -> RwLockWriteGuard<'_, String> {
+        self.text.write().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn journal(&self) -> MutexGuard<'_, Journal> {
+        self.journal.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Drop for TextBuffer {
+    fn drop(&mut self) {
+        let mut journal = self.journal();
+        journal.readers.remove(&self.handle);
+        journal.prune();
     }
 }
 
@@ -0,0 +1,530 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::editor::edit::{CaseChange, TransposeUnit};
+use crate::editor::prefix_arg::{repeat_text, PrefixArg};
+use crate::editor::state::EditorState;
+use crate::editor::window::SplitDirection;
+
+/// Type of a command argument's JSON value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArgKind {
+    String,
+    /// A non-negative integer, such as a character offset.
+    Natural,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArgSpec {
+    /// Key in the payload object.
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+    pub doc: &'static str,
+}
+
+type CommandFn = fn(&mut EditorState, &CommandArgs) -> Result<(), String>;
+
+/// A named editor command, like an Emacs interactive function.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub args: &'static [ArgSpec],
+    #[serde(skip)]
+    run: CommandFn,
+}
+
+/// A command's payload after checking it against the command's `args`.
+#[derive(Debug)]
+pub struct CommandArgs {
+    command: &'static str,
+    values: Map<String, Value>,
+    pub prefix_arg: Option<PrefixArg>,
+}
+
+impl CommandArgs {
+    fn value(&self, name: &str) -> Result<&Value, String> {
+        self.values
+            .get(name)
+            .ok_or_else(|| format!("{}: missing argument `{}`", self.command, name))
+    }
+
+    pub fn string(&self, name: &str) -> Result<&str, String> {
+        let value = self.value(name)?;
+        value.as_str().ok_or_else(|| wrong_type(self.command, name, ArgKind::String))
+    }
+
+    pub fn natural(&self, name: &str) -> Result<usize, String> {
+        let value = self.value(name)?;
+        as_natural(value).ok_or_else(|| wrong_type(self.command, name, ArgKind::Natural))
+    }
+
+    pub fn optional_natural(&self, name: &str) -> Result<Option<usize>, String> {
+        if self.values.contains_key(name) {
+            self.natural(name).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Numeric value of the prefix argument; 1 without one.
+    pub fn count(&self) -> i64 {
+        self.prefix_arg.as_ref().map_or(1, PrefixArg::value)
+    }
+
+    fn region(&self) -> Result<(usize, usize), String> {
+        Ok((self.natural("start")?, self.natural("end")?))
+    }
+}
+
+fn as_natural(value: &Value) -> Option<usize> {
+    value.as_u64().and_then(|n| usize::try_from(n).ok())
+}
+
+fn wrong_type(command: &str, name: &str, kind: ArgKind) -> String {
+    let expected = match kind {
+        ArgKind::String => "a string",
+        ArgKind::Natural => "a non-negative integer",
+    };
+    format!("{}: argument `{}` must be {}", command, name, expected)
+}
+
+impl CommandSpec {
+    /// Checks `payload` against the argument schema. `prefixArg` is accepted
+    /// by every command.
+    pub fn parse_args(&self, payload: Option<Value>) -> Result<CommandArgs, String> {
+        let mut values = match payload {
+            None | Some(Value::Null) => Map::new(),
+            Some(Value::Object(values)) => values,
+            Some(_) => return Err(format!("{}: arguments must be an object", self.name)),
+        };
+        let prefix_arg = values
+            .remove("prefixArg")
+            .map(serde_json::from_value::<PrefixArg>)
+            .transpose()
+            .map_err(|_| {
+                format!(
+                    "{}: argument `prefixArg` must be a number, a one-element list or \"-\"",
+                    self.name
+                )
+            })?;
+
+        if let Some(unknown) = values.keys().find(|key| !self.args.iter().any(|arg| arg.name == *key)) {
+            return Err(format!("{}: unknown argument `{}`", self.name, unknown));
+        }
+        for arg in self.args {
+            match values.get(arg.name) {
+                None if arg.required => {
+                    return Err(format!("{}: missing argument `{}` ({})", self.name, arg.name, arg.doc))
+                }
+                None => {}
+                Some(value) => {
+                    let valid = match arg.kind {
+                        ArgKind::String => value.is_string(),
+                        ArgKind::Natural => as_natural(value).is_some(),
+                    };
+                    if !valid {
+                        return Err(wrong_type(self.name, arg.name, arg.kind));
+                    }
+                }
+            }
+        }
+        Ok(CommandArgs {
+            command: self.name,
+            values,
+            prefix_arg,
+        })
+    }
+}
+
+const fn arg(name: &'static str, kind: ArgKind, doc: &'static str) -> ArgSpec {
+    ArgSpec {
+        name,
+        kind,
+        required: true,
+        doc,
+    }
+}
+
+const fn optional_arg(name: &'static str, kind: ArgKind, doc: &'static str) -> ArgSpec {
+    ArgSpec {
+        name,
+        kind,
+        required: false,
+        doc,
+    }
+}
+
+const fn command(
+    name: &'static str,
+    doc: &'static str,
+    args: &'static [ArgSpec],
+    run: CommandFn,
+) -> CommandSpec {
+    CommandSpec { name, doc, args, run }
+}
+
+const NO_ARGS: &[ArgSpec] = &[];
+const REGION_ARGS: &[ArgSpec] = &[
+    arg("start", ArgKind::Natural, "region start offset"),
+    arg("end", ArgKind::Natural, "region end offset"),
+];
+const OPTIONAL_REGION_ARGS: &[ArgSpec] = &[
+    optional_arg("start", ArgKind::Natural, "region start offset"),
+    optional_arg("end", ArgKind::Natural, "region end offset"),
+];
+const QUERY_ARGS: &[ArgSpec] = &[arg("query", ArgKind::String, "text to search for")];
+const MODE_ARGS: &[ArgSpec] = &[arg("mode", ArgKind::String, "mode name")];
+/// Window commands carry the selected window's scroll position so it survives the switch.
+const WINDOW_ARGS: &[ArgSpec] = &[optional_arg(
+    "scrollLine",
+    ArgKind::Natural,
+    "first visible line of the selected window",
+)];
+
+fn set_scroll(editor: &mut EditorState, args: &CommandArgs) -> Result<(), String> {
+    if let Some(line) = args.optional_natural("scrollLine")? {
+        editor.set_window_scroll(line);
+    }
+    Ok(())
+}
+
+fn toggle_minor_mode(editor: &mut EditorState, args: &CommandArgs, enable: Option<bool>) -> Result<(), String> {
+    let mode = args.string("mode")?;
+    editor.current_mut().minor_mode_command(mode, enable)
+}
+
+static COMMANDS: &[CommandSpec] = &[
+    command("noop", "Do nothing; returns a fresh snapshot.", NO_ARGS, |_, _| Ok(())),
+    command("keyboard_quit", "Cancel query-replace and keyboard macro definition.", NO_ARGS, |e, _| {
+        e.current_mut().end_query_replace();
+        e.current_mut().set_status_message(Some("Quit".to_string()));
+        e.cancel_kbd_macro();
+        Ok(())
+    }),
+    command("move_to_line_start", "Move to the beginning of the line; with N, move N - 1 lines first.", NO_ARGS, |e, a| {
+        let buf = e.current_mut();
+        buf.move_lines(a.count() - 1);
+        buf.move_to_line_start();
+        Ok(())
+    }),
+    command("move_to_line_end", "Move to the end of the line; with N, move N - 1 lines first.", NO_ARGS, |e, a| {
+        let buf = e.current_mut();
+        buf.move_lines(a.count() - 1);
+        buf.move_to_line_end();
+        Ok(())
+    }),
+    command("move_forward", "Move forward N characters.", NO_ARGS, |e, a| {
+        e.current_mut().move_chars(a.count());
+        Ok(())
+    }),
+    command("move_backward", "Move backward N characters.", NO_ARGS, |e, a| {
+        e.current_mut().move_chars(-a.count());
+        Ok(())
+    }),
+    command("move_next_line", "Move down N lines, keeping the column.", NO_ARGS, |e, a| {
+        e.current_mut().move_lines(a.count());
+        Ok(())
+    }),
+    command("move_previous_line", "Move up N lines, keeping the column.", NO_ARGS, |e, a| {
+        e.current_mut().move_lines(-a.count());
+        Ok(())
+    }),
+    command("move_forward_word", "Move forward N words.", NO_ARGS, |e, a| {
+        e.current_mut().move_words(a.count());
+        Ok(())
+    }),
+    command("move_backward_word", "Move backward N words.", NO_ARGS, |e, a| {
+        e.current_mut().move_words(-a.count());
+        Ok(())
+    }),
+    command("move_to_buffer_start", "Move to the beginning of the buffer; with N, N/10 of the way in.", NO_ARGS, |e, a| {
+        match &a.prefix_arg {
+            Some(prefix) => e.current_mut().move_to_buffer_fraction(prefix.value(), false),
+            None => e.current_mut().move_to_buffer_start(),
+        }
+        Ok(())
+    }),
+    command("move_to_buffer_end", "Move to the end of the buffer; with N, N/10 of the way from it.", NO_ARGS, |e, a| {
+        match &a.prefix_arg {
+            Some(prefix) => e.current_mut().move_to_buffer_fraction(prefix.value(), true),
+            None => e.current_mut().move_to_buffer_end(),
+        }
+        Ok(())
+    }),
+    command("delete_char", "Delete N characters after point; with an argument, kill them.", NO_ARGS, |e, a| {
+        let kill = a.prefix_arg.is_some();
+        let (buf, kr) = e.current_and_kill_ring();
+        buf.delete_chars(a.count(), kill.then_some(kr))
+    }),
+    command("delete_backward_char", "Delete N characters before point; with an argument, kill them.", NO_ARGS, |e, a| {
+        let kill = a.prefix_arg.is_some();
+        let (buf, kr) = e.current_and_kill_ring();
+        buf.delete_chars(-a.count(), kill.then_some(kr))
+    }),
+    command("kill_line", "Kill the rest of the line; with N, kill N lines (backward when negative).", NO_ARGS, |e, a| {
+        let (buf, kr) = e.current_and_kill_ring();
+        match &a.prefix_arg {
+            Some(prefix) => buf.kill_lines(prefix.value(), kr),
+            None => buf.kill_line(kr),
+        }
+    }),
+    command("yank", "Insert the last kill; with N, the Nth most recent; with C-u, leave point before it.", NO_ARGS, |e, a| {
+        let kr = e.kill_ring.clone();
+        match &a.prefix_arg {
+            Some(prefix) if prefix.is_universal() => e.current_mut().yank_nth(&kr, 0, true),
+            Some(prefix) => e.current_mut().yank_nth(&kr, prefix.value() - 1, false),
+            None => e.current_mut().yank(&kr),
+        }
+    }),
+    command("undo", "Undo the last N changes.", NO_ARGS, |e, a| {
+        e.current().check_writable()?;
+        for _ in 0..a.count().max(1) {
+            e.current_mut().undo();
+        }
+        Ok(())
+    }),
+    command("redo", "Redo the last N undone changes.", NO_ARGS, |e, a| {
+        e.current().check_writable()?;
+        for _ in 0..a.count().max(1) {
+            e.current_mut().redo();
+        }
+        Ok(())
+    }),
+    command("kill_region", "Kill the text between start and end.", REGION_ARGS, |e, a| {
+        let (start, end) = a.region()?;
+        let (buf, kr) = e.current_and_kill_ring();
+        buf.kill_region(start, end, kr)
+    }),
+    command("copy_region", "Save the text between start and end in the kill ring.", REGION_ARGS, |e, a| {
+        let (start, end) = a.region()?;
+        let (buf, kr) = e.current_and_kill_ring();
+        buf.copy_region(start, end, kr);
+        Ok(())
+    }),
+    command("transpose_chars", "Swap the characters around point; with N, drag the previous one N forward.", NO_ARGS, |e, a| {
+        let count = a.prefix_arg.as_ref().map(PrefixArg::value);
+        e.current_mut().transpose_chars(count)
+    }),
+    command("transpose_words", "Swap the words around point; with N, drag the previous one N words forward.", NO_ARGS, |e, a| {
+        e.current_mut().transpose(TransposeUnit::Word, a.count())
+    }),
+    command("transpose_lines", "Swap the current and previous lines; with N, drag the previous one N lines down.", NO_ARGS, |e, a| {
+        e.current_mut().transpose(TransposeUnit::Line, a.count())
+    }),
+    command("upcase_word", "Convert the next N words to upper case; backward when negative.", NO_ARGS, |e, a| {
+        e.current_mut().change_word_case(CaseChange::Upcase, a.count())
+    }),
+    command("downcase_word", "Convert the next N words to lower case; backward when negative.", NO_ARGS, |e, a| {
+        e.current_mut().change_word_case(CaseChange::Downcase, a.count())
+    }),
+    command("capitalize_word", "Capitalize the next N words; backward when negative.", NO_ARGS, |e, a| {
+        e.current_mut().change_word_case(CaseChange::Capitalize, a.count())
+    }),
+    command("kill_word", "Kill to the end of the Nth word forward.", NO_ARGS, |e, a| {
+        let (buf, kr) = e.current_and_kill_ring();
+        buf.kill_words(a.count(), kr)
+    }),
+    command("backward_kill_word", "Kill back to the start of the Nth word backward.", NO_ARGS, |e, a| {
+        let (buf, kr) = e.current_and_kill_ring();
+        buf.kill_words(-a.count(), kr)
+    }),
+    command("delete_horizontal_space", "Delete spaces and tabs around point; with an argument, only before it.", NO_ARGS, |e, a| {
+        e.current_mut().delete_horizontal_space(a.prefix_arg.is_some())
+    }),
+    command("just_one_space", "Leave N spaces around point; negative N also deletes newlines.", NO_ARGS, |e, a| {
+        e.current_mut().just_one_space(a.count())
+    }),
+    command("open_line", "Insert N newlines after point without moving.", NO_ARGS, |e, a| {
+        e.current_mut().open_line(a.count())
+    }),
+    command("delete_blank_lines", "Delete blank lines around point, leaving one, or those after a nonblank line.", NO_ARGS, |e, _| {
+        e.current_mut().delete_blank_lines()
+    }),
+    command("isearch_forward", "Search forward from point.", QUERY_ARGS, |e, a| {
+        let case_fold = e.settings.case_fold;
+        e.current_mut().isearch_forward(a.string("query")?, case_fold)
+    }),
+    command("isearch_backward", "Search backward from point.", QUERY_ARGS, |e, a| {
+        let case_fold = e.settings.case_fold;
+        e.current_mut().isearch_backward(a.string("query")?, case_fold)
+    }),
+    command(
+        "set_cursor",
+        "Move point to an offset.",
+        &[arg("cursor", ArgKind::Natural, "character offset")],
+        |e, a| {
+            e.current_mut().set_cursor(a.natural("cursor")?);
+            Ok(())
+        },
+    ),
+    command(
+        "insert_text",
+        "Insert text at point, N times.",
+        &[arg("text", ArgKind::String, "text to insert")],
+        |e, a| {
+            let count = a.count();
+            let times = u64::try_from(count).map_err(|_| format!("Negative repetition argument {}", count))?;
+            let text = repeat_text(a.string("text")?, times)?;
+            e.current_mut().insert_text(&text)
+        },
+    ),
+    command("split_window_below", "Split the selected window into two stacked windows.", WINDOW_ARGS, |e, a| {
+        set_scroll(e, a)?;
+        e.split_window(SplitDirection::Vertical);
+        Ok(())
+    }),
+    command("split_window_right", "Split the selected window into two side-by-side windows.", WINDOW_ARGS, |e, a| {
+        set_scroll(e, a)?;
+        e.split_window(SplitDirection::Horizontal);
+        Ok(())
+    }),
+    command("other_window", "Select the Nth next window.", WINDOW_ARGS, |e, a| {
+        set_scroll(e, a)?;
+        e.other_window(a.count() as isize);
+        Ok(())
+    }),
+    command("delete_window", "Close the selected window.", WINDOW_ARGS, |e, a| {
+        set_scroll(e, a)?;
+        e.delete_window()
+    }),
+    command("delete_other_windows", "Make the selected window fill the frame.", WINDOW_ARGS, |e, a| {
+        set_scroll(e, a)?;
+        e.delete_other_windows();
+        Ok(())
+    }),
+    command("clone_indirect_buffer", "Create an indirect buffer sharing this buffer's text.", NO_ARGS, |e, _| {
+        e.clone_indirect_buffer()
+    }),
+    command(
+        "clone_indirect_buffer_other_window",
+        "Create an indirect buffer and show it in another window.",
+        WINDOW_ARGS,
+        |e, a| {
+            set_scroll(e, a)?;
+            e.clone_indirect_buffer_other_window()
+        },
+    ),
+    command("narrow_to_region", "Restrict editing to the text between start and end.", REGION_ARGS, |e, a| {
+        let (start, end) = a.region()?;
+        e.current_mut().narrow_to_region(start, end)
+    }),
+    command("narrow_to_page", "Restrict editing to the current page.", NO_ARGS, |e, _| {
+        e.current_mut().narrow_to_page()
+    }),
+    command("widen", "Remove narrowing.", NO_ARGS, |e, _| {
+        e.current_mut().widen();
+        Ok(())
+    }),
+    command("set_major_mode", "Switch the buffer's major mode.", MODE_ARGS, |e, a| {
+        e.current_mut().set_major_mode(a.string("mode")?)
+    }),
+    command(
+        "comment_line",
+        "Comment or uncomment the region's lines, or the current line.",
+        OPTIONAL_REGION_ARGS,
+        |e, a| {
+            let region = match (a.optional_natural("start")?, a.optional_natural("end")?) {
+                (Some(start), Some(end)) => Some((start, end)),
+                (None, None) => None,
+                _ => return Err("comment_line: `start` and `end` must be given together".to_string()),
+            };
+            e.current_mut().comment_line(region)
+        },
+    ),
+    command("indent_line", "Indent the current line for the major mode.", NO_ARGS, |e, _| {
+        e.current_mut().indent_line()
+    }),
+    command("enable_minor_mode", "Turn a minor mode on.", MODE_ARGS, |e, a| {
+        toggle_minor_mode(e, a, Some(true))
+    }),
+    command("disable_minor_mode", "Turn a minor mode off.", MODE_ARGS, |e, a| {
+        toggle_minor_mode(e, a, Some(false))
+    }),
+    command("toggle_minor_mode", "Toggle a minor mode.", MODE_ARGS, |e, a| toggle_minor_mode(e, a, None)),
+    command(
+        "set_fill_column",
+        "Set the column auto-fill breaks lines at.",
+        &[arg("fillColumn", ArgKind::Natural, "column number")],
+        |e, a| e.current_mut().set_fill_column(a.natural("fillColumn")?),
+    ),
+    command("kmacro_insert_counter", "Insert the keyboard macro counter and increment it.", NO_ARGS, |e, _| {
+        e.kmacro_insert_counter(1)
+    }),
+    command("markdown_promote_heading", "Remove one `#` from the heading (Markdown mode).", NO_ARGS, |e, _| {
+        e.current_mut().run_mode_command("markdown_promote_heading")
+    }),
+    command("markdown_demote_heading", "Add one `#` to the heading (Markdown mode).", NO_ARGS, |e, _| {
+        e.current_mut().run_mode_command("markdown_demote_heading")
+    }),
+];
+
+/// Every registered command, in registration order.
+pub fn commands() -> &'static [CommandSpec] {
+    COMMANDS
+}
+
+pub fn lookup(name: &str) -> Result<&'static CommandSpec, String> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name == name)
+        .ok_or_else(|| format!("unknown command: {name}"))
+}
+
+impl EditorState {
+    /// Runs a registered command with a JSON payload; shared by
+    /// `editor_command` and keyboard macro playback. With a prefix argument,
+    /// whatever the command changes undoes as one step.
+    pub fn run_command(&mut self, name: &str, payload: Option<Value>) -> Result<(), String> {
+        let spec = lookup(name)?;
+        let args = spec.parse_args(payload)?;
+        let buffer_id = self.current().id;
+        let undo_mark = self.current().undo_stack.mark();
+        let result = (spec.run)(self, &args);
+        if args.prefix_arg.is_some() && self.current().id == buffer_id {
+            self.current_mut().undo_stack.amalgamate(undo_mark);
+        }
+        self.kill_ring.truncate(self.settings.kill_ring_max);
+        self.sync_text_edits();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{commands, lookup};
+    use crate::editor::state::EditorState;
+    use serde_json::json;
+
+    #[test]
+    fn payloads_are_checked_against_the_schema() {
+        assert!(commands().iter().all(|spec| !spec.doc.is_empty()));
+        assert_eq!(lookup("kill_region").unwrap().args[0].name, "start");
+
+        let mut editor = EditorState::new();
+        assert_eq!(
+            editor.run_command("kill_region", Some(json!({ "start": 1 }))).unwrap_err(),
+            "kill_region: missing argument `end` (region end offset)"
+        );
+        assert_eq!(
+            editor.run_command("set_cursor", Some(json!({ "cursor": "3" }))).unwrap_err(),
+            "set_cursor: argument `cursor` must be a non-negative integer"
+        );
+        assert_eq!(
+            editor.run_command("set_cursor", Some(json!({ "start": 1 }))).unwrap_err(),
+            "set_cursor: unknown argument `start`"
+        );
+        assert!(editor.run_command("no_such_command", None).is_err());
+
+        editor
+            .run_command("insert_text", Some(json!({ "text": "ab", "prefixArg": 3 })))
+            .unwrap();
+        assert_eq!(editor.current().buffer.text(), "ababab");
+        editor.run_command("undo", None).unwrap();
+        assert_eq!(editor.current().buffer.text(), "");
+    }
+}
@@ -1,32 +1,35 @@
 use crate::editor::state::BufferState;
 
-fn is_word_char(ch: char) -> bool {
-    ch.is_alphanumeric() || ch == '_'
-}
-
 impl BufferState {
     pub fn move_forward(&mut self) {
-        if self.cursor < self.buffer.char_len() {
+        if self.cursor < self.point_max() {
             self.cursor += 1;
         }
     }
 
     pub fn move_backward(&mut self) {
-        if self.cursor > 0 {
+        if self.cursor > self.point_min() {
             self.cursor -= 1;
         }
     }
 
+    /// Moves `count` characters forward, or backward when negative.
+    pub fn move_chars(&mut self, count: i64) {
+        self.repeat_motion(count, Self::move_forward, Self::move_backward);
+    }
+
     pub fn move_to_line_start(&mut self) {
+        let min = self.point_min();
         let chars: Vec<char> = self.buffer.as_str().chars().collect();
-        while self.cursor > 0 && chars[self.cursor - 1] != '\n' {
+        while self.cursor > min && chars[self.cursor - 1] != '\n' {
             self.cursor -= 1;
         }
     }
 
     pub fn move_to_line_end(&mut self) {
+        let max = self.point_max();
         let chars: Vec<char> = self.buffer.as_str().chars().collect();
-        while self.cursor < chars.len() && chars[self.cursor] != '\n' {
+        while self.cursor < max && chars[self.cursor] != '\n' {
             self.cursor += 1;
         }
     }
@@ -34,8 +37,14 @@ impl BufferState {
     pub fn move_next_line(&mut self) {
         let (line, col) = self.line_col();
         let target_line = line + 1;
+        let Some(line_start) = self.line_col_to_cursor(target_line, 1) else {
+            return;
+        };
+        if line_start > self.point_max() {
+            return;
+        }
         if let Some(cursor) = self.line_col_to_cursor(target_line, col) {
-            self.cursor = cursor;
+            self.cursor = cursor.min(self.point_max());
         }
     }
 
@@ -44,43 +53,89 @@ impl BufferState {
         if line <= 1 {
             return;
         }
+        // On the first accessible line there is nothing above to move to.
+        let line_start = self.line_col_to_cursor(line, 1).unwrap_or(0);
+        if line_start <= self.point_min() {
+            return;
+        }
         let target_line = line - 1;
         if let Some(cursor) = self.line_col_to_cursor(target_line, col) {
-            self.cursor = cursor;
+            self.cursor = cursor.max(self.point_min());
+        }
+    }
+
+    /// Moves `count` lines down, or up when negative, keeping the column
+    /// where the target line is long enough.
+    pub fn move_lines(&mut self, count: i64) {
+        let (line, col) = self.line_col();
+        let first = self.line_col_at(self.point_min()).0 as i64;
+        let last = self.line_col_at(self.point_max()).0 as i64;
+        let target = (line as i64).saturating_add(count).clamp(first, last) as usize;
+        if let Some(cursor) = self.line_col_to_cursor(target, col) {
+            self.cursor = self.clamp_to_accessible(cursor);
         }
     }
 
     pub fn move_forward_word(&mut self) {
+        let max = self.point_max();
         let chars: Vec<char> = self.buffer.as_str().chars().collect();
-        while self.cursor < chars.len() && is_word_char(chars[self.cursor]) {
+        while self.cursor < max && self.is_word_char(chars[self.cursor]) {
             self.cursor += 1;
         }
-        while self.cursor < chars.len() && !is_word_char(chars[self.cursor]) {
+        while self.cursor < max && !self.is_word_char(chars[self.cursor]) {
             self.cursor += 1;
         }
     }
 
     pub fn move_backward_word(&mut self) {
+        let min = self.point_min();
         let chars: Vec<char> = self.buffer.as_str().chars().collect();
-        if self.cursor == 0 || chars.is_empty() {
+        if self.cursor <= min || chars.is_empty() {
             return;
         }
 
         let mut pos = self.cursor - 1;
-        while pos > 0 && !is_word_char(chars[pos]) {
+        while pos > min && !self.is_word_char(chars[pos]) {
             pos -= 1;
         }
-        while pos > 0 && is_word_char(chars[pos - 1]) {
+        while pos > min && self.is_word_char(chars[pos - 1]) {
             pos -= 1;
         }
         self.cursor = pos;
     }
 
+    pub fn move_words(&mut self, count: i64) {
+        self.repeat_motion(count, Self::move_forward_word, Self::move_backward_word);
+    }
+
     pub fn move_to_buffer_start(&mut self) {
-        self.cursor = 0;
+        self.cursor = self.point_min();
     }
 
     pub fn move_to_buffer_end(&mut self) {
-        self.cursor = self.buffer.char_len();
+        self.cursor = self.point_max();
+    }
+
+    /// `M-<` and `M->` with an argument: moves `tenths`/10 of the way in from
+    /// the start (or end), then to the beginning of the next line.
+    pub fn move_to_buffer_fraction(&mut self, tenths: i64, from_end: bool) {
+        let (min, max) = (self.point_min(), self.point_max());
+        let offset = (max - min) * tenths.clamp(0, 10) as usize / 10;
+        self.cursor = if from_end { max - offset } else { min + offset };
+        self.move_to_line_end();
+        self.move_forward();
+    }
+
+    /// Runs `forward` `count` times, or `backward` `-count` times, stopping
+    /// early once point no longer moves.
+    fn repeat_motion(&mut self, count: i64, forward: fn(&mut Self), backward: fn(&mut Self)) {
+        let step = if count < 0 { backward } else { forward };
+        for _ in 0..count.unsigned_abs() {
+            let before = self.cursor;
+            step(self);
+            if self.cursor == before {
+                break;
+            }
+        }
     }
 }
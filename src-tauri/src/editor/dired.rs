@@ -0,0 +1,386 @@
+use std::path::{Path, PathBuf};
+
+use crate::editor::state::{BufferState, SpecialBuffer};
+
+/// Character offset where the file name starts on an entry line.
+pub const NAME_COLUMN: usize = 32;
+/// Number of lines before the first entry (the directory header).
+const HEADER_LINES: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiredMark {
+    None,
+    Marked,
+    Delete,
+}
+
+impl DiredMark {
+    fn as_char(self) -> char {
+        match self {
+            DiredMark::None => ' ',
+            DiredMark::Marked => '*',
+            DiredMark::Delete => 'D',
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiredEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch.
+    pub modified: Option<u64>,
+    pub mark: DiredMark,
+}
+
+impl DiredEntry {
+    pub fn is_parent(&self) -> bool {
+        self.name == ".."
+    }
+
+    fn kind_char(&self) -> char {
+        if self.is_symlink {
+            'l'
+        } else if self.is_dir {
+            'd'
+        } else {
+            '-'
+        }
+    }
+
+    fn prefix(&self) -> String {
+        let size = if self.is_dir {
+            "-".to_string()
+        } else {
+            self.size.to_string()
+        };
+        let date = self
+            .modified
+            .map(format_timestamp)
+            .unwrap_or_else(|| " ".repeat(16));
+        format!(
+            "{} {} {:>10} {} ",
+            self.mark.as_char(),
+            self.kind_char(),
+            size,
+            date
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiredListing {
+    pub directory: PathBuf,
+    pub entries: Vec<DiredEntry>,
+    /// True while the buffer text is editable for wdired-style renaming.
+    pub wdired: bool,
+}
+
+impl DiredListing {
+    pub fn new(directory: PathBuf, mut entries: Vec<DiredEntry>) -> Self {
+        entries.sort_by(|a, b| {
+            b.is_parent()
+                .cmp(&a.is_parent())
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        Self {
+            directory,
+            entries,
+            wdired: false,
+        }
+    }
+
+    pub fn buffer_name(&self) -> String {
+        let name = self
+            .directory
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.directory.to_string_lossy().to_string());
+        format!("{}/", name.trim_end_matches(['/', '\\']))
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!("  {}:\n", self.directory.display());
+        for entry in &self.entries {
+            out.push_str(&entry.prefix());
+            out.push_str(&entry.name);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Index into `entries` for a 1-based buffer line.
+    pub fn entry_index_at_line(&self, line: usize) -> Option<usize> {
+        let index = line.checked_sub(HEADER_LINES + 1)?;
+        (index < self.entries.len()).then_some(index)
+    }
+
+    pub fn path_of(&self, entry: &DiredEntry) -> PathBuf {
+        if entry.is_parent() {
+            self.directory
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| self.directory.clone())
+        } else {
+            self.directory.join(&entry.name)
+        }
+    }
+
+    /// Carries marks over from an older listing of the same directory.
+    pub fn inherit_marks(&mut self, previous: &DiredListing) {
+        for entry in &mut self.entries {
+            if let Some(old) = previous.entries.iter().find(|e| e.name == entry.name) {
+                entry.mark = old.mark;
+            }
+        }
+    }
+
+    /// Parses an edited wdired buffer into `(old_name, new_name)` pairs.
+    pub fn wdired_renames(&self, text: &str) -> Result<Vec<(String, String)>, String> {
+        let lines: Vec<&str> = text.trim_end_matches('\n').split('\n').collect();
+        if lines.len() != self.entries.len() + HEADER_LINES {
+            return Err("Lines were added or removed; only file names may be edited".to_string());
+        }
+
+        let mut renames = Vec::new();
+        for (entry, line) in self.entries.iter().zip(lines.iter().skip(HEADER_LINES)) {
+            let prefix = entry.prefix();
+            if !line.starts_with(&prefix) {
+                return Err(format!("Only file names may be edited (near {})", entry.name));
+            }
+            let new_name = &line[prefix.len()..];
+            if new_name == entry.name {
+                continue;
+            }
+            if entry.is_parent() {
+                return Err("Cannot rename ..".to_string());
+            }
+            if new_name.trim().is_empty() || new_name.contains(['/', '\\']) {
+                return Err(format!("Invalid file name: '{}'", new_name));
+            }
+            renames.push((entry.name.clone(), new_name.to_string()));
+        }
+        Ok(renames)
+    }
+}
+
+impl BufferState {
+    pub fn load_dired(&mut self, listing: DiredListing) {
+        let previous_line = self.line_col().0;
+        let reload = matches!(
+            &self.special,
+            Some(SpecialBuffer::Dired(old)) if old.directory == listing.directory
+        );
+        self.replace_contents(listing.render());
+        self.cursor = 0;
+        let line = if reload {
+            previous_line
+        } else {
+            // Start on the first real entry, skipping "..".
+            HEADER_LINES
+                + 1
+                + usize::from(listing.entries.first().is_some_and(|e| e.is_parent()))
+        };
+        if let Some(cursor) = self.line_col_to_cursor(line, NAME_COLUMN + 1) {
+            self.cursor = cursor;
+        }
+        self.original_encoding = "UTF-8".to_string();
+        self.line_ending = "LF".to_string();
+        self.file_path = None;
+        self.query_replace_session = None;
+        self.special = Some(SpecialBuffer::Dired(listing));
+    }
+
+    pub fn dired_listing(&self) -> Option<&DiredListing> {
+        match &self.special {
+            Some(SpecialBuffer::Dired(listing)) => Some(listing),
+            _ => None,
+        }
+    }
+
+    pub fn dired_entry_at_cursor(&self) -> Option<&DiredEntry> {
+        let listing = self.dired_listing()?;
+        let index = listing.entry_index_at_line(self.line_col().0)?;
+        listing.entries.get(index)
+    }
+
+    /// Entries to operate on: the marked ones, or the one at point.
+    pub fn dired_targets(&self) -> Vec<DiredEntry> {
+        let Some(listing) = self.dired_listing() else {
+            return Vec::new();
+        };
+        let marked: Vec<DiredEntry> = listing
+            .entries
+            .iter()
+            .filter(|e| e.mark == DiredMark::Marked)
+            .cloned()
+            .collect();
+        if !marked.is_empty() {
+            return marked;
+        }
+        self.dired_entry_at_cursor()
+            .filter(|e| !e.is_parent())
+            .cloned()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn dired_flagged(&self) -> Vec<DiredEntry> {
+        self.dired_listing()
+            .map(|listing| {
+                listing
+                    .entries
+                    .iter()
+                    .filter(|e| e.mark == DiredMark::Delete)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Sets the mark on the entry at point and moves to the next line.
+    pub fn dired_set_mark(&mut self, mark: DiredMark) -> Result<(), String> {
+        let line = self.line_col().0;
+        let Some(SpecialBuffer::Dired(listing)) = self.special.as_mut() else {
+            return Err("Not a dired buffer".to_string());
+        };
+        let index = listing
+            .entry_index_at_line(line)
+            .ok_or_else(|| "No file on this line".to_string())?;
+        if !listing.entries[index].is_parent() {
+            listing.entries[index].mark = mark;
+        }
+        self.rerender_dired(line + 1);
+        Ok(())
+    }
+
+    pub fn dired_unmark_all(&mut self) {
+        let line = self.line_col().0;
+        if let Some(SpecialBuffer::Dired(listing)) = self.special.as_mut() {
+            for entry in &mut listing.entries {
+                entry.mark = DiredMark::None;
+            }
+        }
+        self.rerender_dired(line);
+    }
+
+    pub fn wdired_start(&mut self) -> Result<(), String> {
+        let Some(SpecialBuffer::Dired(listing)) = self.special.as_mut() else {
+            return Err("Not a dired buffer".to_string());
+        };
+        listing.wdired = true;
+        self.undo_stack.clear_all();
+        self.set_status_message(Some(
+            "Editable dired: edit file names, C-c C-c to apply, C-c C-k to abort".to_string(),
+        ));
+        Ok(())
+    }
+
+    pub fn wdired_abort(&mut self) {
+        let line = self.line_col().0;
+        if let Some(SpecialBuffer::Dired(listing)) = self.special.as_mut() {
+            listing.wdired = false;
+        }
+        self.rerender_dired(line);
+        self.set_status_message(Some("Wdired changes discarded".to_string()));
+    }
+
+    fn rerender_dired(&mut self, line: usize) {
+        let Some(listing) = self.dired_listing() else {
+            return;
+        };
+        self.buffer.set_text(listing.render());
+        self.set_modified(false);
+        let last_line = self.buffer.as_str().matches('\n').count();
+        let target = line.clamp(HEADER_LINES + 1, last_line.max(HEADER_LINES + 1));
+        self.cursor = self
+            .line_col_to_cursor(target, NAME_COLUMN + 1)
+            .unwrap_or(0);
+    }
+}
+
+/// Formats Unix seconds as `YYYY-MM-DD HH:MM` (UTC).
+fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60
+    )
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_timestamp, DiredEntry, DiredListing, DiredMark};
+    use crate::editor::state::BufferState;
+    use std::path::PathBuf;
+
+    fn entry(name: &str, is_dir: bool) -> DiredEntry {
+        DiredEntry {
+            name: name.to_string(),
+            is_dir,
+            is_symlink: false,
+            size: 42,
+            modified: Some(1_700_000_000),
+            mark: DiredMark::None,
+        }
+    }
+
+    fn listing() -> DiredListing {
+        DiredListing::new(
+            PathBuf::from("/notes"),
+            vec![entry("b.md", false), entry("..", true), entry("a", true)],
+        )
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13");
+    }
+
+    #[test]
+    fn marks_move_point_and_select_targets() {
+        let mut buf = BufferState::new();
+        buf.load_dired(listing());
+        assert_eq!(buf.name(), "notes/");
+        assert_eq!(buf.dired_entry_at_cursor().map(|e| e.name.as_str()), Some("a"));
+
+        buf.dired_set_mark(DiredMark::Marked).unwrap();
+        assert_eq!(buf.dired_entry_at_cursor().map(|e| e.name.as_str()), Some("b.md"));
+        let targets: Vec<String> = buf.dired_targets().into_iter().map(|e| e.name).collect();
+        assert_eq!(targets, vec!["a".to_string()]);
+        assert!(buf.buffer.as_str().contains("\n* d"));
+    }
+
+    #[test]
+    fn wdired_detects_renamed_lines() {
+        let listing = listing();
+        let edited = listing.render().replace(" b.md\n", " c.md\n");
+        let renames = listing.wdired_renames(&edited).unwrap();
+        assert_eq!(renames, vec![("b.md".to_string(), "c.md".to_string())]);
+
+        let broken = listing.render().replacen("- ", "x ", 1);
+        assert!(listing.wdired_renames(&broken).is_err());
+    }
+}
@@ -1,65 +1,151 @@
+use crate::editor::prefix_arg::repeat_text;
 use crate::editor::state::BufferState;
 
+/// What `C-t`, `M-t` and `C-x C-t` swap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransposeUnit {
+    Char,
+    Word,
+    Line,
+}
+
+/// How `M-u`, `M-l` and `M-c` change the words they cover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseChange {
+    Upcase,
+    Downcase,
+    Capitalize,
+}
+
 impl BufferState {
-    pub fn insert_text(&mut self, text: &str) {
+    pub fn insert_text(&mut self, text: &str) -> Result<(), String> {
         if text.is_empty() {
-            return;
+            return Ok(());
         }
+        self.check_writable()?;
 
         self.record_undo_snapshot();
+        let overwritten = self.overwrite_len(text);
+        if overwritten > 0 {
+            self.buffer.remove_range(self.cursor, self.cursor + overwritten);
+        }
         self.buffer.insert_str(self.cursor, text);
         self.cursor += text.chars().count();
-        self.modified = true;
+        self.auto_fill(text);
+        self.set_modified(true);
         self.status_message = None;
+        Ok(())
     }
 
-    pub fn delete_char(&mut self) {
-        if self.cursor >= self.buffer.char_len() {
-            return;
-        }
+    pub fn delete_char(&mut self) -> Result<(), String> {
+        self.delete_chars(1, None)
+    }
 
-        self.record_undo_snapshot();
-        self.buffer.remove_range(self.cursor, self.cursor + 1);
-        self.modified = true;
-        self.status_message = None;
+    pub fn delete_backward_char(&mut self) -> Result<(), String> {
+        self.delete_chars(-1, None)
     }
 
-    pub fn delete_backward_char(&mut self) {
-        if self.cursor == 0 {
-            return;
+    /// Deletes `count` characters after point, or `-count` before it. Given a
+    /// kill ring the text is killed, as `C-d` and `DEL` do with an argument.
+    pub fn delete_chars(
+        &mut self,
+        count: i64,
+        kill_ring: Option<&mut Vec<String>>,
+    ) -> Result<(), String> {
+        let n = usize::try_from(count.unsigned_abs()).unwrap_or(usize::MAX);
+        let (start, end) = if count < 0 {
+            (self.cursor.saturating_sub(n).max(self.point_min()), self.cursor)
+        } else {
+            (self.cursor, self.cursor.saturating_add(n).min(self.point_max()))
+        };
+        if start >= end {
+            return Ok(());
         }
+        self.check_writable()?;
 
+        let deleted: String = self.buffer.as_str().chars().skip(start).take(end - start).collect();
         self.record_undo_snapshot();
-        self.buffer.remove_range(self.cursor - 1, self.cursor);
-        self.cursor -= 1;
-        self.modified = true;
+        self.buffer.remove_range(start, end);
+        self.cursor = start;
+        if let Some(kill_ring) = kill_ring {
+            push_kill_ring(kill_ring, deleted);
+        }
+        self.set_modified(true);
         self.status_message = None;
+        Ok(())
     }
 
-    pub fn kill_line(&mut self, kill_ring: &mut Vec<String>) {
+    pub fn kill_line(&mut self, kill_ring: &mut Vec<String>) -> Result<(), String> {
+        let max = self.point_max();
         let chars: Vec<char> = self.buffer.as_str().chars().collect();
-        if self.cursor >= chars.len() {
-            return;
+        if self.cursor >= max {
+            return Ok(());
         }
+        self.check_writable()?;
 
         let mut end = self.cursor;
-        while end < chars.len() && chars[end] != '\n' {
+        while end < max && chars[end] != '\n' {
             end += 1;
         }
-        if end < chars.len() && chars[end] == '\n' {
+        if end < max && chars[end] == '\n' {
             end += 1;
         }
 
         if end <= self.cursor {
-            return;
+            return Ok(());
         }
 
         let killed: String = chars[self.cursor..end].iter().collect();
         self.record_undo_snapshot();
         self.buffer.remove_range(self.cursor, end);
         push_kill_ring(kill_ring, killed);
-        self.modified = true;
+        self.set_modified(true);
         self.status_message = Some("Killed line".to_string());
+        Ok(())
+    }
+
+    /// `C-k` with an argument: kills through the end of the `count`th line
+    /// down, or back to the start of the `-count`th line up. Zero kills back to
+    /// the start of the current line.
+    pub fn kill_lines(&mut self, count: i64, kill_ring: &mut Vec<String>) -> Result<(), String> {
+        let (min, max) = (self.point_min(), self.point_max());
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        let (start, end) = if count > 0 {
+            let (mut end, mut lines) = (self.cursor, 0);
+            while end < max && lines < count {
+                if chars[end] == '\n' {
+                    lines += 1;
+                }
+                end += 1;
+            }
+            (self.cursor, end)
+        } else {
+            let (mut start, mut lines) = (self.cursor, 0);
+            loop {
+                while start > min && chars[start - 1] != '\n' {
+                    start -= 1;
+                }
+                if start <= min || lines >= -count {
+                    break;
+                }
+                start -= 1;
+                lines += 1;
+            }
+            (start, self.cursor)
+        };
+        if start >= end {
+            return Ok(());
+        }
+        self.check_writable()?;
+
+        let killed: String = chars[start..end].iter().collect();
+        self.record_undo_snapshot();
+        self.buffer.remove_range(start, end);
+        self.cursor = start;
+        push_kill_ring(kill_ring, killed);
+        self.set_modified(true);
+        self.status_message = Some("Killed line".to_string());
+        Ok(())
     }
 
     pub fn copy_region(&mut self, start: usize, end: usize, kill_ring: &mut Vec<String>) {
@@ -67,61 +153,445 @@ impl BufferState {
             return;
         }
 
-        let chars: Vec<char> = self.buffer.as_str().chars().collect();
-        let safe_end = end.min(chars.len());
+        let (start, safe_end) = (self.clamp_to_accessible(start), self.clamp_to_accessible(end));
         if start >= safe_end {
             return;
         }
-
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
         let copied: String = chars[start..safe_end].iter().collect();
         push_kill_ring(kill_ring, copied);
         self.status_message = Some("Copied region".to_string());
     }
 
-    pub fn kill_region(&mut self, start: usize, end: usize, kill_ring: &mut Vec<String>) {
+    pub fn kill_region(
+        &mut self,
+        start: usize,
+        end: usize,
+        kill_ring: &mut Vec<String>,
+    ) -> Result<(), String> {
         if start >= end {
-            return;
+            return Ok(());
         }
 
-        let chars: Vec<char> = self.buffer.as_str().chars().collect();
-        let safe_end = end.min(chars.len());
+        let (start, safe_end) = (self.clamp_to_accessible(start), self.clamp_to_accessible(end));
         if start >= safe_end {
-            return;
+            return Ok(());
         }
-
+        self.check_writable()?;
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
         let killed: String = chars[start..safe_end].iter().collect();
         self.record_undo_snapshot();
         self.buffer.remove_range(start, safe_end);
-        self.cursor = start.min(self.buffer.char_len());
+        self.cursor = start;
         push_kill_ring(kill_ring, killed);
-        self.modified = true;
+        self.set_modified(true);
         self.status_message = Some("Killed region".to_string());
+        Ok(())
+    }
+
+    pub fn yank(&mut self, kill_ring: &[String]) -> Result<(), String> {
+        self.yank_nth(kill_ring, 0, false)
     }
 
-    pub fn yank(&mut self, kill_ring: &[String]) {
-        let Some(text) = kill_ring.first().cloned() else {
+    /// Inserts the kill `index` entries back, counting around the ring as
+    /// `C-y` with an argument does. With `point_before`, point stays in front
+    /// of the yanked text, as after `C-u C-y`.
+    pub fn yank_nth(
+        &mut self,
+        kill_ring: &[String],
+        index: i64,
+        point_before: bool,
+    ) -> Result<(), String> {
+        if kill_ring.is_empty() {
             self.status_message = Some("Kill ring empty".to_string());
-            return;
-        };
+            return Ok(());
+        }
+        self.check_writable()?;
 
+        let text = &kill_ring[index.rem_euclid(kill_ring.len() as i64) as usize];
         self.record_undo_snapshot();
-        self.buffer.insert_str(self.cursor, &text);
-        self.cursor += text.chars().count();
-        self.modified = true;
+        self.buffer.insert_str(self.cursor, text);
+        if !point_before {
+            self.cursor += text.chars().count();
+        }
+        self.set_modified(true);
         self.status_message = Some("Yank".to_string());
+        Ok(())
     }
 
     pub fn set_cursor(&mut self, cursor: usize) {
-        self.cursor = cursor.min(self.buffer.char_len());
+        self.cursor = self.clamp_to_accessible(cursor);
+    }
+
+    /// `C-t`: swaps the characters around point and moves forward. With no
+    /// argument at the end of a line, swaps the two characters before point.
+    pub fn transpose_chars(&mut self, count: Option<i64>) -> Result<(), String> {
+        let at_line_end = self.cursor >= self.point_max() || self.char_at(self.cursor) == Some('\n');
+        if count.is_none() && at_line_end && self.cursor > self.point_min() {
+            self.cursor -= 1;
+        }
+        self.transpose(TransposeUnit::Char, count.unwrap_or(1))
+    }
+
+    /// Drags the unit before point (the word point is in, for words; the
+    /// previous line, for lines) forward past `count` units, or back past
+    /// `-count` units, leaving point after it.
+    pub fn transpose(&mut self, unit: TransposeUnit, count: i64) -> Result<(), String> {
+        for step in 0..count.unsigned_abs() {
+            let chars: Vec<char> = self.buffer.as_str().chars().collect();
+            let pair = match self.unit_at(unit, &chars, self.cursor) {
+                Some(moved) if count > 0 => self.unit_after(unit, &chars, moved.1).map(|next| (moved, next)),
+                Some(moved) => self.unit_before(unit, &chars, moved.0).map(|prev| (prev, moved)),
+                None => None,
+            };
+            let Some(((a_start, a_end), (b_start, b_end))) = pair else {
+                if step == 0 {
+                    self.status_message = Some("Don't have two things to transpose".to_string());
+                }
+                return Ok(());
+            };
+            self.check_writable()?;
+
+            let mut swapped: String = chars[b_start..b_end].iter().collect();
+            swapped.extend(&chars[a_end..b_start]);
+            swapped.extend(&chars[a_start..a_end]);
+            if unit == TransposeUnit::Line && b_end == self.point_max() {
+                swapped.push('\n');
+            }
+            self.replace_text(a_start, b_end, &swapped);
+            let moved_end = if count > 0 { b_end } else { a_start + (b_end - b_start) };
+            self.cursor = moved_end + usize::from(unit == TransposeUnit::Line);
+        }
+        Ok(())
+    }
+
+    /// Changes the case of the words from point to `count` words forward,
+    /// or `-count` words back. Point only moves going forward.
+    pub fn change_word_case(&mut self, case: CaseChange, count: i64) -> Result<(), String> {
+        let target = self.word_motion(self.cursor, count);
+        let (start, end) = (self.cursor.min(target), self.cursor.max(target));
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        let mut in_word = false;
+        let changed: String = chars[start..end]
+            .iter()
+            .map(|&ch| {
+                let upcase = match case {
+                    CaseChange::Upcase => true,
+                    CaseChange::Downcase => false,
+                    CaseChange::Capitalize => !in_word,
+                };
+                in_word = self.is_word_char(ch);
+                convert_case(ch, upcase)
+            })
+            .collect();
+        if changed.chars().ne(chars[start..end].iter().copied()) {
+            self.check_writable()?;
+            self.replace_text(start, end, &changed);
+        }
+        self.cursor = target.max(self.cursor);
+        Ok(())
+    }
+
+    /// `M-d` and `M-DEL`: kills to the end of the `count`th word forward,
+    /// or back to the start of the `-count`th word.
+    pub fn kill_words(&mut self, count: i64, kill_ring: &mut Vec<String>) -> Result<(), String> {
+        let target = self.word_motion(self.cursor, count);
+        let (start, end) = (self.cursor.min(target), self.cursor.max(target));
+        if start >= end {
+            return Ok(());
+        }
+        self.check_writable()?;
+
+        let killed: String = self.buffer.as_str().chars().skip(start).take(end - start).collect();
+        self.replace_text(start, end, "");
+        self.cursor = start;
+        push_kill_ring(kill_ring, killed);
+        Ok(())
+    }
+
+    /// `M-\`: deletes spaces and tabs around point, or only those before
+    /// it when `backward_only`.
+    pub fn delete_horizontal_space(&mut self, backward_only: bool) -> Result<(), String> {
+        let (start, end) = self.space_around(false);
+        let end = if backward_only { self.cursor } else { end };
+        self.replace_whitespace(start, end, "")
+    }
+
+    /// `M-SPC`: replaces the spaces and tabs around point with `count`
+    /// spaces; a negative count also takes in newlines and leaves `-count`.
+    pub fn just_one_space(&mut self, count: i64) -> Result<(), String> {
+        let (start, end) = self.space_around(count < 0);
+        let spaces = repeat_text(" ", count.unsigned_abs())?;
+        self.replace_whitespace(start, end, &spaces)
+    }
+
+    /// `C-o`: inserts `count` newlines after point without moving it.
+    pub fn open_line(&mut self, count: i64) -> Result<(), String> {
+        let n = u64::try_from(count).map_err(|_| format!("Negative repetition argument {}", count))?;
+        if n == 0 {
+            return Ok(());
+        }
+        let newlines = repeat_text("\n", n)?;
+        self.check_writable()?;
+        let cursor = self.cursor;
+        self.replace_text(cursor, cursor, &newlines);
+        self.cursor = cursor;
+        Ok(())
+    }
+
+    /// `C-x C-o`: on a blank line, deletes the blank lines around it but
+    /// one, or the line itself when it is the only one; on a nonblank line,
+    /// deletes the blank lines that follow it.
+    pub fn delete_blank_lines(&mut self) -> Result<(), String> {
+        let (min, max) = (self.point_min(), self.point_max());
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        let line_at = |pos: usize| {
+            let (mut start, mut end) = (pos, pos);
+            while start > min && chars[start - 1] != '\n' {
+                start -= 1;
+            }
+            while end < max && chars[end] != '\n' {
+                end += 1;
+            }
+            (start, end, chars[start..end].iter().all(|ch| ch.is_whitespace()))
+        };
+        let (line_start, line_end, blank) = line_at(self.cursor);
+
+        let mut first = line_start;
+        while blank && first > min {
+            match line_at(first - 1) {
+                (start, _, true) => first = start,
+                _ => break,
+            }
+        }
+        let mut last = line_end;
+        while last < max {
+            match line_at(last + 1) {
+                (_, end, true) => last = end,
+                _ => break,
+            }
+        }
+
+        let (start, end) = if !blank {
+            (line_end + 1, last + 1)
+        } else if first == line_start && last == line_end {
+            if line_end < max {
+                (line_start, line_end + 1)
+            } else {
+                (line_start.saturating_sub(1).max(min), line_end)
+            }
+        } else {
+            (first, last)
+        };
+        let (start, end) = (start.min(max), end.min(max));
+        if start >= end {
+            return Ok(());
+        }
+        self.check_writable()?;
+        let cursor = if blank { start } else { self.cursor };
+        self.replace_text(start, end, "");
+        self.cursor = cursor;
+        Ok(())
+    }
+
+    fn char_at(&self, pos: usize) -> Option<char> {
+        self.buffer.as_str().chars().nth(pos)
+    }
+
+    /// Where point would be after `count` words forward (to the end of a
+    /// word) or `-count` words back (to the start of one).
+    fn word_motion(&self, from: usize, count: i64) -> usize {
+        let (min, max) = (self.point_min(), self.point_max());
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        let mut pos = from;
+        for _ in 0..count.unsigned_abs() {
+            if count > 0 {
+                while pos < max && !self.is_word_char(chars[pos]) {
+                    pos += 1;
+                }
+                while pos < max && self.is_word_char(chars[pos]) {
+                    pos += 1;
+                }
+            } else {
+                while pos > min && !self.is_word_char(chars[pos - 1]) {
+                    pos -= 1;
+                }
+                while pos > min && self.is_word_char(chars[pos - 1]) {
+                    pos -= 1;
+                }
+            }
+        }
+        pos
+    }
+
+    /// The unit a transpose at `pos` drags.
+    fn unit_at(&self, unit: TransposeUnit, chars: &[char], pos: usize) -> Option<(usize, usize)> {
+        match unit {
+            TransposeUnit::Word if pos > self.point_min() && self.is_word_char(chars[pos - 1]) => {
+                let rest = &chars[pos..self.point_max()];
+                let end = pos + rest.iter().take_while(|ch| self.is_word_char(**ch)).count();
+                self.unit_before(unit, chars, end)
+            }
+            _ => self.unit_before(unit, chars, pos),
+        }
+    }
+
+    /// The unit ending at or before `pos`; for lines, the line before the one `pos` is on.
+    fn unit_before(&self, unit: TransposeUnit, chars: &[char], pos: usize) -> Option<(usize, usize)> {
+        let min = self.point_min();
+        match unit {
+            TransposeUnit::Char => (pos > min).then(|| (pos - 1, pos)),
+            TransposeUnit::Word => {
+                let start = self.word_motion(pos, -1);
+                let end = start + chars[start..pos].iter().take_while(|ch| self.is_word_char(**ch)).count();
+                (start < end).then_some((start, end))
+            }
+            TransposeUnit::Line => {
+                let mut line_start = pos;
+                while line_start > min && chars[line_start - 1] != '\n' {
+                    line_start -= 1;
+                }
+                if line_start == min {
+                    return None;
+                }
+                let end = line_start - 1;
+                let mut start = end;
+                while start > min && chars[start - 1] != '\n' {
+                    start -= 1;
+                }
+                Some((start, end))
+            }
+        }
+    }
+
+    /// The unit starting at or after `pos`; for lines, `pos` is the end of the line before it.
+    fn unit_after(&self, unit: TransposeUnit, chars: &[char], pos: usize) -> Option<(usize, usize)> {
+        let max = self.point_max();
+        match unit {
+            TransposeUnit::Char => (pos < max).then(|| (pos, pos + 1)),
+            TransposeUnit::Word => {
+                let end = self.word_motion(pos, 1);
+                let start = end - chars[pos..end].iter().rev().take_while(|ch| self.is_word_char(**ch)).count();
+                (start < end).then_some((start, end))
+            }
+            TransposeUnit::Line => {
+                let start = pos.checked_add(1).filter(|start| *start <= max)?;
+                let end = start + chars[start..max].iter().take_while(|ch| **ch != '\n').count();
+                Some((start, end))
+            }
+        }
+    }
+
+    /// Spaces and tabs around point, and newlines too with `newlines`.
+    fn space_around(&self, newlines: bool) -> (usize, usize) {
+        let (min, max) = (self.point_min(), self.point_max());
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        let is_space = |ch: char| ch == ' ' || ch == '\t' || (newlines && ch == '\n');
+        let (mut start, mut end) = (self.cursor, self.cursor);
+        while start > min && is_space(chars[start - 1]) {
+            start -= 1;
+        }
+        while end < max && is_space(chars[end]) {
+            end += 1;
+        }
+        (start, end)
+    }
+
+    fn replace_whitespace(&mut self, start: usize, end: usize, with: &str) -> Result<(), String> {
+        let current: String = self.buffer.as_str().chars().skip(start).take(end - start).collect();
+        if current != with {
+            self.check_writable()?;
+            self.replace_text(start, end, with);
+        }
+        self.cursor = start + with.chars().count();
+        Ok(())
+    }
+
+    /// Replaces `start..end` with `text` as one undoable change.
+    fn replace_text(&mut self, start: usize, end: usize, text: &str) {
+        self.record_undo_snapshot();
+        self.buffer.remove_range(start, end);
+        self.buffer.insert_str(start, text);
+        self.set_modified(true);
+        self.status_message = None;
+    }
+}
+
+/// `ch` in upper or lower case, kept as is when the mapping is not one character.
+fn convert_case(ch: char, upcase: bool) -> char {
+    let mut mapped: Vec<char> = if upcase { ch.to_uppercase().collect() } else { ch.to_lowercase().collect() };
+    if mapped.len() == 1 {
+        mapped.remove(0)
+    } else {
+        ch
     }
 }
 
+/// Adds a kill; `EditorState::run_command` trims the ring to `kill_ring_max`.
 fn push_kill_ring(kill_ring: &mut Vec<String>, text: String) {
     if text.is_empty() {
         return;
     }
     kill_ring.insert(0, text);
-    if kill_ring.len() > 10 {
-        kill_ring.truncate(10);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CaseChange, TransposeUnit};
+    use crate::editor::state::BufferState;
+
+    fn buffer(text: &str, cursor: usize) -> BufferState {
+        let mut buf = BufferState::new();
+        buf.insert_text(text).unwrap();
+        buf.set_cursor(cursor);
+        buf
+    }
+
+    #[test]
+    fn word_and_line_commands_follow_emacs() {
+        let mut buf = buffer("abc\n", 3);
+        buf.transpose_chars(None).unwrap();
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("acb\n", 3));
+
+        let mut buf = buffer("one two three", 1);
+        buf.transpose(TransposeUnit::Word, 2).unwrap();
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("two three one", 13));
+        buf.transpose(TransposeUnit::Word, -1).unwrap();
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("two one three", 7));
+
+        let mut buf = buffer("a\nb\nc", 2);
+        buf.transpose(TransposeUnit::Line, 2).unwrap();
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("b\nc\na\n", 6));
+
+        let mut buf = buffer("hello big_world again", 0);
+        buf.change_word_case(CaseChange::Capitalize, 2).unwrap();
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("Hello Big_world again", 15));
+        buf.change_word_case(CaseChange::Upcase, -1).unwrap();
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("Hello BIG_WORLD again", 15));
+
+        let mut kill_ring = Vec::new();
+        let mut buf = buffer("foo  bar baz", 3);
+        buf.kill_words(-1, &mut kill_ring).unwrap();
+        buf.just_one_space(1).unwrap();
+        buf.kill_words(2, &mut kill_ring).unwrap();
+        assert_eq!(buf.buffer.text(), " ");
+        assert_eq!(kill_ring, vec!["bar baz", "foo"]);
+
+        let mut buf = buffer("x\n\n \n\ny\n\n", 3);
+        buf.delete_blank_lines().unwrap();
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("x\n\ny\n\n", 2));
+        buf.delete_blank_lines().unwrap();
+        assert_eq!(buf.buffer.text(), "x\ny\n\n");
+        buf.set_cursor(2);
+        buf.delete_blank_lines().unwrap();
+        assert_eq!(buf.buffer.text(), "x\ny\n");
+        buf.open_line(2).unwrap();
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("x\n\n\ny\n", 2));
+
+        let mut buf = buffer("a  b", 2);
+        let huge = -99_999_999_999_999;
+        assert_eq!(buf.just_one_space(huge), Err(format!("Repetition count {} is too large", -huge)));
+        assert!(buf.open_line(i64::MAX).is_err());
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("a  b", 2));
     }
 }
@@ -0,0 +1,138 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::editor::command::{commands, ArgSpec};
+use crate::editor::state::EditorState;
+
+const COMMAND_HISTORY_MAX: usize = 30;
+
+/// One `M-x` completion candidate.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtendedCommandCandidate {
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub args: &'static [ArgSpec],
+    /// Key sequences currently bound to the command.
+    pub keys: Vec<String>,
+    /// True when the command is in the M-x history.
+    pub recent: bool,
+}
+
+/// Scores `name` against `query` as a subsequence match: matches at the
+/// start of a word and runs of adjacent matches score higher, and shorter
+/// names break ties. `-` and spaces in the query match `_`, so
+/// `kill-line` finds `kill_line`.
+fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    let query: Vec<char> = query
+        .chars()
+        .map(|ch| match ch.to_ascii_lowercase() {
+            '-' | ' ' => '_',
+            ch => ch,
+        })
+        .collect();
+    let name: Vec<char> = name.chars().collect();
+    let is_subsequence = |query: &[char], from: usize| {
+        let mut rest = name[from.min(name.len())..].iter();
+        query.iter().all(|ch| rest.any(|c| c == ch))
+    };
+    if !is_subsequence(&query, 0) {
+        return None;
+    }
+
+    let word_start = |i: usize| i == 0 || name[i - 1] == '_';
+    let mut score = 0;
+    let mut pos = 0;
+    let mut previous: Option<usize> = None;
+    for (index, ch) in query.iter().enumerate() {
+        // Prefer a later word start when the rest still matches, so `mfw`
+        // takes the `w` of `word`.
+        let mut positions = (pos..name.len()).filter(|i| name[*i] == *ch);
+        let first = positions.next()?;
+        let found = std::iter::once(first)
+            .chain(positions)
+            .find(|i| word_start(*i) && is_subsequence(&query[index + 1..], i + 1))
+            .unwrap_or(first);
+        score += 1;
+        if word_start(found) {
+            score += 8;
+        }
+        if previous.is_some_and(|prev| prev + 1 == found) {
+            score += 4;
+        }
+        previous = Some(found);
+        pos = found + 1;
+    }
+    Some(score * 100 - name.len() as i64)
+}
+
+impl EditorState {
+    /// Commands matching `query`, recently used ones first (most recent
+    /// first), then by fuzzy score.
+    pub fn extended_command_candidates(&self, query: &str) -> Vec<ExtendedCommandCandidate> {
+        let mut matches: Vec<(Option<usize>, i64, ExtendedCommandCandidate)> = commands()
+            .iter()
+            .filter_map(|spec| {
+                let score = fuzzy_score(query.trim(), spec.name)?;
+                let recency = self.command_history.iter().position(|name| name == spec.name);
+                let candidate = ExtendedCommandCandidate {
+                    name: spec.name,
+                    doc: spec.doc,
+                    args: spec.args,
+                    keys: self.keymap.keys_for(spec.name),
+                    recent: recency.is_some(),
+                };
+                Some((recency, score, candidate))
+            })
+            .collect();
+        matches.sort_by(|(a_recency, a_score, a), (b_recency, b_score, b)| {
+            match (a_recency, b_recency) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => b_score.cmp(a_score).then(a.name.cmp(b.name)),
+            }
+        });
+        matches.into_iter().map(|(_, _, candidate)| candidate).collect()
+    }
+
+    /// Runs a command chosen with `M-x` and moves it to the front of the history.
+    pub fn execute_extended_command(&mut self, name: &str, payload: Option<Value>) -> Result<(), String> {
+        self.run_command(name, payload)?;
+        self.command_history.retain(|recent| recent != name);
+        self.command_history.insert(0, name.to_string());
+        self.command_history.truncate(COMMAND_HISTORY_MAX);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::state::EditorState;
+
+    #[test]
+    fn ranks_recent_commands_then_fuzzy_matches() {
+        let mut editor = EditorState::new();
+        let names = |editor: &EditorState, query: &str| {
+            editor
+                .extended_command_candidates(query)
+                .iter()
+                .map(|candidate| candidate.name)
+                .collect::<Vec<_>>()
+        };
+
+        let found = names(&editor, "kill-l");
+        assert_eq!(found[0], "kill_line");
+        assert!(!found.contains(&"yank"));
+        assert_eq!(names(&editor, "mfw")[0], "move_forward_word");
+
+        editor.execute_extended_command("widen", None).unwrap();
+        editor.execute_extended_command("move_forward", None).unwrap();
+        assert_eq!(names(&editor, "")[..2], ["move_forward", "widen"]);
+
+        let kill_line = &editor.extended_command_candidates("kill_line")[0];
+        assert_eq!(kill_line.keys, vec!["C-k".to_string()]);
+        assert!(editor.execute_extended_command("no_such_command", None).is_err());
+        assert_eq!(editor.command_history.len(), 2);
+    }
+}
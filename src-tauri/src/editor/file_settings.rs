@@ -0,0 +1,360 @@
+use encoding_rs::{EUC_JP, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+use crate::editor::minor_mode::MinorMode;
+use crate::editor::mode::find_mode;
+use crate::editor::state::BufferState;
+
+/// Encodings a file can be declared to use. Without a declaration femto saves UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    Utf8Bom,
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+    ShiftJis,
+    EucJp,
+}
+
+impl Charset {
+    /// Name shown in the status bar; matches what `decode_content` reports.
+    pub fn label(self) -> &'static str {
+        match self {
+            Charset::Utf8 => "UTF-8",
+            Charset::Utf8Bom => "UTF-8 BOM",
+            Charset::Latin1 => "Latin-1",
+            Charset::Utf16Le => "UTF-16LE",
+            Charset::Utf16Be => "UTF-16BE",
+            Charset::ShiftJis => "Shift-JIS",
+            Charset::EucJp => "EUC-JP",
+        }
+    }
+
+    /// Accepts EditorConfig `charset` values and Emacs coding system names
+    /// (without their `-unix`/`-dos`/`-mac` suffix).
+    pub fn from_name(name: &str) -> Option<Self> {
+        let charset = match name.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" | "prefer-utf-8" => Charset::Utf8,
+            "utf-8-bom" | "utf-8-with-signature" => Charset::Utf8Bom,
+            "latin1" | "latin-1" | "iso-latin-1" | "iso-8859-1" => Charset::Latin1,
+            "utf-16le" | "utf-16le-with-signature" => Charset::Utf16Le,
+            "utf-16be" | "utf-16be-with-signature" | "utf-16" => Charset::Utf16Be,
+            "shift-jis" | "sjis" | "cp932" | "japanese-shift-jis" => Charset::ShiftJis,
+            "euc-jp" | "japanese-iso-8bit" => Charset::EucJp,
+            _ => return None,
+        };
+        Some(charset)
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let encoding = match self {
+            Charset::Utf8 | Charset::Utf8Bom => UTF_8,
+            Charset::Latin1 => WINDOWS_1252,
+            Charset::Utf16Le => UTF_16LE,
+            Charset::Utf16Be => UTF_16BE,
+            Charset::ShiftJis => SHIFT_JIS,
+            Charset::EucJp => EUC_JP,
+        };
+        encoding.decode_with_bom_removal(bytes).0.into_owned()
+    }
+
+    /// Refuses text the charset cannot represent rather than writing
+    /// numeric character references in its place.
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, String> {
+        let encoding = match self {
+            Charset::Utf8 => return Ok(text.as_bytes().to_vec()),
+            Charset::Utf8Bom => return Ok([&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat()),
+            Charset::Utf16Le => {
+                return Ok(std::iter::once(0xFEFF)
+                    .chain(text.encode_utf16())
+                    .flat_map(u16::to_le_bytes)
+                    .collect())
+            }
+            Charset::Utf16Be => {
+                return Ok(std::iter::once(0xFEFF)
+                    .chain(text.encode_utf16())
+                    .flat_map(u16::to_be_bytes)
+                    .collect())
+            }
+            Charset::Latin1 => WINDOWS_1252,
+            Charset::ShiftJis => SHIFT_JIS,
+            Charset::EucJp => EUC_JP,
+        };
+        let (bytes, _, had_unmappable) = encoding.encode(text);
+        if had_unmappable {
+            return Err(format!("{} cannot encode every character in the buffer", self.label()));
+        }
+        Ok(bytes.into_owned())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Space,
+    Tab,
+}
+
+/// Per-file settings declared by EditorConfig or file-local variables. Unset
+/// fields fall back to what was detected or to the major mode's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileSettings {
+    pub mode: Option<String>,
+    pub line_ending: Option<&'static str>,
+    pub charset: Option<Charset>,
+    pub indent_style: Option<IndentStyle>,
+    pub indent_size: Option<usize>,
+    pub tab_width: Option<usize>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "t" | "yes" | "on" => Some(true),
+        "false" | "nil" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn parse_line_ending(value: &str) -> Option<&'static str> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "lf" | "unix" => Some("LF"),
+        "crlf" | "dos" => Some("CRLF"),
+        "cr" | "mac" => Some("CR"),
+        _ => None,
+    }
+}
+
+fn parse_size(value: &str) -> Option<usize> {
+    value.trim().parse().ok().filter(|size| *size > 0)
+}
+
+impl FileSettings {
+    /// Fields set in `self` win; the rest come from `fallback`.
+    pub fn or(self, fallback: FileSettings) -> FileSettings {
+        FileSettings {
+            mode: self.mode.or(fallback.mode),
+            line_ending: self.line_ending.or(fallback.line_ending),
+            charset: self.charset.or(fallback.charset),
+            indent_style: self.indent_style.or(fallback.indent_style),
+            indent_size: self.indent_size.or(fallback.indent_size),
+            tab_width: self.tab_width.or(fallback.tab_width),
+            trim_trailing_whitespace: self.trim_trailing_whitespace.or(fallback.trim_trailing_whitespace),
+            insert_final_newline: self.insert_final_newline.or(fallback.insert_final_newline),
+        }
+    }
+
+    /// Applies one EditorConfig property. Invalid values, including `unset`,
+    /// clear it; unknown keys are ignored, as the EditorConfig spec asks.
+    pub fn set_editorconfig(&mut self, key: &str, value: &str) {
+        match key.trim().to_ascii_lowercase().as_str() {
+            "end_of_line" => self.line_ending = parse_line_ending(value),
+            "charset" => self.charset = Charset::from_name(value),
+            "indent_style" => {
+                self.indent_style = match value.trim().to_ascii_lowercase().as_str() {
+                    "tab" => Some(IndentStyle::Tab),
+                    "space" => Some(IndentStyle::Space),
+                    _ => None,
+                }
+            }
+            // `indent_size = tab` means "use tab_width", which is what an unset size does.
+            "indent_size" => self.indent_size = parse_size(value),
+            "tab_width" => self.tab_width = parse_size(value),
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = parse_bool(value),
+            "insert_final_newline" => self.insert_final_newline = parse_bool(value),
+            _ => {}
+        }
+    }
+
+    /// Applies one Emacs file-local variable. EditorConfig property names are
+    /// accepted too, so `-*- indent_size: 2 -*-` works.
+    fn set_local_variable(&mut self, name: &str, value: &str) {
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim().trim_matches('"');
+        match name.as_str() {
+            "mode" => self.mode = Some(value.to_string()),
+            "coding" | "buffer-file-coding-system" => {
+                let (base, eol) = match value.rsplit_once('-') {
+                    Some((base, eol @ ("unix" | "dos" | "mac"))) => (base, Some(eol)),
+                    _ => (value, None),
+                };
+                self.charset = Charset::from_name(base).or(self.charset);
+                if let Some(eol) = eol.and_then(parse_line_ending) {
+                    self.line_ending = Some(eol);
+                }
+            }
+            "indent-tabs-mode" => {
+                self.indent_style = parse_bool(value)
+                    .map(|tabs| if tabs { IndentStyle::Tab } else { IndentStyle::Space });
+            }
+            "tab-width" => self.tab_width = parse_size(value),
+            "standard-indent" => self.indent_size = parse_size(value),
+            "require-final-newline" => self.insert_final_newline = parse_bool(value),
+            "delete-trailing-whitespace" => self.trim_trailing_whitespace = parse_bool(value),
+            _ if name.ends_with("-indent-offset")
+                || name.ends_with("-basic-offset")
+                || name.ends_with("-indent-level") =>
+            {
+                self.indent_size = parse_size(value);
+            }
+            _ => self.set_editorconfig(&name, value),
+        }
+    }
+}
+
+/// `name: value` pairs of a `-*- ... -*-` line, or `None` without a cookie.
+/// A bare `-*- rust -*-` names the mode.
+fn cookie_variables(line: &str) -> Option<Vec<(String, String)>> {
+    let start = line.find("-*-")? + 3;
+    let end = start + line[start..].find("-*-")?;
+    let inner = line[start..end].trim();
+    if !inner.contains(':') {
+        return Some(vec![("mode".to_string(), inner.to_string())]);
+    }
+    Some(
+        inner
+            .split(';')
+            .filter_map(|pair| pair.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect(),
+    )
+}
+
+/// Pairs of a `Local Variables:` block near the end of the file. Each line
+/// repeats the prefix and suffix around `Local Variables:`, typically a comment
+/// marker, and the block ends at `End:`.
+fn local_variables_block(text: &str) -> Vec<(String, String)> {
+    // Emacs only looks at the last 3000 characters, after the last page break.
+    let tail_start = text
+        .char_indices()
+        .rev()
+        .nth(2999)
+        .map_or(0, |(index, _)| index);
+    let tail = &text[tail_start..];
+    let tail = tail.rsplit_once('\u{c}').map_or(tail, |(_, after)| after);
+
+    let mut lines = tail.lines();
+    let Some((prefix, suffix)) = lines.by_ref().find_map(|line| {
+        let index = line.find("Local Variables:")?;
+        Some((line[..index].to_string(), line[index + 16..].trim().to_string()))
+    }) else {
+        return Vec::new();
+    };
+
+    let mut pairs = Vec::new();
+    for line in lines {
+        let body = line.strip_prefix(prefix.as_str()).unwrap_or(line).trim();
+        let body = body.strip_suffix(suffix.as_str()).unwrap_or(body).trim();
+        if body == "End:" {
+            break;
+        }
+        if let Some((name, value)) = body.split_once(':') {
+            pairs.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    pairs
+}
+
+/// Settings from the file's `-*- ... -*-` line (on the first line, or the
+/// second after `#!`) and its `Local Variables:` block; the block wins.
+pub fn file_local_variables(text: &str) -> FileSettings {
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or("");
+    let cookie = cookie_variables(first).or_else(|| {
+        first
+            .starts_with("#!")
+            .then(|| lines.next().and_then(cookie_variables))
+            .flatten()
+    });
+
+    let mut settings = FileSettings::default();
+    for (name, value) in cookie.into_iter().flatten().chain(local_variables_block(text)) {
+        settings.set_local_variable(&name, &value);
+    }
+    settings
+}
+
+impl BufferState {
+    /// Applies settings resolved when the file was visited.
+    pub fn apply_file_settings(&mut self, settings: FileSettings) {
+        if let Some(mode) = settings.mode.as_deref().and_then(find_mode) {
+            self.major_mode = mode;
+        }
+        if let Some(line_ending) = settings.line_ending {
+            self.line_ending = line_ending.to_string();
+        }
+        if let Some(charset) = settings.charset {
+            self.original_encoding = charset.label().to_string();
+        }
+        if let Some(trim) = settings.trim_trailing_whitespace {
+            if trim != self.has_minor_mode(MinorMode::DeleteTrailingWhitespaceOnSave) {
+                self.set_minor_mode(MinorMode::DeleteTrailingWhitespaceOnSave, trim);
+            }
+        }
+        self.file_settings = settings;
+    }
+
+    /// Columns per indentation level.
+    pub fn indent_width(&self) -> usize {
+        self.file_settings
+            .indent_size
+            .or(self.file_settings.tab_width)
+            .unwrap_or(self.major_mode.indent.width)
+    }
+
+    /// Columns a tab character advances to.
+    pub fn tab_width(&self) -> usize {
+        self.file_settings
+            .tab_width
+            .or(self.file_settings.indent_size)
+            .unwrap_or(self.major_mode.indent.width)
+    }
+
+    pub fn indent_with_tabs(&self) -> bool {
+        match self.file_settings.indent_style {
+            Some(style) => style == IndentStyle::Tab,
+            None => self.major_mode.indent.use_tabs,
+        }
+    }
+
+    /// `text` with a newline added at its end when it is non-empty, lacks one
+    /// and `insert_final_newline` is set.
+    pub fn with_final_newline(&self, mut text: String) -> String {
+        let wanted = self.file_settings.insert_final_newline == Some(true);
+        if wanted && !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_local_variables, Charset, FileSettings, IndentStyle};
+
+    #[test]
+    fn reads_cookie_and_local_variables_block() {
+        let text = "#!/bin/sh\n# -*- mode: shell; coding: shift_jis-unix -*-\necho\n\
+                    # Local Variables:\n# indent-tabs-mode: t\n# sh-basic-offset: 8\n\
+                    # require-final-newline: t\n# End:\n";
+        let settings = file_local_variables(text);
+        assert_eq!(
+            settings,
+            FileSettings {
+                mode: Some("shell".to_string()),
+                line_ending: Some("LF"),
+                charset: Some(Charset::ShiftJis),
+                indent_style: Some(IndentStyle::Tab),
+                indent_size: Some(8),
+                insert_final_newline: Some(true),
+                ..FileSettings::default()
+            }
+        );
+        assert_eq!(Charset::ShiftJis.decode(&Charset::ShiftJis.encode("日本").unwrap()), "日本");
+        assert_eq!(Charset::Utf16Le.decode(&Charset::Utf16Le.encode("é").unwrap()), "é");
+        assert_eq!(
+            Charset::Latin1.encode("café 日本"),
+            Err("Latin-1 cannot encode every character in the buffer".to_string())
+        );
+    }
+}
@@ -0,0 +1,889 @@
+//! Line tokenizers for the languages femto highlights.
+
+use crate::editor::highlight::{LexState, StyleSpan, TokenStyle, Tokenizer};
+
+pub fn grammar_for(mode: &str) -> Option<Tokenizer> {
+    let tokenizer: Tokenizer = match mode {
+        "markdown" => markdown,
+        "rust" => rust,
+        "json" => json,
+        "yaml" => yaml,
+        "toml" => toml,
+        "python" => python,
+        "shell" => shell,
+        _ => return None,
+    };
+    Some(tokenizer)
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+    "unsafe", "use", "where", "while", "yield",
+];
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+    "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+    "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+    "yield", "match", "case",
+];
+
+const SHELL_KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+    "in", "function", "return", "local", "export", "readonly", "declare", "select", "break",
+    "continue", "exit", "source",
+];
+
+fn push(spans: &mut Vec<StyleSpan>, start: usize, end: usize, style: TokenStyle) {
+    if end > start {
+        spans.push(StyleSpan { start, end, style });
+    }
+}
+
+fn is_ident_start(ch: char) -> bool {
+    ch.is_alphabetic() || ch == '_'
+}
+
+fn is_ident_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+fn starts_with(chars: &[char], at: usize, pattern: &str) -> bool {
+    pattern
+        .chars()
+        .enumerate()
+        .all(|(offset, ch)| chars.get(at + offset) == Some(&ch))
+}
+
+fn find(chars: &[char], from: usize, pattern: &str) -> Option<usize> {
+    (from..chars.len()).find(|&i| starts_with(chars, i, pattern))
+}
+
+fn ident_end(chars: &[char], from: usize) -> usize {
+    let mut i = from;
+    while i < chars.len() && is_ident_char(chars[i]) {
+        i += 1;
+    }
+    i
+}
+
+fn next_non_space(chars: &[char], from: usize) -> Option<char> {
+    chars[from.min(chars.len())..]
+        .iter()
+        .copied()
+        .find(|c| !c.is_whitespace())
+}
+
+/// Scans a string body starting after its opening quote. Returns the index after
+/// the closing quote, or the line length and `false` when the string continues.
+fn scan_quoted(chars: &[char], from: usize, quote: char, escapes: bool) -> (usize, bool) {
+    let mut i = from;
+    while i < chars.len() {
+        if escapes && chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if chars[i] == quote {
+            return (i + 1, true);
+        }
+        i += 1;
+    }
+    (chars.len(), false)
+}
+
+fn scan_triple(chars: &[char], from: usize, quote: char) -> (usize, bool) {
+    let close: String = [quote; 3].iter().collect();
+    let mut i = from;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if starts_with(chars, i, &close) {
+            return (i + 3, true);
+        }
+        i += 1;
+    }
+    (chars.len(), false)
+}
+
+/// Digits, hex/float/exponent forms and `_` separators.
+fn number_end(chars: &[char], from: usize) -> usize {
+    let mut i = from;
+    while i < chars.len() {
+        let ch = chars[i];
+        let decimal_point =
+            ch == '.' && chars.get(i + 1).is_some_and(|next| next.is_ascii_digit());
+        if ch.is_ascii_alphanumeric() || ch == '_' || decimal_point {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+fn is_number_start(chars: &[char], i: usize) -> bool {
+    chars[i].is_ascii_digit() && (i == 0 || !is_ident_char(chars[i - 1]))
+}
+
+fn rust_block_comment(chars: &[char], from: usize, mut depth: u8) -> (usize, u8) {
+    let mut i = from;
+    while i < chars.len() {
+        if starts_with(chars, i, "/*") {
+            depth = depth.saturating_add(1);
+            i += 2;
+        } else if starts_with(chars, i, "*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return (i, 0);
+            }
+        } else {
+            i += 1;
+        }
+    }
+    (chars.len(), depth)
+}
+
+fn rust_raw_end(chars: &[char], from: usize, hashes: u8) -> (usize, bool) {
+    let close: String = std::iter::once('"')
+        .chain(std::iter::repeat_n('#', hashes as usize))
+        .collect();
+    match find(chars, from, &close) {
+        Some(i) => (i + close.len(), true),
+        None => (chars.len(), false),
+    }
+}
+
+fn rust(chars: &[char], state: LexState) -> (Vec<StyleSpan>, LexState) {
+    let mut spans = Vec::new();
+    let n = chars.len();
+    let mut i = match state {
+        LexState::BlockComment(depth) => {
+            let (end, depth) = rust_block_comment(chars, 0, depth);
+            push(&mut spans, 0, end, TokenStyle::Comment);
+            if depth > 0 {
+                return (spans, LexState::BlockComment(depth));
+            }
+            end
+        }
+        LexState::String(quote) => {
+            let (end, closed) = scan_quoted(chars, 0, quote, true);
+            push(&mut spans, 0, end, TokenStyle::String);
+            if !closed {
+                return (spans, state);
+            }
+            end
+        }
+        LexState::RawString(hashes) => {
+            let (end, closed) = rust_raw_end(chars, 0, hashes);
+            push(&mut spans, 0, end, TokenStyle::String);
+            if !closed {
+                return (spans, state);
+            }
+            end
+        }
+        _ => 0,
+    };
+
+    while i < n {
+        let ch = chars[i];
+        if starts_with(chars, i, "//") {
+            push(&mut spans, i, n, TokenStyle::Comment);
+            break;
+        }
+        if starts_with(chars, i, "/*") {
+            let (end, depth) = rust_block_comment(chars, i + 2, 1);
+            push(&mut spans, i, end, TokenStyle::Comment);
+            if depth > 0 {
+                return (spans, LexState::BlockComment(depth));
+            }
+            i = end;
+            continue;
+        }
+
+        // Raw strings: r"..", r#".."#, br"..".
+        let raw_start = match ch {
+            'r' => Some(i + 1),
+            'b' if chars.get(i + 1) == Some(&'r') => Some(i + 2),
+            _ => None,
+        };
+        if let Some(start) = raw_start.filter(|_| i == 0 || !is_ident_char(chars[i - 1])) {
+            let hashes = chars[start.min(n)..].iter().take_while(|c| **c == '#').count();
+            if chars.get(start + hashes) == Some(&'"') {
+                let (end, closed) = rust_raw_end(chars, start + hashes + 1, hashes as u8);
+                push(&mut spans, i, end, TokenStyle::String);
+                if !closed {
+                    return (spans, LexState::RawString(hashes as u8));
+                }
+                i = end;
+                continue;
+            }
+        }
+
+        let string_start = match ch {
+            '"' => Some(i + 1),
+            'b' if chars.get(i + 1) == Some(&'"') => Some(i + 2),
+            _ => None,
+        };
+        if let Some(start) = string_start.filter(|_| i == 0 || !is_ident_char(chars[i - 1])) {
+            let (end, closed) = scan_quoted(chars, start, '"', true);
+            push(&mut spans, i, end, TokenStyle::String);
+            if !closed {
+                return (spans, LexState::String('"'));
+            }
+            i = end;
+            continue;
+        }
+
+        if ch == '\'' {
+            // A char literal ('a', '\n', '\u{1F600}') or a lifetime ('a).
+            if chars.get(i + 1) == Some(&'\\') {
+                let (end, _) = scan_quoted(chars, i + 1, '\'', true);
+                push(&mut spans, i, end, TokenStyle::String);
+                i = end;
+                continue;
+            }
+            if chars.get(i + 2) == Some(&'\'') {
+                push(&mut spans, i, i + 3, TokenStyle::String);
+                i += 3;
+                continue;
+            }
+            let end = ident_end(chars, i + 1);
+            push(&mut spans, i, end, TokenStyle::Label);
+            i = end.max(i + 1);
+            continue;
+        }
+
+        if ch == '#' && (chars.get(i + 1) == Some(&'[') || starts_with(chars, i + 1, "![")) {
+            let mut depth = 0;
+            let mut end = n;
+            for (j, c) in chars.iter().enumerate().skip(i) {
+                match c {
+                    '[' => depth += 1,
+                    ']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = j + 1;
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            push(&mut spans, i, end, TokenStyle::Attribute);
+            i = end;
+            continue;
+        }
+
+        if is_number_start(chars, i) {
+            let end = number_end(chars, i);
+            push(&mut spans, i, end, TokenStyle::Number);
+            i = end;
+            continue;
+        }
+
+        if is_ident_start(ch) {
+            let end = ident_end(chars, i);
+            let word: String = chars[i..end].iter().collect();
+            let style = if RUST_KEYWORDS.contains(&word.as_str()) {
+                Some(TokenStyle::Keyword)
+            } else if chars.get(end) == Some(&'!') && chars.get(end + 1) != Some(&'=') {
+                push(&mut spans, i, end + 1, TokenStyle::Macro);
+                i = end + 1;
+                continue;
+            } else if word == "true"
+                || word == "false"
+                || (word.len() > 1
+                    && word.chars().any(char::is_alphabetic)
+                    && word.chars().all(|c| c.is_uppercase() || c.is_ascii_digit() || c == '_'))
+            {
+                Some(TokenStyle::Constant)
+            } else if ch.is_uppercase() {
+                Some(TokenStyle::Type)
+            } else if next_non_space(chars, end) == Some('(') {
+                Some(TokenStyle::Function)
+            } else {
+                None
+            };
+            if let Some(style) = style {
+                push(&mut spans, i, end, style);
+            }
+            i = end;
+            continue;
+        }
+        i += 1;
+    }
+    (spans, LexState::Normal)
+}
+
+const PYTHON_STRING_PREFIXES: &[&str] = &["r", "b", "f", "u", "rb", "br", "fr", "rf"];
+
+fn python(chars: &[char], state: LexState) -> (Vec<StyleSpan>, LexState) {
+    let mut spans = Vec::new();
+    let n = chars.len();
+    let mut i = 0;
+    if let LexState::TripleString(quote) = state {
+        let (end, closed) = scan_triple(chars, 0, quote);
+        push(&mut spans, 0, end, TokenStyle::String);
+        if !closed {
+            return (spans, state);
+        }
+        i = end;
+    }
+
+    let mut previous_word = String::new();
+    while i < n {
+        let ch = chars[i];
+        if ch == '#' {
+            push(&mut spans, i, n, TokenStyle::Comment);
+            break;
+        }
+
+        // A string, possibly with a prefix such as r, b or f.
+        let mut quote_at = None;
+        if ch == '"' || ch == '\'' {
+            quote_at = Some(i);
+        } else if is_ident_start(ch) && (i == 0 || !is_ident_char(chars[i - 1])) {
+            let end = ident_end(chars, i);
+            let word: String = chars[i..end].iter().collect::<String>().to_lowercase();
+            if PYTHON_STRING_PREFIXES.contains(&word.as_str())
+                && matches!(chars.get(end), Some('"') | Some('\''))
+            {
+                quote_at = Some(end);
+            }
+        }
+        if let Some(q) = quote_at {
+            let quote = chars[q];
+            if starts_with(chars, q, &[quote; 3].iter().collect::<String>()) {
+                let (end, closed) = scan_triple(chars, q + 3, quote);
+                push(&mut spans, i, end, TokenStyle::String);
+                if !closed {
+                    return (spans, LexState::TripleString(quote));
+                }
+                i = end;
+            } else {
+                let (end, _) = scan_quoted(chars, q + 1, quote, true);
+                push(&mut spans, i, end, TokenStyle::String);
+                i = end;
+            }
+            previous_word.clear();
+            continue;
+        }
+
+        if ch == '@' && chars[..i].iter().all(|c| c.is_whitespace()) {
+            let mut end = i + 1;
+            while end < n && (is_ident_char(chars[end]) || chars[end] == '.') {
+                end += 1;
+            }
+            push(&mut spans, i, end, TokenStyle::Attribute);
+            i = end;
+            continue;
+        }
+
+        if is_number_start(chars, i) {
+            let end = number_end(chars, i);
+            push(&mut spans, i, end, TokenStyle::Number);
+            i = end;
+            continue;
+        }
+
+        if is_ident_start(ch) {
+            let end = ident_end(chars, i);
+            let word: String = chars[i..end].iter().collect();
+            let style = if previous_word == "def" {
+                Some(TokenStyle::Function)
+            } else if previous_word == "class" {
+                Some(TokenStyle::Type)
+            } else if PYTHON_KEYWORDS.contains(&word.as_str()) {
+                Some(TokenStyle::Keyword)
+            } else if matches!(word.as_str(), "True" | "False" | "None") {
+                Some(TokenStyle::Constant)
+            } else if word == "self" || word == "cls" {
+                Some(TokenStyle::Variable)
+            } else if next_non_space(chars, end) == Some('(') {
+                Some(TokenStyle::Function)
+            } else {
+                None
+            };
+            if let Some(style) = style {
+                push(&mut spans, i, end, style);
+            }
+            previous_word = word;
+            i = end;
+            continue;
+        }
+        if !ch.is_whitespace() {
+            previous_word.clear();
+        }
+        i += 1;
+    }
+    (spans, LexState::Normal)
+}
+
+fn shell(chars: &[char], state: LexState) -> (Vec<StyleSpan>, LexState) {
+    let mut spans = Vec::new();
+    let n = chars.len();
+    let mut i = 0;
+    if let LexState::String(quote) = state {
+        let (end, closed) = scan_quoted(chars, 0, quote, quote == '"');
+        push(&mut spans, 0, end, TokenStyle::String);
+        if !closed {
+            return (spans, state);
+        }
+        i = end;
+    }
+
+    while i < n {
+        let ch = chars[i];
+        let word_start = i == 0 || chars[i - 1].is_whitespace() || ";|&(".contains(chars[i - 1]);
+        if ch == '#' && word_start {
+            push(&mut spans, i, n, TokenStyle::Comment);
+            break;
+        }
+        if ch == '\\' {
+            i += 2;
+            continue;
+        }
+        if ch == '\'' || ch == '"' {
+            let (end, closed) = scan_quoted(chars, i + 1, ch, ch == '"');
+            push(&mut spans, i, end, TokenStyle::String);
+            if !closed {
+                return (spans, LexState::String(ch));
+            }
+            i = end;
+            continue;
+        }
+        if ch == '$' {
+            let end = match chars.get(i + 1) {
+                Some('{') => find(chars, i + 2, "}").map_or(n, |j| j + 1),
+                Some(c) if is_ident_start(*c) => ident_end(chars, i + 1),
+                Some(c) if c.is_ascii_digit() || "@*#?$!-".contains(*c) => i + 2,
+                _ => i + 1,
+            };
+            if end > i + 1 {
+                push(&mut spans, i, end, TokenStyle::Variable);
+            }
+            i = end;
+            continue;
+        }
+        if is_ident_start(ch) && word_start {
+            let end = ident_end(chars, i);
+            let word: String = chars[i..end].iter().collect();
+            if chars.get(end) == Some(&'=') {
+                push(&mut spans, i, end, TokenStyle::Variable);
+            } else if SHELL_KEYWORDS.contains(&word.as_str())
+                && chars.get(end).is_none_or(|c| c.is_whitespace() || *c == ';')
+            {
+                push(&mut spans, i, end, TokenStyle::Keyword);
+            } else if starts_with(chars, end, "()") {
+                push(&mut spans, i, end, TokenStyle::Function);
+            }
+            i = end;
+            continue;
+        }
+        i += 1;
+    }
+    (spans, LexState::Normal)
+}
+
+fn json(chars: &[char], _state: LexState) -> (Vec<StyleSpan>, LexState) {
+    let mut spans = Vec::new();
+    let n = chars.len();
+    let mut i = 0;
+    while i < n {
+        let ch = chars[i];
+        if starts_with(chars, i, "//") {
+            push(&mut spans, i, n, TokenStyle::Comment);
+            break;
+        }
+        if ch == '"' {
+            let (end, _) = scan_quoted(chars, i + 1, '"', true);
+            let style = if next_non_space(chars, end) == Some(':') {
+                TokenStyle::Key
+            } else {
+                TokenStyle::String
+            };
+            push(&mut spans, i, end, style);
+            i = end;
+            continue;
+        }
+        if ch == '-' || ch.is_ascii_digit() {
+            let mut end = i + 1;
+            while end < n && (chars[end].is_ascii_digit() || ".eE+-".contains(chars[end])) {
+                end += 1;
+            }
+            push(&mut spans, i, end, TokenStyle::Number);
+            i = end;
+            continue;
+        }
+        if ch.is_ascii_alphabetic() {
+            let end = ident_end(chars, i);
+            let word: String = chars[i..end].iter().collect();
+            if matches!(word.as_str(), "true" | "false" | "null") {
+                push(&mut spans, i, end, TokenStyle::Constant);
+            }
+            i = end;
+            continue;
+        }
+        i += 1;
+    }
+    (spans, LexState::Normal)
+}
+
+fn indentation(chars: &[char]) -> usize {
+    chars.iter().take_while(|c| **c == ' ' || **c == '\t').count()
+}
+
+/// Plain YAML scalars that are not left as ordinary text.
+fn yaml_scalar_style(word: &str) -> Option<TokenStyle> {
+    match word {
+        "true" | "false" | "True" | "False" | "yes" | "no" | "null" | "Null" | "~" => {
+            Some(TokenStyle::Constant)
+        }
+        _ if word.parse::<f64>().is_ok() => Some(TokenStyle::Number),
+        _ => None,
+    }
+}
+
+fn yaml(chars: &[char], state: LexState) -> (Vec<StyleSpan>, LexState) {
+    let mut spans = Vec::new();
+    let n = chars.len();
+    let indent = indentation(chars);
+    if let LexState::BlockScalar(parent) = state {
+        if indent == n {
+            return (spans, state);
+        }
+        if indent > parent as usize {
+            push(&mut spans, indent, n, TokenStyle::String);
+            return (spans, state);
+        }
+    }
+
+    if starts_with(chars, 0, "---") || starts_with(chars, 0, "...") {
+        push(&mut spans, 0, 3, TokenStyle::Keyword);
+        return (spans, LexState::Normal);
+    }
+
+    let mut i = indent;
+    while starts_with(chars, i, "- ") || (i + 1 == n && chars[i] == '-') {
+        push(&mut spans, i, i + 1, TokenStyle::ListMarker);
+        i += 1;
+        while i < n && chars[i] == ' ' {
+            i += 1;
+        }
+    }
+
+    // A mapping key ends at the first ": " (or ":" at the end) outside quotes.
+    if i < n && chars[i] != '#' {
+        let key_end = if chars[i] == '"' || chars[i] == '\'' {
+            let (end, _) = scan_quoted(chars, i + 1, chars[i], chars[i] == '"');
+            (chars.get(end) == Some(&':')).then_some(end)
+        } else {
+            (i..n).find(|&j| {
+                chars[j] == ':' && chars.get(j + 1).is_none_or(|c| c.is_whitespace())
+            })
+            .filter(|&j| !chars[i..j].windows(2).any(|w| w[0] == ' ' && w[1] == '#'))
+        };
+        if let Some(end) = key_end {
+            push(&mut spans, i, end, TokenStyle::Key);
+            i = end + 1;
+        }
+    }
+
+    let mut next_state = LexState::Normal;
+    while i < n {
+        let ch = chars[i];
+        if ch == '#' && (i == 0 || chars[i - 1].is_whitespace()) {
+            push(&mut spans, i, n, TokenStyle::Comment);
+            break;
+        }
+        if ch.is_whitespace() || ",[]{}".contains(ch) {
+            i += 1;
+            continue;
+        }
+        if ch == '"' || ch == '\'' {
+            let (end, _) = scan_quoted(chars, i + 1, ch, ch == '"');
+            push(&mut spans, i, end, TokenStyle::String);
+            i = end;
+            continue;
+        }
+        let mut end = i;
+        while end < n && !chars[end].is_whitespace() && !",[]{}".contains(chars[end]) {
+            end += 1;
+        }
+        let word: String = chars[i..end].iter().collect();
+        let is_block_indicator = (ch == '|' || ch == '>')
+            && word[1..].chars().all(|c| c == '+' || c == '-' || c.is_ascii_digit())
+            && next_non_space(chars, end).is_none_or(|c| c == '#');
+        let style = match ch {
+            '&' | '*' => Some(TokenStyle::Variable),
+            '!' => Some(TokenStyle::Type),
+            _ if is_block_indicator => {
+                next_state = LexState::BlockScalar(indent as u16);
+                Some(TokenStyle::Keyword)
+            }
+            _ => yaml_scalar_style(&word),
+        };
+        if let Some(style) = style {
+            push(&mut spans, i, end, style);
+        }
+        i = end;
+    }
+    (spans, next_state)
+}
+
+fn toml(chars: &[char], state: LexState) -> (Vec<StyleSpan>, LexState) {
+    let mut spans = Vec::new();
+    let n = chars.len();
+    let mut i = 0;
+    if let LexState::TripleString(quote) = state {
+        let (end, closed) = scan_triple(chars, 0, quote);
+        push(&mut spans, 0, end, TokenStyle::String);
+        if !closed {
+            return (spans, state);
+        }
+        i = end;
+    } else {
+        let indent = indentation(chars);
+        if chars.get(indent) == Some(&'[') {
+            let end = find(chars, indent, "]")
+                .map(|j| if chars.get(j + 1) == Some(&']') { j + 2 } else { j + 1 })
+                .unwrap_or(n);
+            push(&mut spans, indent, end, TokenStyle::Type);
+            i = end;
+        }
+    }
+
+    while i < n {
+        let ch = chars[i];
+        if ch == '#' {
+            push(&mut spans, i, n, TokenStyle::Comment);
+            break;
+        }
+        if ch == '"' || ch == '\'' {
+            if starts_with(chars, i, &[ch; 3].iter().collect::<String>()) {
+                let (end, closed) = scan_triple(chars, i + 3, ch);
+                push(&mut spans, i, end, TokenStyle::String);
+                if !closed {
+                    return (spans, LexState::TripleString(ch));
+                }
+                i = end;
+                continue;
+            }
+            let (end, _) = scan_quoted(chars, i + 1, ch, ch == '"');
+            let style = if next_non_space(chars, end) == Some('=') {
+                TokenStyle::Key
+            } else {
+                TokenStyle::String
+            };
+            push(&mut spans, i, end, style);
+            i = end;
+            continue;
+        }
+        let starts_number = ch.is_ascii_digit()
+            || ((ch == '+' || ch == '-') && chars.get(i + 1).is_some_and(char::is_ascii_digit));
+        if starts_number && (i == 0 || !is_ident_char(chars[i - 1])) {
+            let mut end = i + 1;
+            while end < n && (chars[end].is_ascii_alphanumeric() || "_:.+-".contains(chars[end])) {
+                end += 1;
+            }
+            push(&mut spans, i, end, TokenStyle::Number);
+            i = end;
+            continue;
+        }
+        if is_ident_char(ch) || ch == '-' {
+            let mut end = i;
+            while end < n && (is_ident_char(chars[end]) || "-.".contains(chars[end])) {
+                end += 1;
+            }
+            let word: String = chars[i..end].iter().collect();
+            let is_key = next_non_space(chars, end) == Some('=')
+                && !chars[end..].iter().collect::<String>().trim_start().starts_with("==");
+            if is_key {
+                push(&mut spans, i, end, TokenStyle::Key);
+            } else if word == "true" || word == "false" || word == "inf" || word == "nan" {
+                push(&mut spans, i, end, TokenStyle::Constant);
+            }
+            i = end;
+            continue;
+        }
+        i += 1;
+    }
+    (spans, LexState::Normal)
+}
+
+fn is_fence(chars: &[char]) -> bool {
+    starts_with(chars, indentation(chars), "```")
+}
+
+fn is_heading(chars: &[char]) -> bool {
+    let indent = chars.iter().take_while(|c| **c == ' ').count();
+    let level = chars[indent..].iter().take_while(|c| **c == '#').count();
+    indent <= 3 && (1..=6).contains(&level) && chars.get(indent + level).is_some_and(|c| c.is_whitespace())
+}
+
+fn is_rule(chars: &[char]) -> bool {
+    let marks: Vec<char> = chars.iter().copied().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && "-*_".contains(marks[0]) && marks.iter().all(|c| *c == marks[0])
+}
+
+/// Width of a list marker such as `- `, `* ` or `12. `, including the spaces after it.
+fn list_marker_end(chars: &[char]) -> Option<usize> {
+    let indent = indentation(chars);
+    let digits = chars[indent..].iter().take_while(|c| c.is_ascii_digit()).count();
+    let marker_end = match chars.get(indent + digits) {
+        Some('-' | '*' | '+') if digits == 0 => indent + 1,
+        Some('.') if digits > 0 => indent + digits + 1,
+        _ => return None,
+    };
+    let spaces = chars[marker_end..].iter().take_while(|c| c.is_whitespace()).count();
+    (spaces > 0).then_some(marker_end + spaces)
+}
+
+/// Inline Markdown: code, URLs, HTML tags, strong, emphasis and `[text](url)` links.
+fn markdown_inline(chars: &[char], from: usize, spans: &mut Vec<StyleSpan>) {
+    let n = chars.len();
+    let mut i = from;
+    while i < n {
+        let ch = chars[i];
+        if ch == '`' {
+            if let Some(close) = find(chars, i + 1, "`") {
+                push(spans, i, close + 1, TokenStyle::Code);
+                i = close + 1;
+                continue;
+            }
+        }
+        if starts_with(chars, i, "http://") || starts_with(chars, i, "https://") {
+            let mut end = i;
+            while end < n && !chars[end].is_whitespace() && !"<>])\"".contains(chars[end]) {
+                end += 1;
+            }
+            push(spans, i, end, TokenStyle::LinkUrl);
+            i = end;
+            continue;
+        }
+        if ch == '<' && chars.get(i + 1).is_some_and(|c| c.is_ascii_alphabetic() || *c == '/') {
+            if let Some(close) = find(chars, i + 1, ">") {
+                push(spans, i, close + 1, TokenStyle::HtmlTag);
+                i = close + 1;
+                continue;
+            }
+        }
+        if starts_with(chars, i, "**") {
+            if let Some(close) = find(chars, i + 2, "**").filter(|&c| c > i + 2) {
+                if !chars[i + 2..close].contains(&'*') {
+                    push(spans, i, close + 2, TokenStyle::Strong);
+                    i = close + 2;
+                    continue;
+                }
+            }
+        }
+        if ch == '*' {
+            if let Some(close) = find(chars, i + 1, "*").filter(|&c| c > i + 1) {
+                push(spans, i, close + 1, TokenStyle::Emphasis);
+                i = close + 1;
+                continue;
+            }
+        }
+        if ch == '[' {
+            let close = find(chars, i + 1, "]").filter(|&c| c > i + 1);
+            if let Some(close) = close.filter(|&c| chars.get(c + 1) == Some(&'(')) {
+                if let Some(paren) = find(chars, close + 2, ")").filter(|&p| p > close + 2) {
+                    push(spans, i, close + 1, TokenStyle::LinkText);
+                    push(spans, close + 1, paren + 1, TokenStyle::LinkUrl);
+                    i = paren + 1;
+                    continue;
+                }
+            }
+        }
+        i += 1;
+    }
+}
+
+fn markdown(chars: &[char], state: LexState) -> (Vec<StyleSpan>, LexState) {
+    let mut spans = Vec::new();
+    let n = chars.len();
+    if is_fence(chars) {
+        push(&mut spans, 0, n, TokenStyle::Fence);
+        let next = if state == LexState::Fence {
+            LexState::Normal
+        } else {
+            LexState::Fence
+        };
+        return (spans, next);
+    }
+    if state == LexState::Fence {
+        push(&mut spans, 0, n, TokenStyle::CodeBlock);
+        return (spans, state);
+    }
+
+    if is_heading(chars) {
+        push(&mut spans, 0, n, TokenStyle::Heading);
+    } else if chars[indentation(chars)..].first() == Some(&'>') {
+        push(&mut spans, 0, n, TokenStyle::Quote);
+    } else if is_rule(chars) {
+        push(&mut spans, 0, n, TokenStyle::Rule);
+    } else if let Some(end) = list_marker_end(chars) {
+        push(&mut spans, 0, end, TokenStyle::ListMarker);
+        markdown_inline(chars, end, &mut spans);
+    } else {
+        markdown_inline(chars, 0, &mut spans);
+    }
+    (spans, LexState::Normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::grammar_for;
+    use crate::editor::highlight::{LexState, TokenStyle};
+
+    fn styles(mode: &str, lines: &[&str]) -> Vec<Vec<(String, TokenStyle)>> {
+        let tokenize = grammar_for(mode).unwrap();
+        let mut state = LexState::Normal;
+        lines
+            .iter()
+            .map(|line| {
+                let chars: Vec<char> = line.chars().collect();
+                let (spans, next) = tokenize(&chars, state);
+                state = next;
+                spans
+                    .into_iter()
+                    .map(|s| (chars[s.start..s.end].iter().collect(), s.style))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grammars_carry_state_across_lines() {
+        use TokenStyle::*;
+        let s = |text: &str, style| (text.to_string(), style);
+
+        let md = styles("markdown", &["# Title", "```", "# not a heading", "```", "- see [a](b)"]);
+        assert_eq!(md[0], [s("# Title", Heading)]);
+        assert_eq!(md[2], [s("# not a heading", CodeBlock)]);
+        assert_eq!(md[4], [s("- ", ListMarker), s("[a]", LinkText), s("(b)", LinkUrl)]);
+
+        let py = styles("python", &["def f(x):", "    return '''a", "b''' # done"]);
+        assert_eq!(py[0], [s("def", Keyword), s("f", Function)]);
+        assert_eq!(py[2], [s("b'''", String), s("# done", Comment)]);
+
+        let yaml = styles("yaml", &["key: |", "  text: here", "other: 42 # n"]);
+        assert_eq!(yaml[1], [s("text: here", String)]);
+        assert_eq!(yaml[2], [s("other", Key), s("42", Number), s("# n", Comment)]);
+
+        let toml = styles("toml", &["[package]", "name = \"femto\""]);
+        assert_eq!(toml[1], [s("name", Key), s("\"femto\"", String)]);
+
+        let sh = styles("shell", &["if [ -n \"$HOME\" ]; then echo $1; fi"]);
+        assert_eq!(sh[0][0], s("if", Keyword));
+        assert!(sh[0].contains(&s("$1", Variable)));
+
+        let json = styles("json", &["{\"a\": [1, true]}"]);
+        assert_eq!(json[0], [s("\"a\"", Key), s("1", Number), s("true", Constant)]);
+    }
+}
@@ -0,0 +1,150 @@
+use regex::Regex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::editor::state::{BufferState, SpecialBuffer};
+
+pub const GREP_BUFFER_NAME: &str = "*grep*";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrepMatch {
+    pub path: PathBuf,
+    pub line: usize,
+    pub col: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GrepResults {
+    pub root: PathBuf,
+    pub pattern: String,
+    pub matches: Vec<GrepMatch>,
+    pub files_scanned: usize,
+}
+
+/// Number of header lines written before the first match in the *grep* buffer.
+const HEADER_LINES: usize = 3;
+
+/// Finds the first match of `regex` on every line of `text`.
+/// Columns are 1-based character offsets, like `line_col`.
+pub fn find_matches_in_text(path: &Path, text: &str, regex: &Regex) -> Vec<GrepMatch> {
+    let mut matches = Vec::new();
+    for (index, line) in text.split('\n').enumerate() {
+        if let Some(found) = regex.find(line) {
+            matches.push(GrepMatch {
+                path: path.to_path_buf(),
+                line: index + 1,
+                col: line[..found.start()].chars().count() + 1,
+                text: line.to_string(),
+            });
+        }
+    }
+    matches
+}
+
+impl GrepResults {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("-*- mode: grep -*-\n");
+        out.push_str(&format!(
+            "Grep for \"{}\" in {}\n\n",
+            self.pattern,
+            self.root.display()
+        ));
+        for m in &self.matches {
+            let shown = m.path.strip_prefix(&self.root).unwrap_or(&m.path);
+            out.push_str(&format!(
+                "{}:{}:{}: {}\n",
+                shown.display(),
+                m.line,
+                m.col,
+                m.text
+            ));
+        }
+        let files_with_matches = {
+            let mut paths: Vec<&PathBuf> = self.matches.iter().map(|m| &m.path).collect();
+            paths.dedup();
+            paths.len()
+        };
+        out.push('\n');
+        out.push_str(&format!(
+            "Grep finished with {} matches in {} files ({} scanned)\n",
+            self.matches.len(),
+            files_with_matches,
+            self.files_scanned
+        ));
+        out
+    }
+
+    /// Returns the match listed on the given 1-based line of the rendered buffer.
+    pub fn match_at_line(&self, line: usize) -> Option<&GrepMatch> {
+        line.checked_sub(HEADER_LINES + 1)
+            .and_then(|index| self.matches.get(index))
+    }
+}
+
+impl BufferState {
+    pub fn load_grep_results(&mut self, results: GrepResults) {
+        let text = results.render();
+        let status = format!("Grep finished ({} matches)", results.matches.len());
+        self.replace_contents(text);
+        self.cursor = 0;
+        if !results.matches.is_empty() {
+            if let Some(cursor) = self.line_col_to_cursor(HEADER_LINES + 1, 1) {
+                self.cursor = cursor;
+            }
+        }
+        self.file_path = None;
+        self.query_replace_session = None;
+        self.special = Some(SpecialBuffer::Grep(results));
+        self.set_status_message(Some(status));
+    }
+
+    pub fn grep_match_at_cursor(&self) -> Option<&GrepMatch> {
+        let Some(SpecialBuffer::Grep(results)) = &self.special else {
+            return None;
+        };
+        let (line, _) = self.line_col();
+        results.match_at_line(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_matches_in_text, GrepResults};
+    use crate::editor::state::BufferState;
+    use regex::Regex;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn reports_first_match_per_line_with_char_columns() {
+        let regex = Regex::new("foo").unwrap();
+        let matches = find_matches_in_text(Path::new("a.txt"), "xfoo foo\nnone\nあfoo", &regex);
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].line, matches[0].col), (1, 2));
+        assert_eq!((matches[1].line, matches[1].col), (3, 2));
+    }
+
+    #[test]
+    fn cursor_line_maps_back_to_match() {
+        let root = PathBuf::from("/notes");
+        let regex = Regex::new("todo").unwrap();
+        let mut matches = find_matches_in_text(&root.join("a.md"), "todo one\n", &regex);
+        matches.extend(find_matches_in_text(&root.join("b.md"), "\n\ntodo two\n", &regex));
+        let mut buf = BufferState::new();
+        buf.load_grep_results(GrepResults {
+            root,
+            pattern: "todo".to_string(),
+            matches,
+            files_scanned: 2,
+        });
+
+        assert_eq!(buf.name(), "*grep*");
+        assert_eq!(buf.grep_match_at_cursor().map(|m| m.line), Some(1));
+        buf.move_next_line();
+        let second = buf.grep_match_at_cursor().expect("second match");
+        assert_eq!(second.path, PathBuf::from("/notes/b.md"));
+        assert_eq!(second.line, 3);
+    }
+}
@@ -0,0 +1,224 @@
+use serde::Serialize;
+
+use crate::editor::buffer::char_to_byte_index;
+use crate::editor::grammar::grammar_for;
+use crate::editor::state::BufferState;
+
+/// Kinds of text a grammar can mark; the frontend styles them as `tok-<name>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenStyle {
+    Keyword,
+    Type,
+    Function,
+    Macro,
+    String,
+    Number,
+    Comment,
+    Constant,
+    Attribute,
+    Variable,
+    Key,
+    Label,
+    Heading,
+    Quote,
+    ListMarker,
+    Code,
+    CodeBlock,
+    Fence,
+    Strong,
+    Emphasis,
+    LinkText,
+    LinkUrl,
+    Rule,
+    HtmlTag,
+}
+
+/// Styled run within one line; offsets are character columns, end exclusive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StyleSpan {
+    pub start: usize,
+    pub end: usize,
+    pub style: TokenStyle,
+}
+
+/// Tokenizer state carried from the end of one line to the start of the next.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LexState {
+    #[default]
+    Normal,
+    /// Inside a (possibly nested) block comment.
+    BlockComment(u8),
+    /// Inside a string delimited by this quote that continues past the line end.
+    String(char),
+    /// Inside a `"""` or `'''` string.
+    TripleString(char),
+    /// Inside a Rust raw string closed by `"` and this many `#`.
+    RawString(u8),
+    /// Inside a fenced Markdown code block.
+    Fence,
+    /// Inside a YAML block scalar whose parent line had this indentation.
+    BlockScalar(u16),
+}
+
+/// Tokenizes one line given the state at its start, returning its spans and the state at its end.
+pub type Tokenizer = fn(&[char], LexState) -> (Vec<StyleSpan>, LexState);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineSpans {
+    pub line: usize,
+    pub spans: Vec<StyleSpan>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedLine {
+    spans: Vec<StyleSpan>,
+    end: LexState,
+}
+
+/// Per-buffer tokenization cache, indexed by line of the accessible text. Line
+/// `i` starts in the state line `i - 1` ended in, so the cached prefix stays right
+/// until an edit reaches it; `BufferState::invalidate_highlight` then drops the
+/// lines from the edited one on and only those are tokenized again.
+#[derive(Debug, Default)]
+pub struct HighlightCache {
+    mode: &'static str,
+    /// `point_min` the lines are counted from.
+    origin: usize,
+    /// `point_max` the last cached line was cut at.
+    limit: usize,
+    lines: Vec<CachedLine>,
+    /// Number of lines tokenized by the last request.
+    pub last_tokenized: usize,
+}
+
+impl HighlightCache {
+    fn lines(
+        &mut self,
+        tokenize: Tokenizer,
+        text: &str,
+        first: usize,
+        end: usize,
+    ) -> Vec<LineSpans> {
+        self.last_tokenized = 0;
+        let mut out = Vec::new();
+        for (index, line) in text.split('\n').enumerate().take(end) {
+            if index == self.lines.len() {
+                let state = self.lines.last().map_or(LexState::Normal, |line| line.end);
+                let chars: Vec<char> = line.chars().collect();
+                let (spans, end) = tokenize(&chars, state);
+                self.last_tokenized += 1;
+                self.lines.push(CachedLine { spans, end });
+            }
+            if index >= first {
+                out.push(LineSpans {
+                    line: index,
+                    spans: self.lines[index].spans.clone(),
+                });
+            }
+        }
+        out
+    }
+}
+
+impl BufferState {
+    /// Style spans for accessible lines `first..end` (0-based). Buffers whose
+    /// mode has no grammar get no spans.
+    pub fn highlight_lines(&mut self, first: usize, end: usize) -> Vec<LineSpans> {
+        let Some(tokenize) = grammar_for(self.major_mode.id) else {
+            return Vec::new();
+        };
+        let (min, max) = (self.point_min(), self.point_max());
+        if self.highlight.mode != self.major_mode.id || self.highlight.origin != min {
+            self.highlight = HighlightCache {
+                mode: self.major_mode.id,
+                origin: min,
+                limit: max,
+                ..HighlightCache::default()
+            };
+        } else if self.highlight.limit != max {
+            // The line the old end cut short may go on, and lex differently.
+            self.invalidate_highlight(self.highlight.limit.min(max));
+            self.highlight.limit = max;
+        }
+        let cache = &mut self.highlight;
+        let text = self.buffer.as_str();
+        let start = char_to_byte_index(&text, min);
+        let len = char_to_byte_index(&text[start..], max - min);
+        cache.lines(tokenize, &text[start..start + len], first, end)
+    }
+
+    /// Drops cached highlighting from the line holding buffer position `pos` on,
+    /// after an edit that left the text before `pos` alone.
+    pub fn invalidate_highlight(&mut self, pos: usize) {
+        let origin = self.highlight.origin;
+        let line = match pos.checked_sub(origin) {
+            Some(offset) => {
+                let text = self.buffer.as_str();
+                text.chars().skip(origin).take(offset).filter(|&c| c == '\n').count()
+            }
+            None => 0,
+        };
+        self.highlight.lines.truncate(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenStyle;
+    use crate::editor::state::EditorState;
+
+    #[test]
+    fn lines_from_the_first_edited_one_are_tokenized_again() {
+        let mut editor = EditorState::new();
+        let buf = editor.current_mut();
+        buf.set_major_mode("rust").unwrap();
+        buf.insert_text("/* a\nb */\nfn main() {}\nlet s = \"x\";\n").unwrap();
+        editor.sync_text_edits();
+
+        let buf = editor.current_mut();
+        let lines = buf.highlight_lines(0, 10);
+        assert_eq!(buf.highlight.last_tokenized, 5);
+        assert_eq!(lines[1].spans[0].style, TokenStyle::Comment);
+        assert_eq!(lines[2].spans[0].style, TokenStyle::Keyword);
+        assert_eq!(lines[2].spans[1].style, TokenStyle::Function);
+        assert!(buf.highlight_lines(3, 4).len() == 1 && buf.highlight.last_tokenized == 0);
+
+        buf.cursor = 10;
+        buf.insert_text("pub ").unwrap();
+        editor.sync_text_edits();
+        let buf = editor.current_mut();
+        let lines = buf.highlight_lines(2, 3);
+        assert_eq!(buf.highlight.last_tokenized, 1);
+        assert_eq!((lines[0].line, lines[0].spans[0].style), (2, TokenStyle::Keyword));
+
+        // Closing the comment early changes the state every later line starts in.
+        buf.cursor = 4;
+        buf.insert_text("*/").unwrap();
+        editor.sync_text_edits();
+        let buf = editor.current_mut();
+        let lines = buf.highlight_lines(0, 10);
+        assert_eq!(buf.highlight.last_tokenized, 5);
+        assert_ne!(lines[1].spans.first().map(|span| span.style), Some(TokenStyle::Comment));
+    }
+
+    #[test]
+    fn moving_the_narrowing_end_tokenizes_the_cut_line_again() {
+        let mut editor = EditorState::new();
+        let buf = editor.current_mut();
+        buf.set_major_mode("rust").unwrap();
+        buf.insert_text("let s = \"x\ny\";\nfn f() {}\n").unwrap();
+        buf.narrow_to_region(0, 8).unwrap();
+        editor.sync_text_edits();
+
+        let buf = editor.current_mut();
+        assert_eq!(buf.highlight_lines(0, 10).len(), 1);
+        buf.widen();
+        let lines = buf.highlight_lines(0, 10);
+        assert_eq!(buf.highlight.last_tokenized, 4);
+        assert_eq!(lines[1].spans[0].style, TokenStyle::String);
+        assert_eq!(lines[2].spans[0].style, TokenStyle::Keyword);
+    }
+}
@@ -0,0 +1,335 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::editor::state::{BufferId, BufferState, EditorState, SpecialBuffer};
+
+pub const BUFFER_LIST_NAME: &str = "*Buffer List*";
+/// Number of lines before the first entry (summary, column titles, rule).
+const HEADER_LINES: usize = 3;
+/// Character offset where the buffer name starts on an entry line.
+const NAME_COLUMN: usize = 4;
+
+/// What `list_buffers` and the `*Buffer List*` buffer report about each buffer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferInfo {
+    pub id: BufferId,
+    pub name: String,
+    /// Size in characters.
+    pub size: usize,
+    pub modified: bool,
+    pub encoding: String,
+    pub line_ending: String,
+    pub file_path: Option<String>,
+    pub mode: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BufferListFilter {
+    pub modified_only: bool,
+    /// Only buffers whose file (or listed directory) lies under this directory.
+    pub directory: Option<PathBuf>,
+}
+
+impl BufferListFilter {
+    fn matches(&self, buf: &BufferState) -> bool {
+        if self.modified_only && !buf.is_modified() {
+            return false;
+        }
+        match &self.directory {
+            Some(directory) => buf
+                .file_path
+                .as_deref()
+                .or_else(|| buf.dired_listing().map(|l| l.directory.as_path()))
+                .is_some_and(|path| path.starts_with(directory)),
+            None => true,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.modified_only {
+            parts.push("modified".to_string());
+        }
+        if let Some(directory) = &self.directory {
+            parts.push(format!("in {}", directory.display()));
+        }
+        if parts.is_empty() {
+            "none".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BufferListEntry {
+    pub info: BufferInfo,
+    pub marked: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct BufferList {
+    /// Directory used for file prompts from the list.
+    pub directory: PathBuf,
+    pub filter: BufferListFilter,
+    pub entries: Vec<BufferListEntry>,
+}
+
+impl BufferList {
+    pub fn render(&self) -> String {
+        let width = self
+            .entries
+            .iter()
+            .map(|e| e.info.name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+        let mut out = format!(
+            "{} buffers (filter: {})\n",
+            self.entries.len(),
+            self.filter.describe()
+        );
+        out.push_str(&format!(
+            "MR  {:<width$}  {:>8}  {:<12}  {:<10}  File\n",
+            "Name", "Size", "Mode", "Coding"
+        ));
+        out.push_str(&format!(
+            "--  {:<width$}  {:>8}  {:<12}  {:<10}  ----\n",
+            "----", "----", "----", "------"
+        ));
+        for entry in &self.entries {
+            let info = &entry.info;
+            out.push_str(&format!(
+                "{}{}  {:<width$}  {:>8}  {:<12}  {:<10}  {}\n",
+                if entry.marked { '>' } else { ' ' },
+                if info.modified { '*' } else { ' ' },
+                info.name,
+                info.size,
+                info.mode,
+                format!("{}/{}", info.encoding, info.line_ending),
+                info.file_path.as_deref().unwrap_or("")
+            ));
+        }
+        out
+    }
+
+    /// Index into `entries` for a 1-based buffer line.
+    pub fn entry_index_at_line(&self, line: usize) -> Option<usize> {
+        let index = line.checked_sub(HEADER_LINES + 1)?;
+        (index < self.entries.len()).then_some(index)
+    }
+}
+
+impl BufferState {
+    pub fn info(&self) -> BufferInfo {
+        BufferInfo {
+            id: self.id,
+            name: self.name(),
+            size: self.buffer.char_len(),
+            modified: self.is_modified(),
+            encoding: self.original_encoding.clone(),
+            line_ending: self.line_ending.clone(),
+            file_path: self
+                .file_path
+                .as_ref()
+                .map(|path| path.to_string_lossy().to_string()),
+            mode: self.mode_name(),
+        }
+    }
+
+    pub fn buffer_list(&self) -> Option<&BufferList> {
+        match &self.special {
+            Some(SpecialBuffer::BufferList(list)) => Some(list),
+            _ => None,
+        }
+    }
+
+    fn load_buffer_list(&mut self, list: BufferList) {
+        let reload = self.buffer_list().is_some();
+        let previous_line = self.line_col().0;
+        self.replace_contents(list.render());
+        self.original_encoding = "UTF-8".to_string();
+        self.line_ending = "LF".to_string();
+        self.file_path = None;
+        self.query_replace_session = None;
+        self.special = Some(SpecialBuffer::BufferList(list));
+        self.move_to_list_line(if reload { previous_line } else { HEADER_LINES + 1 });
+    }
+
+    fn move_to_list_line(&mut self, line: usize) {
+        let last_line = self.buffer.as_str().matches('\n').count();
+        let target = line.clamp(HEADER_LINES + 1, last_line.max(HEADER_LINES + 1));
+        self.cursor = self
+            .line_col_to_cursor(target, NAME_COLUMN + 1)
+            .unwrap_or(0);
+    }
+
+    pub fn buffer_list_entry_at_cursor(&self) -> Option<&BufferListEntry> {
+        let list = self.buffer_list()?;
+        let index = list.entry_index_at_line(self.line_col().0)?;
+        list.entries.get(index)
+    }
+
+    /// Buffers to act on: the marked ones, or the one at point.
+    pub fn buffer_list_targets(&self) -> Vec<BufferId> {
+        let Some(list) = self.buffer_list() else {
+            return Vec::new();
+        };
+        let marked: Vec<BufferId> = list
+            .entries
+            .iter()
+            .filter(|e| e.marked)
+            .map(|e| e.info.id)
+            .collect();
+        if !marked.is_empty() {
+            return marked;
+        }
+        self.buffer_list_entry_at_cursor()
+            .map(|e| e.info.id)
+            .into_iter()
+            .collect()
+    }
+
+    /// Sets the mark on the entry at point and moves to the next line.
+    pub fn buffer_list_set_mark(&mut self, marked: bool) -> Result<(), String> {
+        let line = self.line_col().0;
+        let Some(SpecialBuffer::BufferList(list)) = self.special.as_mut() else {
+            return Err("Not a buffer list".to_string());
+        };
+        let index = list
+            .entry_index_at_line(line)
+            .ok_or_else(|| "No buffer on this line".to_string())?;
+        list.entries[index].marked = marked;
+        self.rerender_buffer_list(line + 1);
+        Ok(())
+    }
+
+    pub fn buffer_list_unmark_all(&mut self) {
+        let line = self.line_col().0;
+        if let Some(SpecialBuffer::BufferList(list)) = self.special.as_mut() {
+            for entry in &mut list.entries {
+                entry.marked = false;
+            }
+        }
+        self.rerender_buffer_list(line);
+    }
+
+    fn rerender_buffer_list(&mut self, line: usize) {
+        let Some(list) = self.buffer_list() else {
+            return;
+        };
+        self.buffer.set_text(list.render());
+        self.set_modified(false);
+        self.move_to_list_line(line);
+    }
+}
+
+impl EditorState {
+    pub fn buffer_infos(&self) -> Vec<BufferInfo> {
+        self.buffers.iter().map(BufferState::info).collect()
+    }
+
+    /// Creates or refreshes `*Buffer List*` and shows it in the selected window.
+    pub fn show_buffer_list(&mut self) {
+        let index = match self.find_special_buffer(BUFFER_LIST_NAME) {
+            Some(index) => index,
+            None => {
+                let directory = self
+                    .current()
+                    .default_directory()
+                    .or_else(|| std::env::current_dir().ok())
+                    .unwrap_or_default();
+                let mut buf = BufferState::new();
+                buf.special = Some(SpecialBuffer::BufferList(BufferList {
+                    directory,
+                    filter: BufferListFilter::default(),
+                    entries: Vec::new(),
+                }));
+                self.buffers.push(buf);
+                self.buffers.len() - 1
+            }
+        };
+        self.switch_to_index(index);
+        self.refresh_buffer_list();
+    }
+
+    /// Rebuilds the current `*Buffer List*`, keeping marks of buffers that still exist.
+    pub fn refresh_buffer_list(&mut self) {
+        let Some(old) = self.current().buffer_list().cloned() else {
+            return;
+        };
+        let own_id = self.current().id;
+        let entries = self
+            .buffers
+            .iter()
+            .filter(|b| b.id != own_id && old.filter.matches(b))
+            .map(|b| BufferListEntry {
+                info: b.info(),
+                marked: old
+                    .entries
+                    .iter()
+                    .any(|e| e.marked && e.info.id == b.id),
+            })
+            .collect();
+        let filter = old.filter.clone();
+        self.current_mut().load_buffer_list(BufferList {
+            directory: old.directory,
+            filter,
+            entries,
+        });
+    }
+
+    pub fn set_buffer_list_filter(&mut self, filter: BufferListFilter) -> Result<(), String> {
+        let Some(SpecialBuffer::BufferList(list)) = self.current_mut().special.as_mut() else {
+            return Err("Not a buffer list".to_string());
+        };
+        list.filter = filter;
+        self.refresh_buffer_list();
+        let message = format!(
+            "Filter: {}",
+            self.current().buffer_list().map_or_else(String::new, |l| l.filter.describe())
+        );
+        self.current_mut().set_status_message(Some(message));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferListFilter;
+    use crate::editor::state::{BufferState, EditorState};
+    use std::path::PathBuf;
+
+    #[test]
+    fn lists_filters_and_marks_other_buffers() {
+        let mut editor = EditorState::new();
+        for (path, modified) in [("/p/a.md", true), ("/p/b.md", false), ("/q/c.md", true)] {
+            let mut buf = BufferState::new();
+            buf.set_file_path(PathBuf::from(path));
+            buf.set_modified(modified);
+            editor.buffers.push(buf);
+        }
+
+        editor.show_buffer_list();
+        assert_eq!(editor.current().name(), "*Buffer List*");
+        let names = |editor: &EditorState| -> Vec<String> {
+            let list = editor.current().buffer_list().unwrap();
+            list.entries.iter().map(|e| e.info.name.clone()).collect()
+        };
+        assert_eq!(names(&editor), ["*scratch*", "a.md", "b.md", "c.md"]);
+
+        editor
+            .set_buffer_list_filter(BufferListFilter {
+                modified_only: true,
+                directory: Some(PathBuf::from("/p")),
+            })
+            .unwrap();
+        assert_eq!(names(&editor), ["a.md"]);
+
+        editor.current_mut().buffer_list_set_mark(true).unwrap();
+        assert!(editor.current().buffer.text().contains("\n>*  a.md"));
+        let a_id = editor.buffers[1].id;
+        assert_eq!(editor.current().buffer_list_targets(), vec![a_id]);
+    }
+}
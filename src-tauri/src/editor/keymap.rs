@@ -0,0 +1,162 @@
+use serde::Serialize;
+
+/// Name of the keymap active in every buffer; the others are major-mode names.
+pub const GLOBAL_KEYMAP: &str = "global";
+
+/// Key sequences the frontend binds to registered editor commands, written
+/// the Emacs way (`C-x 2`, `M-<`). Used to show bindings next to commands.
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("C-a", "move_to_line_start"),
+    ("C-e", "move_to_line_end"),
+    ("C-f", "move_forward"),
+    ("C-b", "move_backward"),
+    ("C-n", "move_next_line"),
+    ("C-p", "move_previous_line"),
+    ("M-f", "move_forward_word"),
+    ("M-b", "move_backward_word"),
+    ("M-<", "move_to_buffer_start"),
+    ("M->", "move_to_buffer_end"),
+    ("C-d", "delete_char"),
+    ("DEL", "delete_backward_char"),
+    ("C-h", "delete_backward_char"),
+    ("C-k", "kill_line"),
+    ("C-w", "kill_region"),
+    ("M-w", "copy_region"),
+    ("C-y", "yank"),
+    ("C-t", "transpose_chars"),
+    ("M-t", "transpose_words"),
+    ("M-u", "upcase_word"),
+    ("M-l", "downcase_word"),
+    ("M-c", "capitalize_word"),
+    ("M-d", "kill_word"),
+    ("M-DEL", "backward_kill_word"),
+    ("M-\\", "delete_horizontal_space"),
+    ("M-SPC", "just_one_space"),
+    ("C-o", "open_line"),
+    ("C-/", "undo"),
+    ("C-_", "undo"),
+    ("C-?", "redo"),
+    ("C-s", "isearch_forward"),
+    ("C-r", "isearch_backward"),
+    ("C-g", "keyboard_quit"),
+    ("C-x 2", "split_window_below"),
+    ("C-x 3", "split_window_right"),
+    ("C-x o", "other_window"),
+    ("C-x 0", "delete_window"),
+    ("C-x 1", "delete_other_windows"),
+    ("C-x 4 c", "clone_indirect_buffer_other_window"),
+    ("C-x n n", "narrow_to_region"),
+    ("C-x n p", "narrow_to_page"),
+    ("C-x n w", "widen"),
+    ("C-x C-t", "transpose_lines"),
+    ("C-x C-o", "delete_blank_lines"),
+    ("M-;", "comment_line"),
+    ("TAB", "indent_line"),
+    ("C-x f", "set_fill_column"),
+    ("C-x C-k C-i", "kmacro_insert_counter"),
+];
+
+const MARKDOWN_BINDINGS: &[(&str, &str)] = &[
+    ("C-c C--", "markdown_promote_heading"),
+    ("C-c C-=", "markdown_demote_heading"),
+];
+
+const NAMED_KEYS: &[&str] = &[
+    "TAB", "RET", "SPC", "DEL", "ESC", "Insert", "Delete", "Home", "End", "PageUp", "PageDown",
+    "Up", "Down", "Left", "Right",
+];
+
+/// One binding in a keymap; `command: None` unbinds the key sequence.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyBinding {
+    pub keymap: String,
+    pub keys: String,
+    pub command: Option<String>,
+}
+
+/// Checks a key sequence such as `C-x  C-g` and returns it in canonical
+/// form (`C-x C-g`). Each key is any of the `C-`, `M-` and `S-` modifiers
+/// followed by one character or a named key like `TAB`, `SPC` or `F5`.
+pub fn parse_key_sequence(keys: &str) -> Result<String, String> {
+    let mut parsed = Vec::new();
+    for key in keys.split_whitespace() {
+        let mut base = key;
+        while base.len() > 2 && ["C-", "M-", "S-"].iter().any(|m| base.starts_with(m)) {
+            base = &base[2..];
+        }
+        let function_key = base
+            .strip_prefix('F')
+            .and_then(|n| n.parse::<u8>().ok())
+            .is_some_and(|n| (1..=12).contains(&n));
+        if base.chars().count() != 1 && !NAMED_KEYS.contains(&base) && !function_key {
+            return Err(format!("invalid key `{}` in `{}`", key, keys));
+        }
+        parsed.push(key);
+    }
+    if parsed.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(parsed.join(" "))
+}
+
+/// True when `prefix` is a proper prefix of `keys`, key by key.
+fn is_prefix(prefix: &str, keys: &str) -> bool {
+    keys.len() > prefix.len() && keys.starts_with(prefix) && keys[prefix.len()..].starts_with(' ')
+}
+
+/// The global keymap and per-major-mode keymaps; a mode's bindings take
+/// precedence over global ones in buffers of that mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<KeyBinding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let keymaps = [(GLOBAL_KEYMAP, DEFAULT_BINDINGS), ("markdown", MARKDOWN_BINDINGS)];
+        let bindings = keymaps
+            .iter()
+            .flat_map(|(keymap, bindings)| {
+                bindings.iter().map(|(keys, command)| KeyBinding {
+                    keymap: keymap.to_string(),
+                    keys: keys.to_string(),
+                    command: Some(command.to_string()),
+                })
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Key sequences bound to `command` in any keymap, in binding order.
+    pub fn keys_for(&self, command: &str) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.command.as_deref() == Some(command))
+            .map(|binding| binding.keys.clone())
+            .collect()
+    }
+
+    /// Applies configured bindings in order, replacing or removing the
+    /// existing binding of the same keys in the same keymap.
+    pub fn apply(&mut self, overrides: &[KeyBinding]) {
+        for binding in overrides {
+            self.bindings
+                .retain(|existing| existing.keymap != binding.keymap || existing.keys != binding.keys);
+            if binding.command.is_some() {
+                self.bindings.push(binding.clone());
+            }
+        }
+    }
+
+    /// Another binding in the same keymap that `binding` would shadow or be
+    /// shadowed by because one key sequence is a prefix of the other.
+    pub fn prefix_conflict(&self, binding: &KeyBinding) -> Option<&KeyBinding> {
+        self.bindings.iter().find(|other| {
+            other.keymap == binding.keymap
+                && (is_prefix(&other.keys, &binding.keys) || is_prefix(&binding.keys, &other.keys))
+        })
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::editor::state::EditorState;
+
+pub const KMACRO_FILE_NAME: &str = "kmacros.json";
+
+/// One `editor_command` call captured while a macro was being defined.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacroStep {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>,
+}
+
+pub type KeyboardMacro = Vec<MacroStep>;
+
+/// Keyboard macro state, like Emacs' `defining-kbd-macro`, `last-kbd-macro`
+/// and `kmacro-counter`.
+#[derive(Debug, Default)]
+pub struct MacroRecorder {
+    /// Steps recorded so far; `Some` while a definition is in progress.
+    pub defining: Option<KeyboardMacro>,
+    pub last: Option<KeyboardMacro>,
+    /// Macros named with `name_last_kbd_macro`, persisted in `kmacros.json`.
+    pub named: BTreeMap<String, KeyboardMacro>,
+    pub counter: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KmacroFile {
+    macros: BTreeMap<String, KeyboardMacro>,
+}
+
+/// Reads the named macros. A missing or unreadable file yields none.
+pub fn load(path: &Path) -> BTreeMap<String, KeyboardMacro> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return BTreeMap::new();
+    };
+    serde_json::from_str::<KmacroFile>(&content)
+        .map(|file| file.macros)
+        .unwrap_or_default()
+}
+
+pub fn save(path: &Path, macros: &BTreeMap<String, KeyboardMacro>) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+    }
+    let file = KmacroFile {
+        macros: macros.clone(),
+    };
+    let json = serde_json::to_string_pretty(&file)
+        .map_err(|err| format!("failed to serialize keyboard macros: {err}"))?;
+    std::fs::write(path, json).map_err(|err| format!("failed to write {}: {err}", path.display()))
+}
+
+impl EditorState {
+    pub fn defining_kbd_macro(&self) -> bool {
+        self.kmacro.defining.is_some()
+    }
+
+    pub fn start_kbd_macro(&mut self) -> Result<(), String> {
+        if self.defining_kbd_macro() {
+            return Err("Already defining keyboard macro".to_string());
+        }
+        self.kmacro.defining = Some(Vec::new());
+        self.current_mut()
+            .set_status_message(Some("Defining keyboard macro...".to_string()));
+        Ok(())
+    }
+
+    pub fn end_kbd_macro(&mut self) -> Result<(), String> {
+        let steps = self
+            .kmacro
+            .defining
+            .take()
+            .ok_or_else(|| "Not defining keyboard macro".to_string())?;
+        let message = if steps.is_empty() {
+            "Ignore empty macro".to_string()
+        } else {
+            self.kmacro.last = Some(steps);
+            "Keyboard macro defined".to_string()
+        };
+        self.current_mut().set_status_message(Some(message));
+        Ok(())
+    }
+
+    /// Abandons a definition in progress, as `C-g` does in Emacs.
+    pub fn cancel_kbd_macro(&mut self) {
+        if self.kmacro.defining.take().is_some() {
+            self.current_mut()
+                .set_status_message(Some("Keyboard macro definition cancelled".to_string()));
+        }
+    }
+
+    pub fn record_macro_step(&mut self, command: &str, payload: Option<Value>) {
+        if let Some(steps) = self.kmacro.defining.as_mut() {
+            steps.push(MacroStep {
+                command: command.to_string(),
+                payload,
+            });
+        }
+    }
+
+    /// The last macro, or the macro called `name`.
+    pub fn kbd_macro(&self, name: Option<&str>) -> Result<KeyboardMacro, String> {
+        match name {
+            Some(name) => self
+                .kmacro
+                .named
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("No keyboard macro named {}", name)),
+            None => self
+                .kmacro
+                .last
+                .clone()
+                .ok_or_else(|| "No kbd macro has been defined".to_string()),
+        }
+    }
+
+    pub fn name_last_kbd_macro(&mut self, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Macro name must not be empty".to_string());
+        }
+        let steps = self.kbd_macro(None)?;
+        self.kmacro.named.insert(name.to_string(), steps);
+        self.current_mut()
+            .set_status_message(Some(format!("Keyboard macro saved as {}", name)));
+        Ok(())
+    }
+
+    /// Inserts the macro counter at point and increments it by `step`.
+    pub fn kmacro_insert_counter(&mut self, step: i64) -> Result<(), String> {
+        let text = self.kmacro.counter.to_string();
+        self.current_mut().insert_text(&text)?;
+        self.kmacro.counter += step;
+        Ok(())
+    }
+
+    pub fn kmacro_set_counter(&mut self, value: i64) {
+        self.kmacro.counter = value;
+        self.current_mut()
+            .set_status_message(Some(format!("Macro counter set to {}", value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, save, MacroStep};
+    use crate::editor::state::EditorState;
+    use serde_json::json;
+
+    #[test]
+    fn records_names_and_persists_macros() {
+        let mut editor = EditorState::new();
+        editor.start_kbd_macro().unwrap();
+        assert!(editor.start_kbd_macro().is_err());
+        editor.record_macro_step("insert_text", Some(json!({ "text": "a" })));
+        editor.record_macro_step("move_forward", None);
+        editor.end_kbd_macro().unwrap();
+        editor.name_last_kbd_macro("demo").unwrap();
+
+        let steps = editor.kbd_macro(Some("demo")).unwrap();
+        assert_eq!(steps[1], MacroStep { command: "move_forward".to_string(), payload: None });
+
+        editor.kmacro.counter = 3;
+        editor.kmacro_insert_counter(1).unwrap();
+        assert_eq!(editor.current().buffer.text(), "3");
+        assert_eq!(editor.kmacro.counter, 4);
+
+        let path = std::env::temp_dir()
+            .join(format!("femto-kmacro-{}", std::process::id()))
+            .join("kmacros.json");
+        save(&path, &editor.kmacro.named).unwrap();
+        assert_eq!(load(&path), editor.kmacro.named);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,251 @@
+use serde::Serialize;
+
+use crate::editor::state::BufferState;
+
+pub const DEFAULT_FILL_COLUMN: usize = 70;
+
+/// Per-buffer modes that can be switched on and off independently of the major mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinorMode {
+    ReadOnly,
+    Overwrite,
+    AutoFill,
+    DeleteTrailingWhitespaceOnSave,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinorModeView {
+    pub name: &'static str,
+    /// Short indicator shown in the mode line.
+    pub lighter: &'static str,
+}
+
+impl MinorMode {
+    pub const ALL: [MinorMode; 4] = [
+        MinorMode::ReadOnly,
+        MinorMode::Overwrite,
+        MinorMode::AutoFill,
+        MinorMode::DeleteTrailingWhitespaceOnSave,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MinorMode::ReadOnly => "read-only-mode",
+            MinorMode::Overwrite => "overwrite-mode",
+            MinorMode::AutoFill => "auto-fill-mode",
+            MinorMode::DeleteTrailingWhitespaceOnSave => "delete-trailing-whitespace-on-save",
+        }
+    }
+
+    pub fn lighter(self) -> &'static str {
+        match self {
+            MinorMode::ReadOnly => "RO",
+            MinorMode::Overwrite => "Ovwrt",
+            MinorMode::AutoFill => "Fill",
+            MinorMode::DeleteTrailingWhitespaceOnSave => "WS",
+        }
+    }
+
+    /// Accepts `read-only-mode`, `read-only` or `read_only_mode`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().replace('_', "-");
+        Self::ALL.into_iter().find(|mode| {
+            mode.name() == name || mode.name().strip_suffix("-mode") == Some(name.as_str())
+        })
+    }
+}
+
+impl BufferState {
+    pub fn has_minor_mode(&self, mode: MinorMode) -> bool {
+        self.minor_modes.contains(&mode)
+    }
+
+    pub fn set_minor_mode(&mut self, mode: MinorMode, enabled: bool) {
+        if enabled != self.has_minor_mode(mode) {
+            if enabled {
+                self.minor_modes.push(mode);
+            } else {
+                self.minor_modes.retain(|m| *m != mode);
+            }
+        }
+        let state = if enabled { "enabled" } else { "disabled" };
+        let message = match mode {
+            MinorMode::AutoFill if enabled => {
+                format!("Auto-Fill mode enabled (fill-column {})", self.fill_column)
+            }
+            _ => format!("{} {} in current buffer", mode.name(), state),
+        };
+        self.set_status_message(Some(message));
+    }
+
+    /// Enables (`Some(true)`), disables (`Some(false)`) or toggles (`None`) a minor mode by name.
+    pub fn minor_mode_command(&mut self, name: &str, enable: Option<bool>) -> Result<(), String> {
+        let mode = MinorMode::from_name(name).ok_or_else(|| format!("Unknown minor mode: {}", name))?;
+        let enabled = enable.unwrap_or(!self.has_minor_mode(mode));
+        self.set_minor_mode(mode, enabled);
+        Ok(())
+    }
+
+    pub fn minor_mode_views(&self) -> Vec<MinorModeView> {
+        MinorMode::ALL
+            .into_iter()
+            .filter(|mode| self.has_minor_mode(*mode))
+            .map(|mode| MinorModeView {
+                name: mode.name(),
+                lighter: mode.lighter(),
+            })
+            .collect()
+    }
+
+    pub fn set_fill_column(&mut self, column: usize) -> Result<(), String> {
+        if column == 0 {
+            return Err("fill-column must be positive".to_string());
+        }
+        self.fill_column = column;
+        self.set_status_message(Some(format!("Fill column set to {}", column)));
+        Ok(())
+    }
+
+    /// Errors when the buffer must not be edited: read-only mode is on, or
+    /// it is a dired, grep or buffer-list listing (wdired excepted).
+    pub fn check_writable(&self) -> Result<(), String> {
+        let listing = self.special.as_ref().is_some_and(|special| special.kind() != "wdired");
+        if listing || self.has_minor_mode(MinorMode::ReadOnly) {
+            return Err(format!("Buffer is read-only: {}", self.name()));
+        }
+        Ok(())
+    }
+
+    /// How many characters after point typing `text` replaces in overwrite mode.
+    /// Like Emacs, newlines are never overwritten and inserted newlines replace nothing.
+    pub(crate) fn overwrite_len(&self, text: &str) -> usize {
+        if !self.has_minor_mode(MinorMode::Overwrite) || text.contains('\n') {
+            return 0;
+        }
+        let max = self.point_max();
+        self.buffer
+            .as_str()
+            .chars()
+            .skip(self.cursor)
+            .take(max - self.cursor)
+            .take_while(|c| *c != '\n')
+            .take(text.chars().count())
+            .count()
+    }
+
+    /// Breaks the current line at whitespace when it has grown past `fill_column`.
+    /// Runs after typing a space or newline, within the same undo step.
+    pub(crate) fn auto_fill(&mut self, text: &str) {
+        if !self.has_minor_mode(MinorMode::AutoFill) || !text.ends_with([' ', '\n']) {
+            return;
+        }
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        // The line that received the text ends just before what was typed.
+        let typed_start = self.cursor - text.chars().count();
+        let mut line_start = typed_start;
+        while line_start > self.point_min() && chars[line_start - 1] != '\n' {
+            line_start -= 1;
+        }
+        let line = &chars[line_start..typed_start];
+        if line.len() <= self.fill_column {
+            return;
+        }
+        let indent = line.iter().take_while(|c| **c == ' ' || **c == '\t').count();
+        let Some(space) = (indent + 1..=self.fill_column.min(line.len() - 1))
+            .rev()
+            .find(|i| line[*i] == ' ')
+        else {
+            return;
+        };
+        let mut run_start = space;
+        while run_start > indent && line[run_start - 1] == ' ' {
+            run_start -= 1;
+        }
+        let break_text = format!("\n{}", line[..indent].iter().collect::<String>());
+        let (from, to) = (line_start + run_start, line_start + space + 1);
+        self.buffer.remove_range(from, to);
+        self.buffer.insert_str(from, &break_text);
+        self.cursor = self.cursor + break_text.chars().count() - (to - from);
+    }
+
+    /// The text a save writes: the buffer after the before-save hooks, such
+    /// as deleting trailing whitespace. The buffer itself is left alone so a
+    /// failed write changes nothing; `apply_saved_text` catches it up after.
+    pub fn text_to_save(&self) -> String {
+        let mut text = self.buffer.text();
+        if self.has_minor_mode(MinorMode::DeleteTrailingWhitespaceOnSave) {
+            text = text
+                .split('\n')
+                .map(|line| line.trim_end_matches([' ', '\t']))
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        self.with_final_newline(text)
+    }
+
+    /// Replaces `original`, the text `text_to_save` started from, with the
+    /// `saved` text once it is written, as one undo step. Does nothing when
+    /// the buffer was edited while the write ran.
+    pub fn apply_saved_text(&mut self, original: &str, saved: String) {
+        if saved == original || self.buffer.text() != original {
+            return;
+        }
+        let (line, col) = self.line_col();
+        self.record_undo_snapshot();
+        self.buffer.set_text(saved);
+        self.cursor = self
+            .line_col_to_cursor(line, col)
+            .unwrap_or_else(|| self.buffer.char_len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MinorMode;
+    use crate::editor::state::BufferState;
+
+    #[test]
+    fn read_only_overwrite_and_auto_fill() {
+        let mut buf = BufferState::new();
+        buf.insert_text("abcd\nef").unwrap();
+
+        buf.minor_mode_command("read-only", None).unwrap();
+        assert!(buf.insert_text("x").unwrap_err().contains("read-only"));
+        buf.minor_mode_command("read-only-mode", Some(false)).unwrap();
+
+        buf.set_minor_mode(MinorMode::Overwrite, true);
+        buf.cursor = 2;
+        buf.insert_text("XYZ").unwrap();
+        assert_eq!(buf.buffer.text(), "abXYZ\nef");
+
+        let mut buf = BufferState::new();
+        buf.fill_column = 10;
+        buf.set_minor_mode(MinorMode::AutoFill, true);
+        buf.insert_text("  one two three").unwrap();
+        buf.insert_text(" ").unwrap();
+        assert_eq!(buf.buffer.text(), "  one two\n  three ");
+        assert_eq!(buf.cursor, buf.buffer.char_len());
+    }
+
+    #[test]
+    fn save_hooks_touch_the_buffer_only_after_the_write() {
+        let mut buf = BufferState::new();
+        buf.insert_text("a  \nb\t").unwrap();
+        buf.set_minor_mode(MinorMode::DeleteTrailingWhitespaceOnSave, true);
+        buf.file_settings.insert_final_newline = Some(true);
+        let original = buf.buffer.text();
+        let saved = buf.text_to_save();
+        assert_eq!((saved.as_str(), original.as_str()), ("a\nb\n", "a  \nb\t"));
+
+        buf.apply_saved_text(&original, saved);
+        assert_eq!((buf.buffer.text().as_str(), buf.cursor), ("a\nb\n", 3));
+        buf.undo();
+        assert_eq!(buf.buffer.text(), "a  \nb\t");
+
+        let saved = buf.text_to_save();
+        buf.insert_text("c").unwrap();
+        buf.apply_saved_text(&original, saved);
+        assert_eq!(buf.buffer.text(), "a  \nb\tc");
+    }
+}
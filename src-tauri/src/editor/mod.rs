@@ -1,6 +1,24 @@
 pub mod buffer;
+pub mod command;
 pub mod cursor;
+pub mod dired;
 pub mod edit;
+pub mod extended_command;
+pub mod file_settings;
+pub mod grammar;
+pub mod grep;
+pub mod highlight;
+pub mod ibuffer;
+pub mod keymap;
+pub mod kmacro;
+pub mod minor_mode;
+pub mod mode;
+pub mod narrow;
+pub mod prefix_arg;
+pub mod save_some;
 pub mod search;
+pub mod settings;
 pub mod state;
 pub mod undo;
+pub mod uniquify;
+pub mod window;
@@ -0,0 +1,563 @@
+use std::path::Path;
+
+use crate::editor::state::BufferState;
+
+#[derive(Debug)]
+pub struct CommentSyntax {
+    /// Prefix of a line comment, e.g. `//`.
+    pub line: Option<&'static str>,
+    /// Delimiters of a block comment, e.g. `<!--` and `-->`.
+    pub block: Option<(&'static str, &'static str)>,
+}
+
+#[derive(Debug)]
+pub struct IndentRules {
+    pub width: usize,
+    pub use_tabs: bool,
+    /// A line ending in one of these indents the next line one level deeper.
+    pub openers: &'static str,
+    /// A line starting with one of these is indented one level shallower.
+    pub closers: &'static str,
+}
+
+/// A major mode: how a kind of file is edited.
+#[derive(Debug)]
+pub struct MajorMode {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    /// Whole file names without a useful extension, such as `.bashrc`.
+    pub file_names: &'static [&'static str],
+    /// Interpreters named on a `#!` line; also accepted as mode names.
+    pub interpreters: &'static [&'static str],
+    /// Characters besides letters and digits that are part of a word.
+    pub word_chars: &'static str,
+    pub comment: CommentSyntax,
+    pub indent: IndentRules,
+    /// Commands only available in this mode.
+    pub commands: &'static [&'static str],
+}
+
+impl MajorMode {
+    pub fn is_word_char(&self, ch: char) -> bool {
+        ch.is_alphanumeric() || self.word_chars.contains(ch)
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        let name = name.trim().to_lowercase();
+        let name = name.strip_suffix("-mode").unwrap_or(&name);
+        name == self.id || name == self.name.to_lowercase() || self.interpreters.contains(&name)
+    }
+}
+
+const NO_COMMENTS: CommentSyntax = CommentSyntax {
+    line: None,
+    block: None,
+};
+
+const HASH_COMMENTS: CommentSyntax = CommentSyntax {
+    line: Some("#"),
+    block: None,
+};
+
+const BRACKETS: &str = "{[(";
+const CLOSING_BRACKETS: &str = "}])";
+
+pub static FUNDAMENTAL: MajorMode = MajorMode {
+    id: "fundamental",
+    name: "Fundamental",
+    extensions: &[],
+    file_names: &[],
+    interpreters: &[],
+    word_chars: "_",
+    comment: NO_COMMENTS,
+    indent: IndentRules {
+        width: 4,
+        use_tabs: false,
+        openers: "",
+        closers: "",
+    },
+    commands: &[],
+};
+
+static TEXT: MajorMode = MajorMode {
+    id: "text",
+    name: "Text",
+    extensions: &["txt", "text"],
+    file_names: &[],
+    interpreters: &[],
+    word_chars: "'",
+    comment: NO_COMMENTS,
+    indent: IndentRules {
+        width: 4,
+        use_tabs: false,
+        openers: "",
+        closers: "",
+    },
+    commands: &[],
+};
+
+static MARKDOWN: MajorMode = MajorMode {
+    id: "markdown",
+    name: "Markdown",
+    extensions: &["md", "markdown", "mdown", "mkd"],
+    file_names: &[],
+    interpreters: &[],
+    word_chars: "'",
+    comment: CommentSyntax {
+        line: None,
+        block: Some(("<!--", "-->")),
+    },
+    indent: IndentRules {
+        width: 4,
+        use_tabs: false,
+        openers: "",
+        closers: "",
+    },
+    commands: &["markdown_promote_heading", "markdown_demote_heading"],
+};
+
+static RUST: MajorMode = MajorMode {
+    id: "rust",
+    name: "Rust",
+    extensions: &["rs"],
+    file_names: &[],
+    interpreters: &[],
+    word_chars: "_",
+    comment: CommentSyntax {
+        line: Some("//"),
+        block: Some(("/*", "*/")),
+    },
+    indent: IndentRules {
+        width: 4,
+        use_tabs: false,
+        openers: BRACKETS,
+        closers: CLOSING_BRACKETS,
+    },
+    commands: &[],
+};
+
+static PYTHON: MajorMode = MajorMode {
+    id: "python",
+    name: "Python",
+    extensions: &["py", "pyw", "pyi"],
+    file_names: &[],
+    interpreters: &["python", "python3", "python2"],
+    word_chars: "_",
+    comment: HASH_COMMENTS,
+    indent: IndentRules {
+        width: 4,
+        use_tabs: false,
+        openers: ":([{",
+        closers: CLOSING_BRACKETS,
+    },
+    commands: &[],
+};
+
+static SHELL: MajorMode = MajorMode {
+    id: "shell",
+    name: "Shell",
+    extensions: &["sh", "bash", "zsh", "ksh"],
+    file_names: &[".bashrc", ".bash_profile", ".profile", ".zshrc", ".zprofile"],
+    interpreters: &["sh", "bash", "zsh", "ksh", "dash"],
+    word_chars: "_",
+    comment: HASH_COMMENTS,
+    indent: IndentRules {
+        width: 2,
+        use_tabs: false,
+        openers: "{(",
+        closers: "})",
+    },
+    commands: &[],
+};
+
+static JSON: MajorMode = MajorMode {
+    id: "json",
+    name: "JSON",
+    extensions: &["json", "jsonc"],
+    file_names: &[],
+    interpreters: &[],
+    word_chars: "_",
+    comment: NO_COMMENTS,
+    indent: IndentRules {
+        width: 2,
+        use_tabs: false,
+        openers: "{[",
+        closers: "}]",
+    },
+    commands: &[],
+};
+
+static YAML: MajorMode = MajorMode {
+    id: "yaml",
+    name: "YAML",
+    extensions: &["yaml", "yml"],
+    file_names: &[],
+    interpreters: &[],
+    word_chars: "_",
+    comment: HASH_COMMENTS,
+    indent: IndentRules {
+        width: 2,
+        use_tabs: false,
+        openers: ":",
+        closers: "",
+    },
+    commands: &[],
+};
+
+static TOML: MajorMode = MajorMode {
+    id: "toml",
+    name: "TOML",
+    extensions: &["toml"],
+    file_names: &["Cargo.lock"],
+    interpreters: &[],
+    word_chars: "_",
+    comment: HASH_COMMENTS,
+    indent: IndentRules {
+        width: 2,
+        use_tabs: false,
+        openers: "[{",
+        closers: "]}",
+    },
+    commands: &[],
+};
+
+static MODES: &[&MajorMode] = &[
+    &FUNDAMENTAL,
+    &TEXT,
+    &MARKDOWN,
+    &RUST,
+    &PYTHON,
+    &SHELL,
+    &JSON,
+    &YAML,
+    &TOML,
+];
+
+pub fn all_modes() -> &'static [&'static MajorMode] {
+    MODES
+}
+
+/// Looks a mode up by id, display name, `<name>-mode` or interpreter name.
+pub fn find_mode(name: &str) -> Option<&'static MajorMode> {
+    MODES.iter().copied().find(|mode| mode.matches_name(name))
+}
+
+/// The value of `mode:` in a `-*- ... -*-` line, or the whole cookie when it is
+/// a bare mode name (`-*- rust -*-`).
+fn mode_cookie(line: &str) -> Option<String> {
+    let start = line.find("-*-")? + 3;
+    let end = start + line[start..].find("-*-")?;
+    let inner = line[start..end].trim();
+    if !inner.contains(':') {
+        return (!inner.is_empty()).then(|| inner.to_string());
+    }
+    inner.split(';').find_map(|pair| {
+        let (key, value) = pair.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case("mode")
+            .then(|| value.trim().to_string())
+    })
+}
+
+/// Interpreter named by a `#!` line, looking through `/usr/bin/env`.
+fn shebang_interpreter(line: &str) -> Option<String> {
+    let rest = line.strip_prefix("#!")?;
+    let mut words = rest.split_whitespace();
+    let program = Path::new(words.next()?).file_name()?.to_string_lossy().to_string();
+    if program != "env" {
+        return Some(program);
+    }
+    words
+        .find(|word| !word.starts_with('-') && !word.contains('='))
+        .map(str::to_string)
+}
+
+/// Picks a mode the way Emacs' `set-auto-mode` does: a `-*- mode: -*-` cookie
+/// on the first line (second after a `#!` line), then the `#!` interpreter,
+/// then the file name.
+pub fn detect_mode(path: Option<&Path>, text: &str) -> &'static MajorMode {
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or("");
+    let cookie = mode_cookie(first).or_else(|| {
+        first
+            .starts_with("#!")
+            .then(|| lines.next().and_then(mode_cookie))
+            .flatten()
+    });
+    if let Some(mode) = cookie.as_deref().and_then(find_mode) {
+        return mode;
+    }
+
+    if let Some(interpreter) = shebang_interpreter(first) {
+        if let Some(mode) = MODES
+            .iter()
+            .find(|mode| mode.interpreters.contains(&interpreter.as_str()))
+        {
+            return mode;
+        }
+    }
+
+    let Some(path) = path else {
+        return &FUNDAMENTAL;
+    };
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    MODES
+        .iter()
+        .copied()
+        .find(|mode| {
+            mode.file_names.contains(&file_name.as_str())
+                || mode.extensions.contains(&extension.as_str())
+        })
+        .unwrap_or(&FUNDAMENTAL)
+}
+
+impl BufferState {
+    pub fn is_word_char(&self, ch: char) -> bool {
+        self.major_mode.is_word_char(ch)
+    }
+
+    /// Chooses the major mode from the visited file name and the buffer text.
+    pub fn set_auto_mode(&mut self) {
+        let head: String = self.buffer.as_str().lines().take(2).collect::<Vec<_>>().join("\n");
+        self.major_mode = detect_mode(self.file_path.as_deref(), &head);
+    }
+
+    pub fn set_major_mode(&mut self, name: &str) -> Result<(), String> {
+        let mode = find_mode(name).ok_or_else(|| format!("Unknown mode: {}", name))?;
+        self.major_mode = mode;
+        self.set_status_message(Some(format!("{} mode", mode.name)));
+        Ok(())
+    }
+
+    /// Runs a command belonging to the current major mode.
+    pub fn run_mode_command(&mut self, command: &str) -> Result<(), String> {
+        if !self.major_mode.commands.contains(&command) {
+            return match MODES.iter().find(|mode| mode.commands.contains(&command)) {
+                Some(owner) => Err(format!(
+                    "{} is only available in {} mode",
+                    command, owner.name
+                )),
+                None => Err(format!("unknown command: {command}")),
+            };
+        }
+        match command {
+            "markdown_promote_heading" => self.shift_markdown_heading(-1),
+            "markdown_demote_heading" => self.shift_markdown_heading(1),
+            _ => Err(format!("unknown command: {command}")),
+        }
+    }
+
+    /// Start and end (before the newline) of the line containing `pos`.
+    fn line_bounds(&self, chars: &[char], pos: usize) -> (usize, usize) {
+        let (min, max) = (self.point_min(), self.point_max());
+        let mut start = pos.clamp(min, max);
+        while start > min && chars[start - 1] != '\n' {
+            start -= 1;
+        }
+        let mut end = pos.clamp(min, max);
+        while end < max && chars[end] != '\n' {
+            end += 1;
+        }
+        (start, end)
+    }
+
+    fn replace_span(&mut self, start: usize, end: usize, replacement: &str) -> Result<(), String> {
+        self.check_writable()?;
+        self.record_undo_snapshot();
+        self.buffer.remove_range(start, end);
+        self.buffer.insert_str(start, replacement);
+        self.set_modified(true);
+        Ok(())
+    }
+
+    /// Comments out the lines of the region (or the current line), or uncomments
+    /// them when they are all comments already, like Emacs' `comment-line`.
+    pub fn comment_line(&mut self, region: Option<(usize, usize)>) -> Result<(), String> {
+        let mode = self.major_mode;
+        let (open, close) = match (mode.comment.line, mode.comment.block) {
+            (Some(prefix), _) => (prefix, None),
+            (None, Some((open, close))) => (open, Some(close)),
+            (None, None) => {
+                return Err(format!("No comment syntax is defined for {} mode", mode.name))
+            }
+        };
+
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        let (from, to) = match region {
+            Some((a, b)) => (a.min(b), a.max(b).saturating_sub(usize::from(a != b))),
+            None => (self.cursor, self.cursor),
+        };
+        let (start, _) = self.line_bounds(&chars, from);
+        let (_, end) = self.line_bounds(&chars, to.max(from));
+        let original: String = chars[start..end].iter().collect();
+        let lines: Vec<&str> = original.split('\n').collect();
+
+        let is_commented = |line: &str| {
+            let trimmed = line.trim();
+            trimmed.starts_with(open) && close.is_none_or(|close| trimmed.ends_with(close))
+        };
+        let uncomment = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .all(|line| is_commented(line));
+        let indent = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.chars().take_while(|c| c.is_whitespace()).count())
+            .min()
+            .unwrap_or(0);
+
+        let changed: Vec<String> = lines
+            .iter()
+            .map(|line| {
+                if line.trim().is_empty() {
+                    return line.to_string();
+                }
+                if uncomment {
+                    let leading = &line[..line.len() - line.trim_start().len()];
+                    let mut body = line.trim()[open.len()..].to_string();
+                    if let Some(close) = close {
+                        body.truncate(body.len() - close.len());
+                        body = body.trim_end().to_string();
+                    }
+                    let body = body.strip_prefix(' ').unwrap_or(&body);
+                    format!("{}{}", leading, body)
+                } else {
+                    let split = line
+                        .char_indices()
+                        .nth(indent)
+                        .map_or(line.len(), |(i, _)| i);
+                    let suffix = close.map(|close| format!(" {}", close)).unwrap_or_default();
+                    format!("{}{} {}{}", &line[..split], open, &line[split..], suffix)
+                }
+            })
+            .collect();
+        let replacement = changed.join("\n");
+        let new_end = start + replacement.chars().count();
+        self.replace_span(start, end, &replacement)?;
+
+        // Without a region, move to the next line so repeated calls walk down the buffer.
+        self.cursor = if region.is_none() && new_end < self.point_max() {
+            new_end + 1
+        } else {
+            new_end
+        };
+        Ok(())
+    }
+
+    /// Indents the current line according to the mode's rules, relative to the
+    /// previous non-blank line.
+    pub fn indent_line(&mut self) -> Result<(), String> {
+        let rules = &self.major_mode.indent;
+        let (width, tab_width) = (self.indent_width(), self.tab_width());
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        let (start, end) = self.line_bounds(&chars, self.cursor);
+        let current_indent = chars[start..end]
+            .iter()
+            .take_while(|c| **c == ' ' || **c == '\t')
+            .count();
+
+        let column_of = |line: &[char]| -> usize {
+            line.iter()
+                .take_while(|c| **c == ' ' || **c == '\t')
+                .map(|c| if *c == '\t' { tab_width } else { 1 })
+                .sum()
+        };
+
+        let mut target = 0;
+        let mut prev_end = start;
+        while prev_end > self.point_min() {
+            let (prev_start, line_end) = self.line_bounds(&chars, prev_end - 1);
+            let line = &chars[prev_start..line_end];
+            if line.iter().any(|c| !c.is_whitespace()) {
+                target = column_of(line);
+                let last = line.iter().rev().find(|c| !c.is_whitespace());
+                if last.is_some_and(|c| rules.openers.contains(*c)) {
+                    target += width;
+                }
+                break;
+            }
+            prev_end = prev_start;
+        }
+        let first = chars[start + current_indent..end].first();
+        if first.is_some_and(|c| rules.closers.contains(*c)) {
+            target = target.saturating_sub(width);
+        }
+
+        let indentation = if self.indent_with_tabs() {
+            format!("{}{}", "\t".repeat(target / tab_width), " ".repeat(target % tab_width))
+        } else {
+            " ".repeat(target)
+        };
+        let offset_in_text = self.cursor.saturating_sub(start + current_indent);
+        if chars[start..start + current_indent].iter().collect::<String>() != indentation {
+            self.replace_span(start, start + current_indent, &indentation)?;
+        }
+        self.cursor = start + indentation.chars().count() + offset_in_text;
+        Ok(())
+    }
+
+    fn shift_markdown_heading(&mut self, delta: isize) -> Result<(), String> {
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        let (start, end) = self.line_bounds(&chars, self.cursor);
+        let line: String = chars[start..end].iter().collect();
+        let level = line.chars().take_while(|c| *c == '#').count();
+        if level == 0 || !line[level..].starts_with(' ') {
+            return Err("Not at a heading".to_string());
+        }
+        let new_level = level as isize + delta;
+        if !(1..=6).contains(&new_level) {
+            return Err(format!("Cannot {} heading further", if delta < 0 { "promote" } else { "demote" }));
+        }
+        let new_level = new_level as usize;
+        self.replace_span(start, start + level, &"#".repeat(new_level))?;
+        self.cursor = (self.cursor + new_level).saturating_sub(level).max(start);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detect_mode;
+    use crate::editor::state::BufferState;
+    use std::path::Path;
+
+    #[test]
+    fn detects_modes_by_cookie_shebang_and_extension() {
+        let detect = |path: Option<&str>, text: &str| detect_mode(path.map(Path::new), text).id;
+        assert_eq!(detect(Some("/x/main.rs"), ""), "rust");
+        assert_eq!(detect(Some("/x/README.MD"), ""), "markdown");
+        assert_eq!(detect(Some("/x/.bashrc"), ""), "shell");
+        assert_eq!(detect(Some("/x/run"), "#!/usr/bin/env python3\n"), "python");
+        assert_eq!(detect(Some("/x/notes.txt"), "# -*- mode: yaml; fill-column: 70 -*-"), "yaml");
+        assert_eq!(detect(None, "#!/bin/sh\n# -*- toml -*-\n"), "toml");
+        assert_eq!(detect(Some("/x/file.unknown"), "plain"), "fundamental");
+    }
+
+    #[test]
+    fn comments_and_indents_using_the_mode() {
+        let mut buf = BufferState::new();
+        buf.set_major_mode("rust").unwrap();
+        buf.insert_text("fn main() {\nlet x = 1;\n}").unwrap();
+        buf.cursor = 13;
+        buf.indent_line().unwrap();
+        assert_eq!(buf.buffer.text(), "fn main() {\n    let x = 1;\n}");
+        assert!(!buf.is_word_char('-'));
+
+        buf.cursor = 14;
+        buf.comment_line(None).unwrap();
+        assert_eq!(buf.buffer.text(), "fn main() {\n    // let x = 1;\n}");
+        buf.cursor = 14;
+        buf.comment_line(None).unwrap();
+        assert_eq!(buf.buffer.text(), "fn main() {\n    let x = 1;\n}");
+        assert!(buf.run_mode_command("markdown_promote_heading").is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use crate::editor::buffer::TextEdit;
+use crate::editor::state::BufferState;
+
+/// Character that separates pages, as in Emacs' `page-delimiter`.
+const PAGE_DELIMITER: char = '\u{c}';
+
+/// Accessible region of a narrowed buffer.
+///
+/// The end is stored as the number of characters hidden after the region, so
+/// edits the buffer makes inside its own region keep both bounds right. Edits made
+/// through an indirect sibling move the bounds via `follow`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Narrowing {
+    pub start: usize,
+    pub hidden_after: usize,
+}
+
+impl Narrowing {
+    /// The bounds after `edit`, given the text length `len` before it. Like Emacs'
+    /// `point-min` and `point-max` markers, text inserted at either bound ends up inside.
+    pub fn follow(self, edit: &TextEdit, len: usize) -> Self {
+        let end = len.saturating_sub(self.hidden_after).max(self.start);
+        let end = edit.adjust(end, true);
+        let len = len.saturating_add_signed(edit.delta());
+        Self {
+            start: edit.adjust(self.start, false),
+            hidden_after: len.saturating_sub(end),
+        }
+    }
+}
+
+impl BufferState {
+    /// First accessible position (Emacs `point-min`).
+    pub fn point_min(&self) -> usize {
+        self.narrowing
+            .map_or(0, |n| n.start.min(self.buffer.char_len()))
+    }
+
+    /// Last accessible position (Emacs `point-max`).
+    pub fn point_max(&self) -> usize {
+        let len = self.buffer.char_len();
+        match self.narrowing {
+            Some(n) => len.saturating_sub(n.hidden_after).max(self.point_min()),
+            None => len,
+        }
+    }
+
+    pub fn is_narrowed(&self) -> bool {
+        self.narrowing.is_some()
+    }
+
+    pub fn clamp_to_accessible(&self, pos: usize) -> usize {
+        pos.clamp(self.point_min(), self.point_max())
+    }
+
+    /// The text between `point_min` and `point_max`.
+    pub fn accessible_text(&self) -> String {
+        let (min, max) = (self.point_min(), self.point_max());
+        self.buffer
+            .as_str()
+            .chars()
+            .skip(min)
+            .take(max - min)
+            .collect()
+    }
+
+    pub fn narrow_to_region(&mut self, start: usize, end: usize) -> Result<(), String> {
+        let (start, end) = (start.min(end), start.max(end));
+        let (start, end) = (self.clamp_to_accessible(start), self.clamp_to_accessible(end));
+        if start == end {
+            return Err("The region is empty".to_string());
+        }
+        self.narrowing = Some(Narrowing {
+            start,
+            hidden_after: self.buffer.char_len() - end,
+        });
+        self.cursor = self.clamp_to_accessible(self.cursor);
+        self.set_status_message(Some("Narrowed to region (C-x n w to widen)".to_string()));
+        Ok(())
+    }
+
+    /// Narrows to the page around point; pages are separated by form feeds.
+    pub fn narrow_to_page(&mut self) -> Result<(), String> {
+        let chars: Vec<char> = self.buffer.as_str().chars().collect();
+        let (min, max) = (self.point_min(), self.point_max());
+        let cursor = self.cursor.clamp(min, max);
+
+        let delimiter = (min..cursor).rev().find(|&i| chars[i] == PAGE_DELIMITER);
+        let mut start = delimiter.map_or(min, |i| i + 1);
+        // A page starts on the line after its delimiter.
+        if delimiter.is_some() && start < max && chars[start] == '\n' {
+            start += 1;
+        }
+        let end = (cursor..max)
+            .find(|&i| chars[i] == PAGE_DELIMITER)
+            .unwrap_or(max);
+        if start >= end {
+            return Err("The page is empty".to_string());
+        }
+
+        self.narrowing = Some(Narrowing {
+            start,
+            hidden_after: chars.len() - end,
+        });
+        self.cursor = cursor.clamp(start, end);
+        self.set_status_message(Some("Narrowed to page (C-x n w to widen)".to_string()));
+        Ok(())
+    }
+
+    /// Whether replacing the text with `text` only changes the accessible region.
+    /// Undo refuses other changes, as Emacs does, so hidden text is never touched.
+    pub(crate) fn change_is_accessible(&self, text: &str) -> bool {
+        if !self.is_narrowed() {
+            return true;
+        }
+        let current = self.buffer.as_str();
+        let prefix = current
+            .chars()
+            .zip(text.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = current
+            .chars()
+            .rev()
+            .zip(text.chars().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let end = current.chars().count().saturating_sub(suffix).max(prefix);
+        drop(current);
+        prefix >= self.point_min() && end <= self.point_max()
+    }
+
+    pub fn widen(&mut self) {
+        self.narrowing = None;
+        self.set_status_message(Some("Widened".to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::state::{BufferState, EditorState};
+
+    fn buffer(text: &str) -> BufferState {
+        let mut buf = BufferState::new();
+        buf.insert_text(text).unwrap();
+        buf
+    }
+
+    #[test]
+    fn edits_inside_the_region_keep_the_bounds() {
+        let mut buf = buffer("head\nbody\ntail\n");
+        buf.narrow_to_region(5, 10).unwrap();
+        assert_eq!(buf.accessible_text(), "body\n");
+        assert_eq!(buf.cursor, 10);
+
+        buf.move_to_buffer_start();
+        assert_eq!(buf.cursor, 5);
+        buf.insert_text("new ").unwrap();
+        assert_eq!(buf.accessible_text(), "new body\n");
+        buf.move_to_buffer_end();
+        assert_eq!(buf.cursor, 14);
+
+        assert!(buf.isearch_forward("tail", false).is_err());
+        buf.widen();
+        assert_eq!(buf.accessible_text(), "head\nnew body\ntail\n");
+    }
+
+    #[test]
+    fn sibling_edits_move_the_bounds_and_outside_undo_is_refused() {
+        let mut editor = EditorState::new();
+        editor.current_mut().insert_text("head\nbody\ntail\n").unwrap();
+        editor.clone_indirect_buffer().unwrap();
+        editor.current_mut().narrow_to_region(5, 10).unwrap();
+
+        // An edit through the base before the region shifts it instead of the view.
+        editor.switch_to_buffer(editor.buffers[0].id).unwrap();
+        editor.current_mut().set_cursor(0);
+        editor.current_mut().insert_text(">> ").unwrap();
+        editor.switch_to_buffer(editor.buffers[1].id).unwrap();
+        assert_eq!(editor.current().accessible_text(), "body\n");
+        assert_eq!(editor.current().point_min(), 8);
+
+        // The shared undo entry for that edit lies outside the clone's region.
+        editor.current_mut().undo();
+        assert_eq!(editor.current().buffer.text(), ">> head\nbody\ntail\n");
+        assert_eq!(
+            editor.current().status_message.as_deref(),
+            Some("Changes to be undone are outside visible portion of buffer")
+        );
+        editor.current_mut().widen();
+        editor.current_mut().undo();
+        assert_eq!(editor.current().buffer.text(), "head\nbody\ntail\n");
+    }
+
+    #[test]
+    fn narrows_to_the_page_around_point() {
+        let mut buf = buffer("one\n\u{c}\ntwo\n\u{c}\nthree");
+        buf.cursor = 7;
+        buf.narrow_to_page().unwrap();
+        assert_eq!(buf.accessible_text(), "two\n");
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+/// A command's prefix argument in its raw form, like Emacs'
+/// `current-prefix-arg`. In JSON it is a number (`M-5`, `C-u 1 2`), a
+/// one-element list for `C-u` typed without digits (`[4]`, `[16]`), or `"-"`
+/// for `M--` and `C-u -`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PrefixArg {
+    Number(i64),
+    Universal([i64; 1]),
+    Minus(String),
+}
+
+impl PrefixArg {
+    /// The numeric value, as `prefix-numeric-value` computes it.
+    pub fn value(&self) -> i64 {
+        match self {
+            PrefixArg::Number(value) => *value,
+            PrefixArg::Universal([value]) => *value,
+            PrefixArg::Minus(_) => -1,
+        }
+    }
+
+    /// True for `C-u` without digits, which some commands treat as a flag.
+    pub fn is_universal(&self) -> bool {
+        matches!(self, PrefixArg::Universal(_))
+    }
+}
+
+/// Largest text, in bytes, a repeated insertion may build.
+const MAX_REPEAT_LEN: usize = 1 << 24;
+
+/// `text` repeated `times` times, refusing counts that would build more
+/// than `MAX_REPEAT_LEN` bytes.
+pub fn repeat_text(text: &str, times: u64) -> Result<String, String> {
+    let too_large = || format!("Repetition count {} is too large", times);
+    let times = usize::try_from(times).map_err(|_| too_large())?;
+    match text.len().checked_mul(times) {
+        Some(len) if len <= MAX_REPEAT_LEN => Ok(text.repeat(times)),
+        _ => Err(too_large()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{repeat_text, PrefixArg};
+    use crate::editor::state::BufferState;
+
+    #[test]
+    fn parses_raw_forms_and_counts_kills() {
+        let raw: Vec<PrefixArg> = serde_json::from_str(r#"[3, [16], "-"]"#).unwrap();
+        assert_eq!(raw.iter().map(PrefixArg::value).collect::<Vec<_>>(), vec![3, 16, -1]);
+        assert!(raw[1].is_universal());
+        assert_eq!(repeat_text("ab", 3).unwrap(), "ababab");
+        assert_eq!(repeat_text("ab", u64::MAX), Err(format!("Repetition count {} is too large", u64::MAX)));
+
+        let mut buf = BufferState::new();
+        buf.insert_text("one\ntwo\nthree\nfour\n").unwrap();
+        buf.cursor = 0;
+        let mut kill_ring = Vec::new();
+        buf.move_lines(1);
+        buf.move_chars(1);
+        let mark = buf.undo_stack.mark();
+        buf.kill_lines(2, &mut kill_ring).unwrap();
+        buf.kill_lines(-1, &mut kill_ring).unwrap();
+        buf.undo_stack.amalgamate(mark);
+        assert_eq!(buf.buffer.text(), "four\n");
+        assert_eq!(kill_ring, vec!["one\nt".to_string(), "wo\nthree\n".to_string()]);
+
+        buf.undo();
+        assert_eq!(buf.buffer.text(), "one\ntwo\nthree\nfour\n");
+        assert_eq!(buf.cursor, 5);
+
+        buf.delete_chars(-3, Some(&mut kill_ring)).unwrap();
+        assert_eq!(buf.buffer.text(), "onwo\nthree\nfour\n");
+        assert_eq!(kill_ring[0], "e\nt");
+    }
+
+    #[test]
+    fn amalgamates_a_prefixed_command_when_history_is_full() {
+        let mut buf = BufferState::new();
+        buf.undo_stack.set_limit(3);
+        for word in ["one\n", "two\n", "three\n", "four\n"] {
+            buf.insert_text(word).unwrap();
+        }
+        buf.cursor = 0;
+        let mut kill_ring = Vec::new();
+        let mark = buf.undo_stack.mark();
+        buf.kill_lines(1, &mut kill_ring).unwrap();
+        buf.kill_lines(1, &mut kill_ring).unwrap();
+        buf.undo_stack.amalgamate(mark);
+        assert_eq!(buf.buffer.text(), "three\nfour\n");
+
+        buf.undo();
+        assert_eq!(buf.buffer.text(), "one\ntwo\nthree\nfour\n");
+        buf.undo();
+        assert_eq!(buf.buffer.text(), "one\ntwo\nthree\n");
+    }
+}
@@ -0,0 +1,178 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use crate::editor::state::{BufferId, EditorState};
+
+/// `save-some-buffers`: asks about each modified file-visiting buffer in turn.
+#[derive(Debug, Clone)]
+pub struct SaveSomeSession {
+    pub pending: VecDeque<BufferId>,
+    pub current: Option<BufferId>,
+    pub saved_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveSomeStatus {
+    pub done: bool,
+    pub buffer_id: Option<BufferId>,
+    pub file: Option<String>,
+    pub saved_count: usize,
+    /// Modified file-visiting buffers left unsaved.
+    pub unsaved_count: usize,
+    pub message: String,
+}
+
+impl EditorState {
+    pub fn start_save_some_buffers(&mut self) -> SaveSomeStatus {
+        self.save_some = Some(SaveSomeSession {
+            pending: self.modified_file_buffer_ids().into(),
+            current: None,
+            saved_count: 0,
+        });
+        self.save_some_next_status()
+    }
+
+    /// Applies a y/n/!/q answer to the buffer being asked about and returns the
+    /// buffers the caller must now write to disk.
+    pub fn save_some_step(&mut self, action: &str) -> Result<Vec<BufferId>, String> {
+        let action = action.to_lowercase();
+        if action != "y" && action != "n" && action != "!" && action != "q" {
+            return Err("action must be one of y/n/!/q".to_string());
+        }
+        let session = self
+            .save_some
+            .as_mut()
+            .ok_or_else(|| "save-some-buffers is not active".to_string())?;
+        let current = session.current.take();
+        let to_save: Vec<BufferId> = match action.as_str() {
+            "y" => current.into_iter().collect(),
+            "!" => current.into_iter().chain(session.pending.drain(..)).collect(),
+            "q" => {
+                session.pending.clear();
+                Vec::new()
+            }
+            _ => Vec::new(),
+        };
+        Ok(to_save)
+    }
+
+    /// Counts a buffer written by the caller on behalf of the session.
+    pub fn save_some_record_saved(&mut self) {
+        if let Some(session) = self.save_some.as_mut() {
+            session.saved_count += 1;
+        }
+    }
+
+    /// Moves to the next pending buffer that is still modified, or finishes the session.
+    pub fn save_some_next_status(&mut self) -> SaveSomeStatus {
+        let Some(mut session) = self.save_some.take() else {
+            return SaveSomeStatus {
+                done: true,
+                buffer_id: None,
+                file: None,
+                saved_count: 0,
+                unsaved_count: self.modified_file_buffer_ids().len(),
+                message: "No active save-some-buffers".to_string(),
+            };
+        };
+
+        while let Some(id) = session.pending.pop_front() {
+            let Some(buf) = self.index_of(id).map(|index| &self.buffers[index]) else {
+                continue;
+            };
+            let Some(path) = buf.file_path.as_ref().filter(|_| buf.is_modified()) else {
+                continue;
+            };
+            let file = path.to_string_lossy().to_string();
+            let message = format!("Save file {}? (y/n/!/q)", file);
+            session.current = Some(id);
+            let saved_count = session.saved_count;
+            self.save_some = Some(session);
+            return SaveSomeStatus {
+                done: false,
+                buffer_id: Some(id),
+                file: Some(file),
+                saved_count,
+                unsaved_count: self.modified_file_buffer_ids().len(),
+                message,
+            };
+        }
+
+        let unsaved_count = self.modified_file_buffer_ids().len();
+        let message = match (session.saved_count, unsaved_count) {
+            (0, 0) => "(No files need saving)".to_string(),
+            (saved, 0) => format!("Saved {} files", saved),
+            (saved, unsaved) => format!("Saved {} files, {} left unsaved", saved, unsaved),
+        };
+        self.current_mut().set_status_message(Some(message.clone()));
+        SaveSomeStatus {
+            done: true,
+            buffer_id: None,
+            file: None,
+            saved_count: session.saved_count,
+            unsaved_count,
+            message,
+        }
+    }
+
+    /// Errors while modified file-visiting buffers remain, unless `force` is set.
+    pub fn check_quit(&self, force: bool) -> Result<(), String> {
+        if force {
+            return Ok(());
+        }
+        let names: Vec<String> = self
+            .modified_file_buffer_ids()
+            .into_iter()
+            .filter_map(|id| self.index_of(id))
+            .map(|index| self.buffers[index].name())
+            .collect();
+        if names.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Modified buffers exist: {}. Confirmation required.",
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::state::{BufferState, EditorState};
+    use std::path::PathBuf;
+
+    #[test]
+    fn steps_through_modified_file_buffers() {
+        let mut editor = EditorState::new();
+        editor.current_mut().set_modified(true);
+        for path in ["/a.txt", "/b.txt", "/c.txt"] {
+            let mut buf = BufferState::new();
+            buf.set_file_path(PathBuf::from(path));
+            buf.set_modified(true);
+            editor.buffers.push(buf);
+        }
+        let ids: Vec<_> = editor.buffers[1..].iter().map(|b| b.id).collect();
+
+        let status = editor.start_save_some_buffers();
+        assert_eq!(status.buffer_id, Some(ids[0]));
+        assert_eq!(editor.save_some_step("y").unwrap(), vec![ids[0]]);
+        editor.buffers[1].mark_saved();
+        editor.save_some_record_saved();
+
+        let status = editor.save_some_next_status();
+        assert_eq!(status.buffer_id, Some(ids[1]));
+        assert!(editor.save_some_step("n").unwrap().is_empty());
+        let status = editor.save_some_next_status();
+        assert_eq!(status.buffer_id, Some(ids[2]));
+        assert!(editor.save_some_step("q").unwrap().is_empty());
+
+        let status = editor.save_some_next_status();
+        assert!(status.done);
+        assert_eq!((status.saved_count, status.unsaved_count), (1, 2));
+        let err = editor.check_quit(false).unwrap_err();
+        assert!(err.contains("b.txt, c.txt"));
+        assert!(editor.check_quit(true).is_ok());
+    }
+}
@@ -1,6 +1,9 @@
 use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::PathBuf;
 
-use crate::editor::state::BufferState;
+use crate::editor::buffer::TextEdit;
+use crate::editor::state::{BufferId, BufferState};
 
 #[derive(Debug, Clone)]
 pub struct QueryReplaceSession {
@@ -8,6 +11,11 @@ pub struct QueryReplaceSession {
     pub replace_with: String,
     pub search_from: usize,
     pub replaced_count: usize,
+    /// Whether matching ignores case; see `folds_case`.
+    pub fold: bool,
+    /// Where point goes when the session ends, moved over replacements before it.
+    /// Set by project query replace so visiting a file leaves its point alone.
+    pub return_point: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
@@ -20,20 +28,112 @@ pub struct QueryReplaceStatus {
     pub message: String,
 }
 
+/// Query replace that walks a list of files, one buffer at a time.
+#[derive(Debug, Clone)]
+pub struct ProjectReplaceSession {
+    pub query: String,
+    pub replace_with: String,
+    pub pending: VecDeque<PathBuf>,
+    pub current: Option<PathBuf>,
+    pub file_counts: Vec<FileReplaceCount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileReplaceCount {
+    pub path: String,
+    pub replaced_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectReplaceStatus {
+    pub done: bool,
+    pub file: Option<String>,
+    pub replaced_count: usize,
+    pub total_replaced: usize,
+    pub next_line: Option<usize>,
+    pub next_col: Option<usize>,
+    pub message: String,
+    pub file_counts: Vec<FileReplaceCount>,
+    pub modified_buffers: Vec<BufferId>,
+}
+
+impl ProjectReplaceSession {
+    pub fn new(query: String, replace_with: String, files: Vec<PathBuf>) -> Self {
+        Self {
+            query,
+            replace_with,
+            pending: files.into(),
+            current: None,
+            file_counts: Vec::new(),
+        }
+    }
+
+    /// Records the result of the file being processed and clears `current`.
+    pub fn finish_current(&mut self, replaced_count: usize) {
+        if let Some(path) = self.current.take() {
+            self.file_counts.push(FileReplaceCount {
+                path: path.to_string_lossy().to_string(),
+                replaced_count,
+            });
+        }
+    }
+
+    pub fn total_replaced(&self) -> usize {
+        self.file_counts.iter().map(|f| f.replaced_count).sum()
+    }
+
+    pub fn summary(&self, cancelled: bool) -> String {
+        let files = self
+            .file_counts
+            .iter()
+            .filter(|f| f.replaced_count > 0)
+            .count();
+        let verb = if cancelled { "cancelled" } else { "finished" };
+        format!(
+            "Query replace {}: {} occurrences in {} files",
+            verb,
+            self.total_replaced(),
+            files
+        )
+    }
+}
+
+/// True when a search for `query` should ignore case: `case_fold` is on
+/// and, as in Emacs, the query has no uppercase letters.
+pub fn folds_case(case_fold: bool, query: &str) -> bool {
+    case_fold && !query.chars().any(char::is_uppercase)
+}
+
+/// Whether `text` contains `query`, ignoring case when `fold` is set.
+pub fn contains_query(text: &str, query: &str, fold: bool) -> bool {
+    if fold {
+        text.to_lowercase().contains(&query.to_lowercase())
+    } else {
+        text.contains(query)
+    }
+}
+
+fn chars_match(a: char, b: char, fold: bool) -> bool {
+    a == b || (fold && a.to_lowercase().eq(b.to_lowercase()))
+}
+
 impl BufferState {
-    pub fn isearch_forward(&mut self, query: &str) -> Result<(), String> {
+    pub fn isearch_forward(&mut self, query: &str, case_fold: bool) -> Result<(), String> {
         if query.is_empty() {
             return Err("search query is empty".to_string());
         }
 
-        let start = (self.cursor + 1).min(self.buffer.char_len());
-        if let Some(pos) = self.find_next_match_from(start, query) {
+        let fold = folds_case(case_fold, query);
+        let start = (self.cursor + 1).min(self.point_max());
+        if let Some(pos) = self.find_next_match_from(start, query, fold) {
             self.cursor = pos;
             self.set_status_message(Some(format!("I-Search forward: {}", query)));
             return Ok(());
         }
 
-        if let Some(pos) = self.find_next_match_from(0, query) {
+        if let Some(pos) = self.find_next_match_from(self.point_min(), query, fold) {
             self.cursor = pos;
             self.set_status_message(Some(format!("I-Search wrapped: {}", query)));
             return Ok(());
@@ -42,19 +142,20 @@ impl BufferState {
         Err(format!("Not found: {}", query))
     }
 
-    pub fn isearch_backward(&mut self, query: &str) -> Result<(), String> {
+    pub fn isearch_backward(&mut self, query: &str, case_fold: bool) -> Result<(), String> {
         if query.is_empty() {
             return Err("search query is empty".to_string());
         }
 
+        let fold = folds_case(case_fold, query);
         let start = self.cursor.saturating_sub(1);
-        if let Some(pos) = self.find_prev_match_from(start, query) {
+        if let Some(pos) = self.find_prev_match_from(start, query, fold) {
             self.cursor = pos;
             self.set_status_message(Some(format!("I-Search backward: {}", query)));
             return Ok(());
         }
 
-        if let Some(pos) = self.find_prev_match_from(self.buffer.char_len(), query) {
+        if let Some(pos) = self.find_prev_match_from(self.point_max(), query, fold) {
             self.cursor = pos;
             self.set_status_message(Some(format!("I-Search wrapped: {}", query)));
             return Ok(());
@@ -67,16 +168,20 @@ impl BufferState {
         &mut self,
         query: String,
         replace_with: String,
+        case_fold: bool,
     ) -> Result<QueryReplaceStatus, String> {
         if query.is_empty() {
             return Err("query must not be empty".to_string());
         }
+        self.check_writable()?;
 
         self.query_replace_session = Some(QueryReplaceSession {
+            fold: folds_case(case_fold, &query),
             query,
             replace_with,
-            search_from: 0,
+            search_from: self.point_min(),
             replaced_count: 0,
+            return_point: None,
         });
 
         Ok(self.query_replace_next_status())
@@ -93,7 +198,7 @@ impl BufferState {
                 .query_replace_session
                 .as_ref()
                 .map_or(0, |session| session.replaced_count);
-            self.query_replace_session = None;
+            self.end_query_replace();
             self.set_status_message(Some(format!(
                 "Query replace cancelled ({} replaced)",
                 replaced_count
@@ -107,7 +212,7 @@ impl BufferState {
             });
         }
 
-        let (query, replace_with, search_from, replaced_count) = {
+        let (query, replace_with, search_from, replaced_count, fold) = {
             let session = self
                 .query_replace_session
                 .as_ref()
@@ -117,12 +222,13 @@ impl BufferState {
                 session.replace_with.clone(),
                 session.search_from,
                 session.replaced_count,
+                session.fold,
             )
         };
 
-        let maybe_pos = self.find_next_match_from(search_from, &query);
+        let maybe_pos = self.find_next_match_from(search_from, &query, fold);
         let Some(pos) = maybe_pos else {
-            self.query_replace_session = None;
+            self.end_query_replace();
             self.set_status_message(Some(format!(
                 "Replaced {} occurrences",
                 replaced_count
@@ -142,14 +248,14 @@ impl BufferState {
             let query_len = query.chars().count();
             let replace_len = replace_with.chars().count();
 
-            while let Some(found) = self.find_next_match_from(next_from, &query) {
-                self.replace_range(found, found + query_len, &replace_with);
+            while let Some(found) = self.find_next_match_from(next_from, &query, fold) {
+                self.replace_query_match(found, query_len, &replace_with);
                 count += 1;
                 next_from = found + replace_len;
             }
 
-            self.query_replace_session = None;
-            self.modified = true;
+            self.end_query_replace();
+            self.set_modified(true);
             self.set_status_message(Some(format!("Replaced {} occurrences", count)));
             return Ok(QueryReplaceStatus {
                 done: true,
@@ -164,9 +270,9 @@ impl BufferState {
         let mut next_replaced_count = replaced_count;
 
         if action == "y" {
-            self.replace_range(pos, pos + query.chars().count(), &replace_with);
+            self.replace_query_match(pos, query.chars().count(), &replace_with);
             self.cursor = pos + replace_with.chars().count();
-            self.modified = true;
+            self.set_modified(true);
             next_search_from = self.cursor;
             next_replaced_count += 1;
         }
@@ -190,7 +296,7 @@ impl BufferState {
             };
         };
 
-        if let Some(pos) = self.find_next_match_from(session.search_from, &session.query) {
+        if let Some(pos) = self.find_next_match_from(session.search_from, &session.query, session.fold) {
             self.cursor = pos;
             let (line, col) = self.line_col_at(pos);
             QueryReplaceStatus {
@@ -206,7 +312,7 @@ impl BufferState {
             }
         } else {
             let replaced = session.replaced_count;
-            self.query_replace_session = None;
+            self.end_query_replace();
             self.set_status_message(Some(format!("Replaced {} occurrences", replaced)));
             QueryReplaceStatus {
                 done: true,
@@ -218,24 +324,91 @@ impl BufferState {
         }
     }
 
-    fn find_next_match_from(&self, start_char: usize, query: &str) -> Option<usize> {
+    /// Drops the query replace session, putting point back if the session asks for it.
+    pub fn end_query_replace(&mut self) {
+        let session = self.query_replace_session.take();
+        if let Some(point) = session.and_then(|session| session.return_point) {
+            self.cursor = self.clamp_to_accessible(point);
+        }
+    }
+
+    fn replace_query_match(&mut self, pos: usize, len: usize, replace_with: &str) {
+        self.replace_range(pos, pos + len, replace_with);
+        let edit = TextEdit {
+            start: pos,
+            removed: len,
+            inserted: replace_with.chars().count(),
+            own: true,
+        };
+        if let Some(session) = self.query_replace_session.as_mut() {
+            session.return_point = session.return_point.map(|point| edit.adjust(point, false));
+        }
+    }
+
+    /// Finds `query` within the accessible region, starting at `start_char`.
+    fn find_next_match_from(&self, start_char: usize, query: &str, fold: bool) -> Option<usize> {
+        let (min, max) = (self.point_min(), self.point_max());
+        if fold {
+            let (text, query) = self.folded_haystack(query);
+            let last = max.checked_sub(query.len())?;
+            return (start_char.max(min)..=last).find(|i| Self::folded_match_at(&text, &query, *i));
+        }
         let text = self.buffer.as_str();
-        let start_byte = crate::editor::buffer::char_to_byte_index(text, start_char);
-        let relative = text.get(start_byte..)?.find(query)?;
+        let start_byte = crate::editor::buffer::char_to_byte_index(&text, start_char.max(min));
+        let end_byte = crate::editor::buffer::char_to_byte_index(&text, max);
+        let relative = text.get(start_byte..end_byte)?.find(query)?;
         let byte_pos = start_byte + relative;
         Some(text[..byte_pos].chars().count())
     }
 
-    fn find_prev_match_from(&self, start_char: usize, query: &str) -> Option<usize> {
+    /// Finds the last `query` that ends before `start_char` within the accessible region.
+    fn find_prev_match_from(&self, start_char: usize, query: &str, fold: bool) -> Option<usize> {
+        let (min, max) = (self.point_min(), self.point_max());
+        if fold {
+            let (text, query) = self.folded_haystack(query);
+            let last = start_char.min(max).checked_sub(query.len())?;
+            return (min..=last).rev().find(|i| Self::folded_match_at(&text, &query, *i));
+        }
         let text = self.buffer.as_str();
-        let start_byte = crate::editor::buffer::char_to_byte_index(text, start_char);
-        let haystack = text.get(..start_byte)?;
-        let byte_pos = haystack.rfind(query)?;
+        let min_byte = crate::editor::buffer::char_to_byte_index(&text, min);
+        let start_byte = crate::editor::buffer::char_to_byte_index(&text, start_char.min(max));
+        let haystack = text.get(min_byte..start_byte)?;
+        let byte_pos = min_byte + haystack.rfind(query)?;
         Some(text[..byte_pos].chars().count())
     }
 
+    fn folded_haystack(&self, query: &str) -> (Vec<char>, Vec<char>) {
+        (self.buffer.as_str().chars().collect(), query.chars().collect())
+    }
+
+    fn folded_match_at(text: &[char], query: &[char], at: usize) -> bool {
+        text.get(at..at + query.len())
+            .is_some_and(|window| window.iter().zip(query).all(|(a, b)| chars_match(*a, *b, true)))
+    }
+
     fn replace_range(&mut self, start: usize, end: usize, replacement: &str) {
         self.buffer.remove_range(start, end);
         self.buffer.insert_str(start, replacement);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::state::BufferState;
+
+    #[test]
+    fn return_point_follows_replacements_before_it() {
+        let mut buf = BufferState::new();
+        buf.insert_text("foo bar foo baz foo").unwrap();
+        buf.set_cursor(12);
+        buf.start_query_replace("foo".to_string(), "quux".to_string(), false)
+            .unwrap();
+        buf.query_replace_session.as_mut().unwrap().return_point = Some(12);
+        assert_eq!(buf.cursor, 0);
+
+        let status = buf.query_replace_step("!").unwrap();
+        assert!(status.done);
+        assert_eq!(buf.buffer.text(), "quux bar quux baz quux");
+        assert_eq!(buf.cursor, 14);
+    }
+}
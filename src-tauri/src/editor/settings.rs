@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+
+use crate::editor::file_settings::{Charset, FileSettings};
+use crate::editor::minor_mode::DEFAULT_FILL_COLUMN;
+
+pub const DEFAULT_KILL_RING_MAX: usize = 10;
+pub const DEFAULT_UNDO_LIMIT: usize = 1000;
+
+/// What to do with the previous contents of a file before overwriting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupPolicy {
+    None,
+    /// One `file~` backup, replaced on every save.
+    Simple,
+    /// A new `file.~N~` backup on every save.
+    Numbered,
+}
+
+impl BackupPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            BackupPolicy::None => "none",
+            BackupPolicy::Simple => "simple",
+            BackupPolicy::Numbered => "numbered",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "none" => Some(BackupPolicy::None),
+            "simple" => Some(BackupPolicy::Simple),
+            "numbered" => Some(BackupPolicy::Numbered),
+            _ => None,
+        }
+    }
+
+    /// Backup file for `path`; `exists` tells which numbered backups are taken.
+    pub fn backup_path(self, path: &Path, exists: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+        let path = path.to_string_lossy();
+        match self {
+            BackupPolicy::None => None,
+            BackupPolicy::Simple => Some(PathBuf::from(format!("{}~", path))),
+            BackupPolicy::Numbered => (1..)
+                .map(|n| PathBuf::from(format!("{}.~{}~", path, n)))
+                .find(|candidate| !exists(candidate)),
+        }
+    }
+}
+
+/// Editor-wide settings from the `editor`, `files` and `search` sections of
+/// config.yaml. EditorConfig and file-local variables still win per file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Columns a tab advances to; `None` leaves it to the major mode.
+    pub tab_width: Option<usize>,
+    pub fill_column: usize,
+    pub undo_limit: usize,
+    pub kill_ring_max: usize,
+    /// Line ending of new files and buffers.
+    pub line_ending: &'static str,
+    /// Encoding of new files and buffers.
+    pub encoding: Charset,
+    pub backup: BackupPolicy,
+    /// Search ignores case unless the query has an uppercase letter.
+    pub case_fold: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tab_width: None,
+            fill_column: DEFAULT_FILL_COLUMN,
+            undo_limit: DEFAULT_UNDO_LIMIT,
+            kill_ring_max: DEFAULT_KILL_RING_MAX,
+            line_ending: "CRLF",
+            encoding: Charset::Utf8,
+            backup: BackupPolicy::Simple,
+            case_fold: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Fallback for what a visited file does not declare itself. Line
+    /// ending and encoding only apply to new files; existing ones keep
+    /// what was detected.
+    pub fn file_defaults(&self, new_file: bool) -> FileSettings {
+        FileSettings {
+            tab_width: self.tab_width,
+            line_ending: new_file.then_some(self.line_ending),
+            charset: new_file.then_some(self.encoding),
+            ..FileSettings::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BackupPolicy;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn numbered_backups_take_the_next_free_number() {
+        let taken = |path: &Path| path.to_string_lossy().ends_with(".~1~");
+        assert_eq!(
+            BackupPolicy::Numbered.backup_path(Path::new("notes.md"), taken),
+            Some(PathBuf::from("notes.md.~2~"))
+        );
+        assert_eq!(
+            BackupPolicy::Simple.backup_path(Path::new("notes.md"), taken),
+            Some(PathBuf::from("notes.md~"))
+        );
+        assert_eq!(BackupPolicy::None.backup_path(Path::new("notes.md"), taken), None);
+        assert_eq!(BackupPolicy::from_name("Numbered"), Some(BackupPolicy::Numbered));
+    }
+}
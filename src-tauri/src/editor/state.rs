@@ -1,14 +1,43 @@
 use serde::Serialize;
-use std::path::PathBuf;
-
-use crate::editor::buffer::TextBuffer;
-use crate::editor::search::QueryReplaceSession;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::editor::buffer::{TextBuffer, TextEdit};
+use crate::editor::dired::DiredListing;
+use crate::editor::file_settings::FileSettings;
+use crate::editor::grep::{GrepResults, GREP_BUFFER_NAME};
+use crate::editor::highlight::HighlightCache;
+use crate::editor::ibuffer::{BufferList, BUFFER_LIST_NAME};
+use crate::editor::keymap::Keymap;
+use crate::editor::kmacro::MacroRecorder;
+use crate::editor::minor_mode::{MinorMode, MinorModeView, DEFAULT_FILL_COLUMN};
+use crate::editor::mode::{MajorMode, FUNDAMENTAL};
+use crate::editor::narrow::Narrowing;
+use crate::editor::save_some::SaveSomeSession;
+use crate::editor::search::{ProjectReplaceSession, QueryReplaceSession};
+use crate::editor::settings::Settings;
 use crate::editor::undo::{UndoSnapshot, UndoStack};
+use crate::editor::window::{SplitDirection, WindowLayout, WindowNode};
+
+/// Stable identifier of a buffer; unlike its name it never changes while the buffer lives.
+pub type BufferId = u64;
+
+static NEXT_BUFFER_ID: AtomicU64 = AtomicU64::new(1);
+
+fn next_buffer_id() -> BufferId {
+    NEXT_BUFFER_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EditorSnapshot {
+    /// Accessible text; the whole buffer unless narrowed.
     pub text: String,
+    /// Real buffer offset of the first character of `text`.
+    pub narrow_start: usize,
+    pub narrowed: bool,
     pub cursor: usize,
     pub line: usize,
     pub col: usize,
@@ -17,38 +46,213 @@ pub struct EditorSnapshot {
     pub encoding: String,
     pub line_ending: String,
     pub file_path: Option<String>,
+    pub default_directory: Option<String>,
+    pub buffer_id: BufferId,
+    pub buffer_name: String,
+    pub buffer_kind: String,
+    /// Id of the major mode, e.g. `rust`.
+    pub major_mode: String,
+    /// Mode shown in the mode line, e.g. `Rust` or `Dired`.
+    pub mode_name: String,
+    pub minor_modes: Vec<MinorModeView>,
+    pub fill_column: usize,
+    /// Columns a tab character advances to, for rendering.
+    pub tab_width: usize,
+    /// True while a keyboard macro is being recorded.
+    pub defining_macro: bool,
     pub status_message: Option<String>,
+    pub layout: WindowNode,
+    pub windows: Vec<WindowView>,
+}
+
+/// One window's view of its buffer. The selected window mirrors the top-level fields.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowView {
+    pub id: usize,
+    pub selected: bool,
+    pub buffer_name: String,
+    pub buffer_kind: String,
+    pub text: String,
+    pub narrow_start: usize,
+    pub cursor: usize,
+    pub line: usize,
+    pub col: usize,
+    pub scroll_line: usize,
+    pub modified: bool,
+    pub tab_width: usize,
+}
+
+/// Data backing a buffer that is generated by the editor rather than read from a file.
+#[derive(Debug, Clone)]
+pub enum SpecialBuffer {
+    Grep(GrepResults),
+    Dired(DiredListing),
+    BufferList(BufferList),
+}
+
+impl SpecialBuffer {
+    pub fn name(&self) -> String {
+        match self {
+            SpecialBuffer::Grep(_) => GREP_BUFFER_NAME.to_string(),
+            SpecialBuffer::Dired(listing) => listing.buffer_name(),
+            SpecialBuffer::BufferList(_) => BUFFER_LIST_NAME.to_string(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SpecialBuffer::Grep(_) => "grep",
+            SpecialBuffer::Dired(listing) if listing.wdired => "wdired",
+            SpecialBuffer::Dired(_) => "dired",
+            SpecialBuffer::BufferList(_) => "ibuffer",
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        match self {
+            SpecialBuffer::Grep(results) => &results.root,
+            SpecialBuffer::Dired(listing) => &listing.directory,
+            SpecialBuffer::BufferList(list) => &list.directory,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct BufferState {
+    pub id: BufferId,
     pub buffer: TextBuffer,
     pub cursor: usize,
     pub undo_stack: UndoStack,
-    pub modified: bool,
+    /// Shared with indirect buffers, like `buffer` and `undo_stack`.
+    modified: Arc<AtomicBool>,
     pub original_encoding: String,
     pub line_ending: String,
     pub file_path: Option<PathBuf>,
     pub status_message: Option<String>,
     pub query_replace_session: Option<QueryReplaceSession>,
+    pub special: Option<SpecialBuffer>,
+    /// Set for buffers made by `clone_indirect`.
+    pub indirect_name: Option<String>,
+    /// Accessible region; `None` when the whole buffer is visible.
+    pub narrowing: Option<Narrowing>,
+    /// Disambiguating suffix set by `EditorState::uniquify_buffer_names`.
+    pub name_suffix: Option<String>,
+    pub major_mode: &'static MajorMode,
+    pub minor_modes: Vec<MinorMode>,
+    pub fill_column: usize,
+    pub highlight: HighlightCache,
+    /// Declared by EditorConfig or file-local variables when the file was visited.
+    pub file_settings: FileSettings,
 }
 
 impl BufferState {
     pub fn new() -> Self {
         Self {
+            id: next_buffer_id(),
             buffer: TextBuffer::new(),
             cursor: 0,
             undo_stack: UndoStack::new(),
-            modified: false,
+            modified: Arc::new(AtomicBool::new(false)),
             original_encoding: "UTF-8".to_string(),
             line_ending: "CRLF".to_string(),
             file_path: None,
             status_message: None,
             query_replace_session: None,
+            special: None,
+            indirect_name: None,
+            narrowing: None,
+            name_suffix: None,
+            major_mode: &FUNDAMENTAL,
+            minor_modes: Vec::new(),
+            fill_column: DEFAULT_FILL_COLUMN,
+            highlight: HighlightCache::default(),
+            file_settings: FileSettings::default(),
+        }
+    }
+
+    /// Creates an indirect buffer named `name` that shares this buffer's text,
+    /// undo history and modified flag but has its own point.
+    pub fn clone_indirect(&self, name: String) -> Self {
+        Self {
+            id: next_buffer_id(),
+            buffer: self.buffer.share(),
+            cursor: self.cursor,
+            undo_stack: self.undo_stack.share(),
+            modified: Arc::clone(&self.modified),
+            original_encoding: self.original_encoding.clone(),
+            line_ending: self.line_ending.clone(),
+            file_path: None,
+            status_message: None,
+            query_replace_session: None,
+            special: None,
+            indirect_name: Some(name),
+            narrowing: self.narrowing,
+            name_suffix: None,
+            major_mode: self.major_mode,
+            minor_modes: self.minor_modes.clone(),
+            fill_column: self.fill_column,
+            highlight: HighlightCache::default(),
+            file_settings: self.file_settings.clone(),
+        }
+    }
+
+    pub fn is_indirect(&self) -> bool {
+        self.indirect_name.is_some()
+    }
+
+    /// Moves point and the narrowing bounds over edits that indirect siblings made to
+    /// the shared text. The buffer's own edits already keep them right. Highlighting
+    /// is dropped from the first line any edit touched.
+    pub fn follow_text_edits(&mut self, edits: &[TextEdit]) {
+        if let Some(start) = edits.iter().map(|edit| edit.start).min() {
+            self.invalidate_highlight(start);
         }
+        let delta: isize = edits.iter().map(TextEdit::delta).sum();
+        let mut len = self.buffer.char_len().saturating_add_signed(-delta);
+        for edit in edits {
+            if !edit.own {
+                self.cursor = edit.adjust(self.cursor, false);
+                self.narrowing = self.narrowing.map(|n| n.follow(edit, len));
+            }
+            len = len.saturating_add_signed(edit.delta());
+        }
+    }
+
+    /// Gives the buffer fresh text, undo history and modified flag, detaching it
+    /// from any indirect buffers that shared the old ones.
+    pub fn replace_contents(&mut self, text: String) {
+        self.buffer = TextBuffer::from_text(text);
+        self.highlight = HighlightCache::default();
+        self.undo_stack = UndoStack::new();
+        self.modified = Arc::new(AtomicBool::new(false));
+        self.indirect_name = None;
+        self.narrowing = None;
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.modified.load(Ordering::Relaxed)
+    }
+
+    pub fn set_modified(&mut self, modified: bool) {
+        self.modified.store(modified, Ordering::Relaxed);
     }
 
+    /// Display name, including any `<dir>` suffix added to tell same-named files apart.
     pub fn name(&self) -> String {
+        match &self.name_suffix {
+            Some(suffix) => format!("{}<{}>", self.base_name(), suffix),
+            None => self.base_name(),
+        }
+    }
+
+    pub fn base_name(&self) -> String {
+        if let Some(name) = &self.indirect_name {
+            return name.clone();
+        }
+        if let Some(special) = &self.special {
+            return special.name();
+        }
         match &self.file_path {
             Some(path) => path
                 .file_name()
@@ -58,6 +262,17 @@ impl BufferState {
         }
     }
 
+    /// Mode name shown in buffer lists and the mode line.
+    pub fn mode_name(&self) -> String {
+        match &self.special {
+            Some(SpecialBuffer::Grep(_)) => "Grep",
+            Some(SpecialBuffer::Dired(_)) => "Dired",
+            Some(SpecialBuffer::BufferList(_)) => "Buffer List",
+            None => self.major_mode.name,
+        }
+        .to_string()
+    }
+
     pub fn load_content(
         &mut self,
         content: String,
@@ -65,14 +280,26 @@ impl BufferState {
         line_ending: String,
         file_path: PathBuf,
     ) {
-        self.buffer = TextBuffer::from_text(content);
+        self.replace_contents(content);
         self.cursor = 0;
-        self.modified = false;
         self.original_encoding = encoding;
         self.line_ending = line_ending;
         self.file_path = Some(file_path);
-        self.undo_stack.clear_all();
         self.query_replace_session = None;
+        self.special = None;
+        self.file_settings = FileSettings::default();
+        self.set_auto_mode();
+    }
+
+    /// Directory used as the starting point for file prompts, like Emacs' `default-directory`.
+    pub fn default_directory(&self) -> Option<PathBuf> {
+        if let Some(special) = &self.special {
+            return Some(special.directory().to_path_buf());
+        }
+        self.file_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(Path::to_path_buf)
     }
 
     pub fn set_file_path(&mut self, file_path: PathBuf) {
@@ -84,12 +311,12 @@ impl BufferState {
     }
 
     pub fn mark_saved(&mut self) {
-        self.modified = false;
+        self.set_modified(false);
     }
 
     pub fn record_undo_snapshot(&mut self) {
         self.undo_stack.push_undo(UndoSnapshot {
-            text: self.buffer.as_str().to_string(),
+            text: self.buffer.text(),
             cursor: self.cursor,
         });
         self.undo_stack.clear_redo();
@@ -100,15 +327,22 @@ impl BufferState {
             self.set_status_message(Some("Undo: no more changes".to_string()));
             return;
         };
+        if !self.change_is_accessible(&prev.text) {
+            self.undo_stack.push_undo(prev);
+            self.set_status_message(Some(
+                "Changes to be undone are outside visible portion of buffer".to_string(),
+            ));
+            return;
+        }
 
         let current = UndoSnapshot {
-            text: self.buffer.as_str().to_string(),
+            text: self.buffer.text(),
             cursor: self.cursor,
         };
         self.undo_stack.push_redo(current);
-        self.buffer = TextBuffer::from_text(prev.text);
-        self.cursor = prev.cursor.min(self.buffer.char_len());
-        self.modified = true;
+        self.buffer.set_text(prev.text);
+        self.cursor = self.clamp_to_accessible(prev.cursor);
+        self.set_modified(true);
         self.set_status_message(Some("Undo".to_string()));
     }
 
@@ -117,15 +351,22 @@ impl BufferState {
             self.set_status_message(Some("Redo: no more changes".to_string()));
             return;
         };
+        if !self.change_is_accessible(&next.text) {
+            self.undo_stack.push_redo(next);
+            self.set_status_message(Some(
+                "Changes to be redone are outside visible portion of buffer".to_string(),
+            ));
+            return;
+        }
 
         let current = UndoSnapshot {
-            text: self.buffer.as_str().to_string(),
+            text: self.buffer.text(),
             cursor: self.cursor,
         };
         self.undo_stack.push_undo(current);
-        self.buffer = TextBuffer::from_text(next.text);
-        self.cursor = next.cursor.min(self.buffer.char_len());
-        self.modified = true;
+        self.buffer.set_text(next.text);
+        self.cursor = self.clamp_to_accessible(next.cursor);
+        self.set_modified(true);
         self.set_status_message(Some("Redo".to_string()));
     }
 
@@ -195,6 +436,20 @@ pub struct EditorState {
     pub kill_ring: Vec<String>,
     /// Index of the previously active buffer, used as the default for C-x b
     pub prev_index: usize,
+    pub project_replace: Option<ProjectReplaceSession>,
+    pub save_some: Option<SaveSomeSession>,
+    /// Recently visited files, most recent first.
+    pub recent_files: Vec<PathBuf>,
+    pub recent_files_max: usize,
+    pub kmacro: MacroRecorder,
+    pub keymap: Keymap,
+    /// Commands run with M-x, most recent first.
+    pub command_history: Vec<String>,
+    /// Window tree; the selected window always shows `buffers[current_index]`.
+    pub windows: WindowLayout,
+    pub settings: Settings,
+    /// Config file each configured setting came from, keyed by `section.key`.
+    pub settings_sources: BTreeMap<String, String>,
 }
 
 impl EditorState {
@@ -204,6 +459,47 @@ impl EditorState {
             current_index: 0,
             kill_ring: Vec::new(),
             prev_index: 0,
+            project_replace: None,
+            save_some: None,
+            recent_files: Vec::new(),
+            recent_files_max: 50,
+            kmacro: MacroRecorder::default(),
+            keymap: Keymap::default(),
+            command_history: Vec::new(),
+            windows: WindowLayout::new(0),
+            settings: Settings::default(),
+            settings_sources: BTreeMap::new(),
+        }
+    }
+
+    /// A fresh buffer with the configured defaults for new buffers.
+    pub fn new_buffer(&self) -> BufferState {
+        let mut buf = BufferState::new();
+        buf.fill_column = self.settings.fill_column;
+        buf.undo_stack.set_limit(self.settings.undo_limit);
+        buf.apply_file_settings(self.settings.file_defaults(true));
+        buf
+    }
+
+    /// Switches to new settings. Existing buffers get the new undo limit,
+    /// and an untouched `*scratch*` is recreated with the new defaults.
+    pub fn apply_settings(&mut self, settings: Settings, sources: BTreeMap<String, String>) {
+        self.settings = settings;
+        self.settings_sources = sources;
+        self.kill_ring.truncate(self.settings.kill_ring_max);
+        for index in 0..self.buffers.len() {
+            let buf = &self.buffers[index];
+            let untouched = buf.file_path.is_none()
+                && buf.special.is_none()
+                && !buf.is_indirect()
+                && !buf.is_modified()
+                && buf.buffer.char_len() == 0;
+            if untouched {
+                let id = buf.id;
+                self.buffers[index] = BufferState { id, ..self.new_buffer() };
+            } else {
+                self.buffers[index].undo_stack.set_limit(self.settings.undo_limit);
+            }
         }
     }
 
@@ -220,25 +516,142 @@ impl EditorState {
         (&mut self.buffers[self.current_index], &mut self.kill_ring)
     }
 
-    pub fn snapshot(&self) -> EditorSnapshot {
+    pub fn snapshot(&mut self) -> EditorSnapshot {
+        self.sync_text_edits();
         let buf = self.current();
         let (line, col) = buf.line_col();
 
         EditorSnapshot {
-            text: buf.buffer.as_str().to_string(),
+            text: buf.accessible_text(),
+            narrow_start: buf.point_min(),
+            narrowed: buf.is_narrowed(),
             cursor: buf.cursor,
             line,
             col,
             chars: buf.buffer.char_len(),
-            modified: buf.modified,
+            modified: buf.is_modified(),
             encoding: buf.original_encoding.clone(),
             line_ending: buf.line_ending.clone(),
             file_path: buf
                 .file_path
                 .as_ref()
                 .map(|path| path.to_string_lossy().to_string()),
+            default_directory: buf
+                .default_directory()
+                .map(|path| path.to_string_lossy().to_string()),
+            buffer_id: buf.id,
+            buffer_name: buf.name(),
+            buffer_kind: buf
+                .special
+                .as_ref()
+                .map_or("file", |special| special.kind())
+                .to_string(),
+            major_mode: buf.major_mode.id.to_string(),
+            mode_name: buf.mode_name(),
+            minor_modes: buf.minor_mode_views(),
+            fill_column: buf.fill_column,
+            tab_width: buf.tab_width(),
+            defining_macro: self.defining_kbd_macro(),
             status_message: buf.status_message.clone(),
+            layout: self.windows.root.clone(),
+            windows: self.window_views(),
+        }
+    }
+
+    /// Moves positions kept outside the text over the edits made since the last call:
+    /// points and narrowing of indirect siblings, and points of unselected windows.
+    /// Runs before windows are switched so a saved point is never moved twice.
+    pub fn sync_text_edits(&mut self) {
+        for index in 0..self.buffers.len() {
+            let edits = self.buffers[index].buffer.take_edits();
+            if !edits.is_empty() {
+                self.buffers[index].follow_text_edits(&edits);
+                self.windows.follow_text_edits(index, &edits);
+            }
+        }
+    }
+
+    fn window_views(&self) -> Vec<WindowView> {
+        self.windows
+            .order()
+            .into_iter()
+            .filter_map(|id| self.windows.get(id))
+            .map(|window| {
+                let selected = window.id == self.windows.selected;
+                let buf = &self.buffers[window.buffer];
+                let cursor = if selected {
+                    buf.cursor
+                } else {
+                    buf.clamp_to_accessible(window.point)
+                };
+                let (line, col) = buf.line_col_at(cursor);
+                WindowView {
+                    id: window.id,
+                    selected,
+                    buffer_name: buf.name(),
+                    buffer_kind: buf
+                        .special
+                        .as_ref()
+                        .map_or("file", |special| special.kind())
+                        .to_string(),
+                    text: buf.accessible_text(),
+                    narrow_start: buf.point_min(),
+                    cursor,
+                    line,
+                    col,
+                    scroll_line: window.scroll_line,
+                    modified: buf.is_modified(),
+                    tab_width: buf.tab_width(),
+                }
+            })
+            .collect()
+    }
+
+    /// Makes `id` the selected window, saving point of the old one and restoring the new one's.
+    pub fn select_window(&mut self, id: usize) {
+        if self.windows.get(id).is_none() {
+            return;
         }
+        self.sync_text_edits();
+        let cursor = self.current().cursor;
+        self.windows.selected_mut().point = cursor;
+        self.windows.selected = id;
+        self.load_selected_window();
+    }
+
+    fn load_selected_window(&mut self) {
+        let window = self.windows.selected().clone();
+        self.current_index = window.buffer;
+        let buf = self.current_mut();
+        buf.cursor = buf.clamp_to_accessible(window.point);
+    }
+
+    pub fn set_window_scroll(&mut self, scroll_line: usize) {
+        self.windows.selected_mut().scroll_line = scroll_line;
+    }
+
+    pub fn split_window(&mut self, direction: SplitDirection) {
+        self.sync_text_edits();
+        let cursor = self.current().cursor;
+        self.windows.selected_mut().point = cursor;
+        self.windows.split(direction);
+    }
+
+    pub fn other_window(&mut self, count: isize) {
+        let next = self.windows.next_window(count);
+        self.select_window(next);
+    }
+
+    pub fn delete_window(&mut self) -> Result<(), String> {
+        self.sync_text_edits();
+        let selected = self.windows.selected;
+        self.windows.delete(selected)?;
+        self.load_selected_window();
+        Ok(())
+    }
+
+    pub fn delete_other_windows(&mut self) {
+        self.windows.delete_others();
     }
 
     pub fn push_kill_ring(&mut self, text: String) {
@@ -246,30 +659,66 @@ impl EditorState {
             return;
         }
         self.kill_ring.insert(0, text);
-        if self.kill_ring.len() > 10 {
-            self.kill_ring.truncate(10);
-        }
+        self.kill_ring.truncate(self.settings.kill_ring_max);
+    }
+
+    pub fn record_recent_file(&mut self, path: &Path) {
+        self.recent_files.retain(|p| p != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(self.recent_files_max);
+    }
+
+    /// Drops recent-file entries whose files no longer exist; returns how many were removed.
+    pub fn remove_missing_recent_files(&mut self) -> usize {
+        let before = self.recent_files.len();
+        self.recent_files.retain(|p| p.exists());
+        before - self.recent_files.len()
+    }
+
+    pub fn modified_file_buffer_ids(&self) -> Vec<BufferId> {
+        self.buffers
+            .iter()
+            .filter(|b| b.is_modified() && b.file_path.is_some())
+            .map(|b| b.id)
+            .collect()
+    }
+
+    pub fn index_of(&self, id: BufferId) -> Option<usize> {
+        self.buffers.iter().position(|b| b.id == id)
     }
 
     pub fn buffer_names(&self) -> Vec<String> {
         self.buffers.iter().map(|b| b.name()).collect()
     }
 
-    pub fn switch_to_buffer(&mut self, name: &str) -> Result<(), String> {
+    pub fn switch_to_buffer(&mut self, id: BufferId) -> Result<(), String> {
         let index = self
-            .buffers
-            .iter()
-            .position(|b| b.name() == name)
-            .ok_or_else(|| format!("No buffer named '{}'", name))?;
-        if index != self.current_index {
-            self.prev_index = self.current_index;
-            self.current_index = index;
-        }
+            .index_of(id)
+            .ok_or_else(|| format!("No buffer with id {}", id))?;
+        self.switch_to_index(index);
+        let name = self.current().name();
         self.current_mut()
             .set_status_message(Some(format!("Switched to {}", name)));
         Ok(())
     }
 
+    pub fn find_dired_buffer(&self, directory: &Path) -> Option<usize> {
+        self.buffers.iter().position(|b| {
+            b.dired_listing()
+                .map(|listing| listing.directory == directory)
+                .unwrap_or(false)
+        })
+    }
+
+    pub fn find_special_buffer(&self, name: &str) -> Option<usize> {
+        self.buffers.iter().position(|b| {
+            b.special
+                .as_ref()
+                .map(|special| special.name() == name)
+                .unwrap_or(false)
+        })
+    }
+
     pub fn find_buffer_by_path(&self, path: &PathBuf) -> Option<usize> {
         self.buffers.iter().position(|b| {
             b.file_path
@@ -279,50 +728,129 @@ impl EditorState {
         })
     }
 
+    /// Shows `buffers[index]` in the selected window.
     pub fn switch_to_index(&mut self, index: usize) {
+        self.sync_text_edits();
+        // Every newly opened buffer is switched to, so names are recomputed here.
+        self.uniquify_buffer_names();
         if index != self.current_index {
             self.prev_index = self.current_index;
             self.current_index = index;
         }
+        // An indirect sibling may have shortened the text since this buffer was shown.
+        let buf = self.current_mut();
+        buf.cursor = buf.clamp_to_accessible(buf.cursor);
+        let cursor = buf.cursor;
+        let window = self.windows.selected_mut();
+        window.buffer = index;
+        window.point = cursor;
     }
 
-    pub fn default_switch_name(&self) -> String {
+    pub fn default_switch_id(&self) -> BufferId {
         if self.prev_index < self.buffers.len() && self.prev_index != self.current_index {
-            self.buffers[self.prev_index].name()
+            self.buffers[self.prev_index].id
         } else {
             self.buffers
                 .iter()
                 .enumerate()
                 .find(|(i, _)| *i != self.current_index)
-                .map(|(_, b)| b.name())
-                .unwrap_or_else(|| self.current().name())
+                .map(|(_, b)| b.id)
+                .unwrap_or_else(|| self.current().id)
         }
     }
 
-    pub fn kill_buffer(&mut self, name: Option<&str>, force: bool) -> Result<String, String> {
-        let target_index = match name {
-            Some(name) if !name.trim().is_empty() => self
-                .buffers
-                .iter()
-                .position(|b| b.name() == name)
-                .ok_or_else(|| format!("No buffer named '{}'", name))?,
-            _ => self.current_index,
+    /// Creates an indirect clone of the current buffer and shows it in the selected window.
+    pub fn clone_indirect_buffer(&mut self) -> Result<(), String> {
+        let index = self.push_indirect_clone()?;
+        self.switch_to_index(index);
+        Ok(())
+    }
+
+    /// Like `clone_indirect_buffer`, but shows the clone in another window, splitting if needed.
+    pub fn clone_indirect_buffer_other_window(&mut self) -> Result<(), String> {
+        let index = self.push_indirect_clone()?;
+        if self.windows.windows.len() == 1 {
+            self.split_window(SplitDirection::Vertical);
+        }
+        self.other_window(1);
+        self.switch_to_index(index);
+        Ok(())
+    }
+
+    fn push_indirect_clone(&mut self) -> Result<usize, String> {
+        let base = self.current();
+        if base.special.is_some() {
+            return Err(format!("Cannot clone special buffer {}", base.name()));
+        }
+        let base_name = base.name();
+        let stem = match (base.is_indirect(), base_name.rfind('<')) {
+            (true, Some(pos)) if base_name.ends_with('>') => base_name[..pos].to_string(),
+            _ => base_name,
+        };
+        let names = self.buffer_names();
+        let name = (2..)
+            .map(|n| format!("{stem}<{n}>"))
+            .find(|candidate| !names.contains(candidate))
+            .expect("an unused clone name always exists");
+        let clone = self.current().clone_indirect(name.clone());
+        self.buffers.push(clone);
+        let index = self.buffers.len() - 1;
+        self.buffers[index].set_status_message(Some(format!("Cloned {stem} as {name}")));
+        Ok(index)
+    }
+
+    /// Kills a buffer. Killing a base buffer also kills its indirect buffers; an
+    /// indirect buffer never needs confirmation because its text lives on in the base.
+    pub fn kill_buffer(&mut self, id: Option<BufferId>, force: bool) -> Result<String, String> {
+        let target_index = match id {
+            Some(id) => self
+                .index_of(id)
+                .ok_or_else(|| format!("No buffer with id {}", id))?,
+            None => self.current_index,
         };
 
-        if self.buffers[target_index].modified && !force {
+        let target = &self.buffers[target_index];
+        if target.is_modified() && !target.is_indirect() && !force {
             return Err("Buffer modified. Confirmation required.".to_string());
         }
 
-        let killed_name = self.buffers[target_index].name();
+        let killed_name = target.name();
+        let doomed: Vec<usize> = if target.is_indirect() {
+            vec![target_index]
+        } else {
+            self.buffers
+                .iter()
+                .enumerate()
+                .filter(|(i, b)| {
+                    *i == target_index
+                        || (b.is_indirect() && b.buffer.shares_text_with(&target.buffer))
+                })
+                .map(|(i, _)| i)
+                .collect()
+        };
+        for index in doomed.iter().rev() {
+            self.remove_buffer(*index);
+        }
+        self.uniquify_buffer_names();
+
+        let message = match doomed.len() {
+            1 => format!("Killed {}", killed_name),
+            n => format!("Killed {} and {} indirect buffer(s)", killed_name, n - 1),
+        };
+        self.current_mut().set_status_message(Some(message));
+        Ok(killed_name)
+    }
+
+    fn remove_buffer(&mut self, target_index: usize) {
         self.buffers.remove(target_index);
 
         if self.buffers.is_empty() {
-            self.buffers.push(BufferState::new());
+            let buf = self.new_buffer();
+            self.buffers.push(buf);
             self.current_index = 0;
             self.prev_index = 0;
-            self.current_mut()
-                .set_status_message(Some(format!("Killed {}", killed_name)));
-            return Ok(killed_name);
+            self.windows.buffer_removed(target_index, 0);
+            return;
         }
 
         if target_index < self.current_index {
@@ -341,21 +869,24 @@ impl EditorState {
             self.prev_index = self.current_index;
         }
 
-        self.current_mut()
-            .set_status_message(Some(format!("Killed {}", killed_name)));
-        Ok(killed_name)
+        self.windows.buffer_removed(target_index, self.current_index);
+        let cursor = self.current().cursor;
+        let window = self.windows.selected_mut();
+        window.buffer = self.current_index;
+        window.point = cursor;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{BufferState, EditorState};
+    use crate::editor::window::SplitDirection;
     use std::path::PathBuf;
 
     #[test]
     fn kill_current_modified_requires_force() {
         let mut editor = EditorState::new();
-        editor.current_mut().modified = true;
+        editor.current_mut().set_modified(true);
         let err = editor.kill_buffer(None, false).expect_err("expected confirmation error");
         assert!(err.contains("Confirmation required"));
     }
@@ -374,9 +905,54 @@ mod tests {
         editor.current_index = 2;
         editor.prev_index = 1;
 
-        let killed = editor.kill_buffer(Some("a.txt"), true).expect("kill should succeed");
+        let a_id = editor.buffers[1].id;
+        let killed = editor.kill_buffer(Some(a_id), true).expect("kill should succeed");
         assert_eq!(killed, "a.txt");
         assert_eq!(editor.current().name(), "b.txt");
-        assert_eq!(editor.default_switch_name(), "*scratch*");
+        assert_eq!(editor.default_switch_id(), editor.buffers[0].id);
+    }
+
+    #[test]
+    fn windows_keep_their_own_point() {
+        let mut editor = EditorState::new();
+        editor.current_mut().insert_text("hello world").unwrap();
+        editor.split_window(SplitDirection::Vertical);
+        editor.current_mut().set_cursor(2);
+
+        editor.other_window(1);
+        assert_eq!(editor.current().cursor, 11);
+        editor.other_window(1);
+        assert_eq!(editor.current().cursor, 2);
+
+        // The other window's point moves with the text like a marker.
+        editor.current_mut().insert_text("big ").unwrap();
+        let snapshot = editor.snapshot();
+        let cursors: Vec<usize> = snapshot.windows.iter().map(|w| w.cursor).collect();
+        assert_eq!(cursors, vec![6, 15]);
+        editor.other_window(1);
+        assert_eq!(editor.current().cursor, 15);
+    }
+
+    #[test]
+    fn indirect_buffers_share_text_and_die_with_their_base() {
+        let mut editor = EditorState::new();
+        editor.current_mut().set_file_path(PathBuf::from("notes.md"));
+        editor.current_mut().insert_text("abc").unwrap();
+        editor.clone_indirect_buffer().unwrap();
+        assert_eq!(editor.current().name(), "notes.md<2>");
+
+        editor.current_mut().set_cursor(0);
+        editor.current_mut().insert_text("x").unwrap();
+        editor.sync_text_edits();
+        assert_eq!(editor.buffers[0].buffer.text(), "xabc");
+        assert_eq!(editor.buffers[0].cursor, 4);
+        editor.switch_to_buffer(editor.buffers[0].id).unwrap();
+        editor.current_mut().undo();
+        editor.sync_text_edits();
+        assert_eq!(editor.buffers[1].buffer.text(), "abc");
+        assert_eq!(editor.buffers[1].cursor, 0);
+
+        editor.kill_buffer(Some(editor.buffers[0].id), true).unwrap();
+        assert_eq!(editor.buffer_names(), vec!["*scratch*".to_string()]);
     }
 }
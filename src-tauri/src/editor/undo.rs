@@ -1,53 +1,109 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
 #[derive(Debug, Clone)]
 pub struct UndoSnapshot {
     pub text: String,
     pub cursor: usize,
 }
 
-#[derive(Debug, Clone)]
-pub struct UndoStack {
+#[derive(Debug)]
+struct UndoHistory {
     undo_stack: Vec<UndoSnapshot>,
     redo_stack: Vec<UndoSnapshot>,
     max_size: usize,
+    pushes: u64,
+}
+
+/// Undo history of a buffer, shared with its indirect buffers like the text itself.
+#[derive(Debug)]
+pub struct UndoStack {
+    history: Arc<Mutex<UndoHistory>>,
 }
 
 impl UndoStack {
     pub fn new() -> Self {
         Self {
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
-            max_size: 1000,
+            history: Arc::new(Mutex::new(UndoHistory {
+                undo_stack: Vec::new(),
+                redo_stack: Vec::new(),
+                max_size: 1000,
+                pushes: 0,
+            })),
+        }
+    }
+
+    /// Another handle to the same history.
+    pub fn share(&self) -> Self {
+        Self {
+            history: Arc::clone(&self.history),
         }
     }
 
+    fn history(&self) -> MutexGuard<'_, UndoHistory> {
+        self.history.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
     pub fn push_undo(&mut self, snapshot: UndoSnapshot) {
-        self.undo_stack.push(snapshot);
-        if self.undo_stack.len() > self.max_size {
-            self.undo_stack.remove(0);
+        let mut history = self.history();
+        history.undo_stack.push(snapshot);
+        history.pushes += 1;
+        if history.undo_stack.len() > history.max_size {
+            history.undo_stack.remove(0);
         }
     }
 
     pub fn pop_undo(&mut self) -> Option<UndoSnapshot> {
-        self.undo_stack.pop()
+        self.history().undo_stack.pop()
+    }
+
+    /// Counts every snapshot ever pushed; pass it to `amalgamate` later.
+    /// Unlike the stack length it keeps growing once the history is full.
+    pub fn mark(&self) -> u64 {
+        self.history().pushes
+    }
+
+    /// Keeps only the first snapshot pushed since `mark`, so everything
+    /// recorded after that point undoes in one step.
+    pub fn amalgamate(&mut self, mark: u64) {
+        let mut history = self.history();
+        let pushed = usize::try_from(history.pushes - mark).unwrap_or(usize::MAX);
+        let pushed = pushed.min(history.undo_stack.len());
+        if pushed > 1 {
+            let keep = history.undo_stack.len() - pushed + 1;
+            history.undo_stack.truncate(keep);
+        }
+    }
+
+    /// Caps the undo and redo stacks at `limit` snapshots, dropping the oldest.
+    pub fn set_limit(&mut self, limit: usize) {
+        let mut guard = self.history();
+        let history = &mut *guard;
+        history.max_size = limit;
+        for stack in [&mut history.undo_stack, &mut history.redo_stack] {
+            let excess = stack.len().saturating_sub(limit);
+            stack.drain(..excess);
+        }
     }
 
     pub fn push_redo(&mut self, snapshot: UndoSnapshot) {
-        self.redo_stack.push(snapshot);
-        if self.redo_stack.len() > self.max_size {
-            self.redo_stack.remove(0);
+        let mut history = self.history();
+        history.redo_stack.push(snapshot);
+        if history.redo_stack.len() > history.max_size {
+            history.redo_stack.remove(0);
         }
     }
 
     pub fn pop_redo(&mut self) -> Option<UndoSnapshot> {
-        self.redo_stack.pop()
+        self.history().redo_stack.pop()
     }
 
     pub fn clear_redo(&mut self) {
-        self.redo_stack.clear();
+        self.history().redo_stack.clear();
     }
 
     pub fn clear_all(&mut self) {
-        self.undo_stack.clear();
-        self.redo_stack.clear();
+        let mut history = self.history();
+        history.undo_stack.clear();
+        history.redo_stack.clear();
     }
 }
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::path::{Component, Path};
+
+use crate::editor::state::{BufferState, EditorState};
+
+/// The path that identifies a buffer for uniquifying: its file, or the listed directory.
+fn buffer_path(buf: &BufferState) -> Option<&Path> {
+    if buf.is_indirect() {
+        return None;
+    }
+    match (&buf.file_path, buf.dired_listing()) {
+        (Some(path), _) => Some(path),
+        (None, Some(listing)) => Some(&listing.directory),
+        _ => None,
+    }
+}
+
+/// Parent directory names, nearest first.
+fn parent_dirs(path: &Path) -> Vec<String> {
+    let mut dirs: Vec<String> = path
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.reverse();
+    dirs
+}
+
+fn suffix(dirs: &[String], depth: usize) -> String {
+    let mut parts: Vec<&str> = dirs.iter().take(depth).map(String::as_str).collect();
+    parts.reverse();
+    parts.join("/")
+}
+
+impl EditorState {
+    /// Recomputes `name_suffix` so buffers visiting same-named files get distinct names,
+    /// like Emacs' uniquify with `post-forward-angle-brackets`: `README.md<a>`,
+    /// `README.md<b>`. Each buffer uses as few parent directories as it needs.
+    pub fn uniquify_buffer_names(&mut self) {
+        let mut groups: HashMap<String, Vec<(usize, Vec<String>)>> = HashMap::new();
+        for (index, buf) in self.buffers.iter().enumerate() {
+            if let Some(path) = buffer_path(buf) {
+                groups
+                    .entry(buf.base_name())
+                    .or_default()
+                    .push((index, parent_dirs(path)));
+            }
+        }
+
+        for buf in &mut self.buffers {
+            buf.name_suffix = None;
+        }
+
+        for members in groups.values().filter(|members| members.len() > 1) {
+            for (index, dirs) in members {
+                let others: Vec<&Vec<String>> = members
+                    .iter()
+                    .filter(|(other, _)| other != index)
+                    .map(|(_, dirs)| dirs)
+                    .collect();
+                let depth = (1..=dirs.len())
+                    .find(|&depth| {
+                        let own = suffix(dirs, depth);
+                        others.iter().all(|other| suffix(other, depth) != own)
+                    })
+                    .unwrap_or(dirs.len());
+                self.buffers[*index].name_suffix = Some(suffix(dirs, depth.max(1)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::state::{BufferState, EditorState};
+    use std::path::PathBuf;
+
+    fn open(editor: &mut EditorState, path: &str) {
+        let mut buf = BufferState::new();
+        buf.set_file_path(PathBuf::from(path));
+        editor.buffers.push(buf);
+        editor.switch_to_index(editor.buffers.len() - 1);
+    }
+
+    #[test]
+    fn same_named_files_get_minimal_directory_suffixes() {
+        let mut editor = EditorState::new();
+        open(&mut editor, "/src/x/a/README.md");
+        assert_eq!(editor.current().name(), "README.md");
+
+        open(&mut editor, "/src/y/a/README.md");
+        open(&mut editor, "/src/b/README.md");
+        let names = editor.buffer_names();
+        assert_eq!(
+            names[1..],
+            ["README.md<x/a>", "README.md<y/a>", "README.md<b>"]
+        );
+
+        let id = editor.buffers[2].id;
+        editor.kill_buffer(Some(id), false).unwrap();
+        assert_eq!(editor.buffer_names()[1..], ["README.md<a>", "README.md<b>"]);
+    }
+}
@@ -0,0 +1,251 @@
+use serde::Serialize;
+
+use crate::editor::buffer::TextEdit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SplitDirection {
+    /// Windows stacked top to bottom (C-x 2).
+    Vertical,
+    /// Windows side by side (C-x 3).
+    Horizontal,
+}
+
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub id: usize,
+    pub buffer: usize,
+    /// Point of this window. For the selected window the live value is the buffer's cursor.
+    pub point: usize,
+    /// First visible line (0-based).
+    pub scroll_line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum WindowNode {
+    Leaf {
+        window: usize,
+    },
+    Split {
+        direction: SplitDirection,
+        children: Vec<WindowNode>,
+    },
+}
+
+impl WindowNode {
+    fn leaves(&self, out: &mut Vec<usize>) {
+        match self {
+            WindowNode::Leaf { window } => out.push(*window),
+            WindowNode::Split { children, .. } => {
+                for child in children {
+                    child.leaves(out);
+                }
+            }
+        }
+    }
+
+    fn split_leaf(&mut self, target: usize, new_window: usize, direction: SplitDirection) -> bool {
+        match self {
+            WindowNode::Leaf { window } if *window == target => {
+                *self = WindowNode::Split {
+                    direction,
+                    children: vec![
+                        WindowNode::Leaf { window: target },
+                        WindowNode::Leaf { window: new_window },
+                    ],
+                };
+                true
+            }
+            WindowNode::Leaf { .. } => false,
+            WindowNode::Split {
+                direction: own,
+                children,
+            } => {
+                let own = *own;
+                for i in 0..children.len() {
+                    let is_target = children[i] == WindowNode::Leaf { window: target };
+                    if is_target && own == direction {
+                        children.insert(i + 1, WindowNode::Leaf { window: new_window });
+                        return true;
+                    }
+                    if children[i].split_leaf(target, new_window, direction) {
+                        return true;
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// Removes the leaf for `target`, collapsing splits left with a single child.
+    fn remove_leaf(&mut self, target: usize) -> bool {
+        let WindowNode::Split { children, .. } = self else {
+            return false;
+        };
+        let before = children.len();
+        children.retain(|child| *child != WindowNode::Leaf { window: target });
+        let removed = children.len() != before
+            || children.iter_mut().any(|child| child.remove_leaf(target));
+        if children.len() == 1 {
+            *self = children.remove(0);
+        }
+        removed
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowLayout {
+    pub windows: Vec<Window>,
+    pub root: WindowNode,
+    pub selected: usize,
+    next_id: usize,
+}
+
+impl WindowLayout {
+    pub fn new(buffer: usize) -> Self {
+        Self {
+            windows: vec![Window {
+                id: 0,
+                buffer,
+                point: 0,
+                scroll_line: 0,
+            }],
+            root: WindowNode::Leaf { window: 0 },
+            selected: 0,
+            next_id: 1,
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Window> {
+        self.windows.iter().find(|w| w.id == id)
+    }
+
+    pub fn selected(&self) -> &Window {
+        self.get(self.selected)
+            .expect("selected window must exist")
+    }
+
+    pub fn selected_mut(&mut self) -> &mut Window {
+        let id = self.selected;
+        self.windows
+            .iter_mut()
+            .find(|w| w.id == id)
+            .expect("selected window must exist")
+    }
+
+    /// Window ids in display order (top-left to bottom-right).
+    pub fn order(&self) -> Vec<usize> {
+        let mut out = Vec::new();
+        self.root.leaves(&mut out);
+        out
+    }
+
+    /// Splits the selected window; the new window shows the same buffer and is not selected.
+    pub fn split(&mut self, direction: SplitDirection) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut window = self.selected().clone();
+        window.id = id;
+        self.windows.push(window);
+        self.root.split_leaf(self.selected, id, direction);
+        id
+    }
+
+    /// The window after the selected one, cycling in display order.
+    pub fn next_window(&self, count: isize) -> usize {
+        let order = self.order();
+        let pos = order.iter().position(|id| *id == self.selected).unwrap_or(0) as isize;
+        let len = order.len() as isize;
+        order[(pos + count).rem_euclid(len) as usize]
+    }
+
+    pub fn delete(&mut self, id: usize) -> Result<(), String> {
+        if self.windows.len() <= 1 {
+            return Err("Attempt to delete minibuffer or sole ordinary window".to_string());
+        }
+        let next = self.next_window(1);
+        self.root.remove_leaf(id);
+        self.windows.retain(|w| w.id != id);
+        if self.selected == id {
+            self.selected = next;
+        }
+        Ok(())
+    }
+
+    pub fn delete_others(&mut self) {
+        let id = self.selected;
+        self.windows.retain(|w| w.id == id);
+        self.root = WindowNode::Leaf { window: id };
+    }
+
+    /// Moves the points of unselected windows showing `buffer` over `edits`, like markers.
+    pub fn follow_text_edits(&mut self, buffer: usize, edits: &[TextEdit]) {
+        let selected = self.selected;
+        for window in &mut self.windows {
+            if window.buffer == buffer && window.id != selected {
+                window.point = edits
+                    .iter()
+                    .fold(window.point, |point, edit| edit.adjust(point, false));
+            }
+        }
+    }
+
+    /// Fixes buffer indices after the buffer at `removed` was deleted.
+    /// Windows that showed it switch to `fallback` (already adjusted).
+    pub fn buffer_removed(&mut self, removed: usize, fallback: usize) {
+        for window in &mut self.windows {
+            if window.buffer == removed {
+                window.buffer = fallback;
+                window.point = 0;
+                window.scroll_line = 0;
+            } else if window.buffer > removed {
+                window.buffer -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SplitDirection, WindowLayout, WindowNode};
+
+    #[test]
+    fn splits_cycle_and_collapse() {
+        let mut layout = WindowLayout::new(0);
+        let below = layout.split(SplitDirection::Vertical);
+        let right = layout.split(SplitDirection::Horizontal);
+        assert_eq!(layout.order(), vec![0, right, below]);
+        assert_eq!(layout.next_window(1), right);
+        assert_eq!(layout.next_window(-1), below);
+
+        layout.delete(0).unwrap();
+        assert_eq!(layout.selected, right);
+        assert_eq!(
+            layout.root,
+            WindowNode::Split {
+                direction: SplitDirection::Vertical,
+                children: vec![
+                    WindowNode::Leaf { window: right },
+                    WindowNode::Leaf { window: below },
+                ],
+            }
+        );
+
+        layout.delete_others();
+        assert_eq!(layout.order(), vec![right]);
+        assert!(layout.delete(right).is_err());
+    }
+
+    #[test]
+    fn same_direction_split_adds_a_sibling() {
+        let mut layout = WindowLayout::new(0);
+        let a = layout.split(SplitDirection::Vertical);
+        let b = layout.split(SplitDirection::Vertical);
+        assert_eq!(layout.order(), vec![0, b, a]);
+        let WindowNode::Split { children, .. } = &layout.root else {
+            panic!("expected split");
+        };
+        assert_eq!(children.len(), 3);
+    }
+}
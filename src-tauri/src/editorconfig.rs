@@ -0,0 +1,226 @@
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::editor::file_settings::FileSettings;
+
+pub const EDITORCONFIG_FILE_NAME: &str = ".editorconfig";
+
+struct Section {
+    pattern: String,
+    properties: Vec<(String, String)>,
+}
+
+struct EditorConfigFile {
+    directory: PathBuf,
+    root: bool,
+    sections: Vec<Section>,
+}
+
+fn parse(directory: &Path, content: &str) -> EditorConfigFile {
+    let mut file = EditorConfigFile {
+        directory: directory.to_path_buf(),
+        root: false,
+        sections: Vec::new(),
+    };
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(pattern) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            file.sections.push(Section {
+                pattern: pattern.to_string(),
+                properties: Vec::new(),
+            });
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim().to_ascii_lowercase(), value.trim().to_string());
+        match file.sections.last_mut() {
+            Some(section) => section.properties.push((key, value)),
+            None if key == "root" => file.root = value.eq_ignore_ascii_case("true"),
+            None => {}
+        }
+    }
+    file
+}
+
+/// A compiled section pattern. Each `{n..m}` is a capture group in `regex`
+/// whose number must fall in the matching entry of `ranges`.
+struct Glob {
+    regex: Regex,
+    ranges: Vec<(i64, i64)>,
+}
+
+impl Glob {
+    fn is_match(&self, path: &str) -> bool {
+        let Some(captures) = self.regex.captures(path) else {
+            return false;
+        };
+        self.ranges.iter().enumerate().all(|(i, (low, high))| {
+            captures
+                .get(i + 1)
+                .is_none_or(|number| number.as_str().parse::<i64>().is_ok_and(|n| (*low..=*high).contains(&n)))
+        })
+    }
+}
+
+/// Translates an EditorConfig glob into an anchored regex over `/`-separated
+/// paths relative to the `.editorconfig` directory. A pattern without `/`
+/// matches a file name in any subdirectory; one with `/` is anchored there.
+fn glob_regex(pattern: &str) -> Option<Glob> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex = String::from("^");
+    if !pattern.contains('/') {
+        regex.push_str("(?:.*/)?");
+    }
+
+    let mut i = usize::from(chars.first() == Some(&'/'));
+    let mut braces = 0;
+    let mut ranges = Vec::new();
+    while i < chars.len() {
+        let ch = chars[i];
+        match ch {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                regex.push_str(".*");
+                i += 1;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let close = chars[i + 1..].iter().position(|c| *c == ']')? + i + 1;
+                let mut class: String = chars[i + 1..close].iter().collect();
+                if let Some(rest) = class.strip_prefix('!') {
+                    class = format!("^{}", rest);
+                }
+                regex.push('[');
+                regex.push_str(&class.replace('\\', "\\\\"));
+                regex.push(']');
+                i = close;
+            }
+            '{' => {
+                let close = chars[i + 1..].iter().position(|c| *c == '}').map(|p| p + i + 1);
+                let body: Option<String> = close.map(|close| chars[i + 1..close].iter().collect());
+                // `{1..10}` matches a number, checked against the range in `Glob::is_match`.
+                let range = body.as_deref().and_then(|body| {
+                    let (low, high) = body.split_once("..")?;
+                    Some((low.parse::<i64>().ok()?, high.parse::<i64>().ok()?))
+                });
+                if let (Some((low, high)), Some(close)) = (range, close) {
+                    regex.push_str("([+-]?[0-9]+)");
+                    ranges.push((low.min(high), low.max(high)));
+                    i = close;
+                } else if body.is_some_and(|body| body.contains(',')) {
+                    regex.push_str("(?:");
+                    braces += 1;
+                } else {
+                    regex.push_str("\\{");
+                }
+            }
+            ',' if braces > 0 => regex.push('|'),
+            '}' if braces > 0 => {
+                regex.push(')');
+                braces -= 1;
+            }
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            _ => regex.push_str(&regex::escape(&ch.to_string())),
+        }
+        i += 1;
+    }
+    regex.push('$');
+    let regex = Regex::new(&regex).ok()?;
+    Some(Glob { regex, ranges })
+}
+
+impl EditorConfigFile {
+    fn apply(&self, path: &Path, settings: &mut FileSettings) {
+        let Ok(relative) = path.strip_prefix(&self.directory) else {
+            return;
+        };
+        let relative = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        for section in &self.sections {
+            if glob_regex(&section.pattern).is_some_and(|regex| regex.is_match(&relative)) {
+                for (key, value) in &section.properties {
+                    settings.set_editorconfig(key, value);
+                }
+            }
+        }
+    }
+}
+
+/// EditorConfig properties for `path`, read from `.editorconfig` files in its
+/// directory and each parent up to one declaring `root = true`. Nearer files
+/// and later sections win. Unreadable files are skipped.
+pub fn resolve(path: &Path) -> FileSettings {
+    let mut files = Vec::new();
+    for directory in path.ancestors().skip(1) {
+        let Ok(content) = std::fs::read_to_string(directory.join(EDITORCONFIG_FILE_NAME)) else {
+            continue;
+        };
+        let file = parse(directory, &content);
+        let root = file.root;
+        files.push(file);
+        if root {
+            break;
+        }
+    }
+
+    let mut settings = FileSettings::default();
+    for file in files.iter().rev() {
+        file.apply(path, &mut settings);
+    }
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve;
+    use crate::editor::file_settings::{Charset, IndentStyle};
+
+    #[test]
+    fn nearer_files_override_parents_up_to_root() {
+        let base = std::env::temp_dir().join(format!("femto-editorconfig-{}", std::process::id()));
+        let nested = base.join("project").join("src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(base.join(".editorconfig"), "[*]\ncharset = latin1\n").unwrap();
+        std::fs::write(
+            base.join("project").join(".editorconfig"),
+            "root = true\n[*]\nindent_style = tab\nend_of_line = lf\n\n[*.{md,txt}]\ntrim_trailing_whitespace = true\n\n[src/**.rs]\nindent_style = space\nindent_size = 4\n",
+        )
+        .unwrap();
+
+        let rust = resolve(&nested.join("main.rs"));
+        assert_eq!(rust.indent_style, Some(IndentStyle::Space));
+        assert_eq!(rust.indent_size, Some(4));
+        assert_eq!(rust.line_ending, Some("LF"));
+        assert_eq!(rust.charset, None);
+
+        std::fs::write(
+            nested.join(".editorconfig"),
+            "[*.{0..999999999}]\nindent_size = 8\n[log{2..4}]\nindent_size = 3\ncharset: latin1\n",
+        )
+        .unwrap();
+        assert_eq!(resolve(&nested.join("part.1234")).indent_size, Some(8));
+        let log = resolve(&nested.join("log3"));
+        assert_eq!((log.indent_size, log.charset), (Some(3), None));
+        assert_eq!(resolve(&nested.join("log5")).indent_size, None);
+        assert_eq!(resolve(&nested.join("part.-1")).indent_size, None);
+
+        let notes = resolve(&nested.join("notes.md"));
+        assert_eq!(notes.indent_style, Some(IndentStyle::Tab));
+        assert_eq!(notes.trim_trailing_whitespace, Some(true));
+
+        assert_eq!(resolve(&base.join("other.txt")).charset, Some(Charset::Latin1));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::commands::decode_content;
+use crate::editor::grep::{find_matches_in_text, GrepResults};
+
+/// Bytes inspected when deciding whether a file is binary.
+const BINARY_SNIFF_LEN: usize = 8000;
+/// Emit a progress event every this many files.
+const PROGRESS_INTERVAL: usize = 25;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrepProgress {
+    pub files_scanned: usize,
+    pub match_count: usize,
+    pub current_file: Option<String>,
+    pub done: bool,
+}
+
+/// Cancellation token of the grep currently running, if any.
+#[derive(Debug, Default)]
+pub struct GrepState {
+    active: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl GrepState {
+    /// Registers a new run, cancelling the previous one.
+    pub fn begin(&self) -> Arc<AtomicBool> {
+        let token = Arc::new(AtomicBool::new(false));
+        if let Ok(mut active) = self.active.lock() {
+            if let Some(previous) = active.replace(token.clone()) {
+                previous.store(true, Ordering::Relaxed);
+            }
+        }
+        token
+    }
+
+    pub fn cancel(&self) -> bool {
+        let Ok(active) = self.active.lock() else {
+            return false;
+        };
+        match active.as_ref() {
+            Some(token) => {
+                token.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, token: &Arc<AtomicBool>) {
+        if let Ok(mut active) = self.active.lock() {
+            if active.as_ref().is_some_and(|current| Arc::ptr_eq(current, token)) {
+                *active = None;
+            }
+        }
+    }
+}
+
+/// Walks `root` honoring .gitignore and hidden-file rules, skipping binary files.
+/// `files` narrows the search to matching file names (see `file_filter`); the walk stops
+/// early once `cancel` is set, and the caller should then discard the partial results.
+pub fn grep_directory(
+    root: &Path,
+    pattern: &str,
+    regex: &Regex,
+    files: &Override,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(&GrepProgress),
+) -> GrepResults {
+    let mut results = GrepResults {
+        root: root.to_path_buf(),
+        pattern: pattern.to_string(),
+        matches: Vec::new(),
+        files_scanned: 0,
+    };
+
+    let walk = WalkBuilder::new(root).overrides(files.clone()).build();
+    for entry in walk.flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+            continue;
+        }
+
+        let path = entry.path();
+        let Ok(bytes) = std::fs::read(path) else {
+            continue;
+        };
+        results.files_scanned += 1;
+        if !is_binary(&bytes) {
+            let decoded = decode_content(&bytes);
+            results
+                .matches
+                .extend(find_matches_in_text(path, &decoded.text, regex));
+        }
+
+        if results.files_scanned.is_multiple_of(PROGRESS_INTERVAL) {
+            on_progress(&GrepProgress {
+                files_scanned: results.files_scanned,
+                match_count: results.matches.len(),
+                current_file: Some(path.to_string_lossy().to_string()),
+                done: false,
+            });
+        }
+    }
+
+    on_progress(&GrepProgress {
+        files_scanned: results.files_scanned,
+        match_count: results.matches.len(),
+        current_file: None,
+        done: true,
+    });
+    results
+}
+
+/// File name filter for `rgrep`: whitespace-separated wildcards such as `*.rs *.toml`.
+/// Without wildcards every file is searched.
+pub fn file_filter(root: &Path, wildcards: Option<&str>) -> Result<Override, String> {
+    let mut builder = OverrideBuilder::new(root);
+    for glob in wildcards.unwrap_or_default().split_whitespace() {
+        builder
+            .add(glob)
+            .map_err(|err| format!("invalid file wildcard `{glob}`: {err}"))?;
+    }
+    builder
+        .build()
+        .map_err(|err| format!("invalid file wildcards: {err}"))
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
@@ -1,13 +1,35 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod commands;
+mod completion;
+mod config;
+mod config_watch;
+mod dired;
 mod editor;
+mod editorconfig;
+mod grep;
+mod recentf;
+mod theme;
 
 use std::sync::Mutex;
 
+use config_watch::ConfigWatch;
 use editor::state::EditorState;
+use grep::GrepState;
 use tauri::Emitter;
 
+fn initial_state() -> EditorState {
+    let mut state = EditorState::new();
+    if let Some(path) = commands::recentf_path() {
+        state.recent_files = recentf::load(&path);
+        state.recent_files.truncate(state.recent_files_max);
+    }
+    if let Some(path) = commands::kmacro_path() {
+        state.kmacro.named = editor::kmacro::load(&path);
+    }
+    state
+}
+
 fn main() {
     tauri::Builder::default()
         .setup(|app| {
@@ -27,23 +49,58 @@ fn main() {
             for path_str in &paths {
                 let _ = app.emit("open-file", path_str.clone());
             }
+            config_watch::spawn(app.handle().clone());
             Ok(())
         })
-        .manage(Mutex::new(EditorState::new()))
+        .on_window_event(|window, event| {
+            // Closing the window goes through the same save-some check as C-x C-c,
+            // which exits via `quit_editor` once the user has decided.
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                api.prevent_close();
+                let _ = window.emit("quit-requested", ());
+            }
+        })
+        .manage(Mutex::new(initial_state()))
+        .manage(GrepState::default())
+        .manage(ConfigWatch::default())
         .invoke_handler(tauri::generate_handler![
             commands::initialize_editor,
             commands::editor_command,
+            commands::describe_command,
+            commands::list_commands,
+            commands::extended_command_candidates,
+            commands::execute_extended_command,
+            commands::kmacro_command,
+            commands::kmacro_names,
             commands::start_query_replace,
             commands::query_replace_step,
+            commands::start_project_query_replace,
+            commands::project_query_replace_step,
             commands::open_file,
+            commands::dired_command,
+            commands::ibuffer_command,
+            commands::save_some_buffers,
+            commands::save_some_buffers_step,
+            commands::quit_editor,
+            commands::grep,
+            commands::rgrep,
+            commands::cancel_grep,
+            commands::grep_goto,
             commands::file_exists,
             commands::default_save_directory,
             commands::path_completions,
+            commands::recent_files,
+            commands::recentf_cleanup,
             commands::load_app_config,
+            commands::describe_config,
+            commands::list_themes,
+            commands::load_theme,
             commands::save_file,
             commands::save_file_as,
+            commands::save_buffers,
             commands::switch_buffer,
             commands::list_buffers,
+            commands::highlight_lines,
             commands::kill_buffer
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+pub const RECENTF_FILE_NAME: &str = "recentf.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RecentfFile {
+    files: Vec<String>,
+}
+
+/// Reads the persisted list. A missing or unreadable file yields an empty list.
+pub fn load(path: &Path) -> Vec<PathBuf> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    serde_json::from_str::<RecentfFile>(&content)
+        .map(|file| file.files.into_iter().map(PathBuf::from).collect())
+        .unwrap_or_default()
+}
+
+pub fn save(path: &Path, files: &[PathBuf]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+    }
+    let file = RecentfFile {
+        files: files
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect(),
+    };
+    let json = serde_json::to_string_pretty(&file)
+        .map_err(|err| format!("failed to serialize recent files: {err}"))?;
+    std::fs::write(path, json)
+        .map_err(|err| format!("failed to write {}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, save};
+    use std::path::PathBuf;
+
+    #[test]
+    fn round_trips_through_disk() {
+        let path = std::env::temp_dir()
+            .join(format!("femto-recentf-{}", std::process::id()))
+            .join("recentf.json");
+        let files = vec![PathBuf::from("/a/one.md"), PathBuf::from("/b/two.txt")];
+        save(&path, &files).expect("save should succeed");
+        assert_eq!(load(&path), files);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+        assert!(load(&path).is_empty());
+    }
+}
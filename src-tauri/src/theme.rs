@@ -0,0 +1,410 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value as YamlValue;
+
+use crate::config::{resolve_config_dir, ConfigSource};
+
+/// Directory under the config directory holding user themes, one `<name>.yaml` each.
+pub const THEMES_DIR_NAME: &str = "themes";
+
+/// Syntax token classes a theme can color under `tokens:`; `link_text`
+/// sets the `--tok-link-text` CSS variable.
+pub const TOKEN_NAMES: &[&str] = &[
+    "heading", "quote", "list", "code", "strong", "em", "link_text", "link_url", "hr", "keyword",
+    "type", "function", "macro", "string", "number", "comment", "constant", "attribute",
+    "variable", "key", "label",
+];
+
+/// Themes that ship with the editor. A user theme of the same name replaces one.
+const BUILTIN_THEMES: &[(&str, &str)] = &[
+    (
+        "light",
+        r##"
+background_color: "#ffffff"
+text_color: "#000000"
+cursor_color: "#800080"
+selection_bg: "#264f78"
+current_line_bg: "#7dcfff"
+statusbar_bg: "#f5f5f5"
+tokens:
+  heading: "#a31515"
+  quote: "#0451a5"
+  list: "#0451a5"
+  code: "#098658"
+  strong: "#0000ff"
+  em: "#000000"
+  link_text: "#0000ff"
+  link_url: "#a31515"
+  hr: "#565f89"
+  keyword: "#0000ff"
+  type: "#267f99"
+  function: "#795e26"
+  macro: "#af00db"
+  string: "#a31515"
+  number: "#098658"
+  comment: "#008000"
+  constant: "#0070c1"
+  attribute: "#808080"
+  variable: "#001080"
+  key: "#0451a5"
+  label: "#af00db"
+"##,
+    ),
+    (
+        "dark",
+        r##"
+background_color: "#1e1e1e"
+text_color: "#d4d4d4"
+cursor_color: "#528bff"
+selection_bg: "#264f78"
+current_line_bg: "#3a3d41"
+statusbar_bg: "#2d2d30"
+tokens:
+  heading: "#569cd6"
+  quote: "#6a9955"
+  list: "#6796e6"
+  code: "#ce9178"
+  strong: "#569cd6"
+  em: "#d4d4d4"
+  link_text: "#4ec9b0"
+  link_url: "#ce9178"
+  hr: "#808080"
+  keyword: "#569cd6"
+  type: "#4ec9b0"
+  function: "#dcdcaa"
+  macro: "#c586c0"
+  string: "#ce9178"
+  number: "#b5cea8"
+  comment: "#6a9955"
+  constant: "#4fc1ff"
+  attribute: "#9cdcfe"
+  variable: "#9cdcfe"
+  key: "#9cdcfe"
+  label: "#c586c0"
+"##,
+    ),
+    (
+        "tokyo-night",
+        r##"
+extends: dark
+background_color: "#1a1b26"
+text_color: "#c0caf5"
+cursor_color: "#c0caf5"
+selection_bg: "#33467c"
+current_line_bg: "#292e42"
+statusbar_bg: "#16161e"
+tokens:
+  heading: "#7aa2f7"
+  quote: "#9ece6a"
+  list: "#7dcfff"
+  code: "#9ece6a"
+  strong: "#ff9e64"
+  em: "#c0caf5"
+  link_text: "#7aa2f7"
+  link_url: "#73daca"
+  hr: "#565f89"
+  keyword: "#bb9af7"
+  type: "#2ac3de"
+  function: "#7aa2f7"
+  macro: "#bb9af7"
+  string: "#9ece6a"
+  number: "#ff9e64"
+  comment: "#565f89"
+  constant: "#ff9e64"
+  attribute: "#73daca"
+  variable: "#c0caf5"
+  key: "#7dcfff"
+  label: "#e0af68"
+"##,
+    ),
+    (
+        "solarized-dark",
+        r##"
+background_color: "#002b36"
+text_color: "#839496"
+cursor_color: "#93a1a1"
+selection_bg: "#073642"
+current_line_bg: "#073642"
+statusbar_bg: "#073642"
+tokens:
+  heading: "#cb4b16"
+  quote: "#2aa198"
+  list: "#268bd2"
+  code: "#2aa198"
+  strong: "#b58900"
+  em: "#6c71c4"
+  link_text: "#268bd2"
+  link_url: "#2aa198"
+  hr: "#586e75"
+  keyword: "#859900"
+  type: "#b58900"
+  function: "#268bd2"
+  macro: "#cb4b16"
+  string: "#2aa198"
+  number: "#d33682"
+  comment: "#586e75"
+  constant: "#6c71c4"
+  attribute: "#b58900"
+  variable: "#268bd2"
+  key: "#268bd2"
+  label: "#d33682"
+"##,
+    ),
+    (
+        "solarized-light",
+        r##"
+extends: solarized-dark
+background_color: "#fdf6e3"
+text_color: "#657b83"
+cursor_color: "#586e75"
+selection_bg: "#93a1a1"
+current_line_bg: "#eee8d5"
+statusbar_bg: "#eee8d5"
+tokens:
+  comment: "#93a1a1"
+  hr: "#93a1a1"
+"##,
+    ),
+];
+
+/// A theme as written in YAML, in a theme file or in config.yaml's `theme:`.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct RawThemeConfig {
+    /// Named theme whose colors this one starts from.
+    pub extends: Option<String>,
+    pub background_color: Option<String>,
+    pub text_color: Option<String>,
+    pub cursor_color: Option<String>,
+    pub selection_bg: Option<String>,
+    pub current_line_bg: Option<String>,
+    pub current_line_highlight: Option<bool>,
+    pub statusbar_bg: Option<String>,
+    pub minibuffer_bg: Option<String>,
+    pub background_image: Option<String>,
+    pub font_family: Option<String>,
+    /// Colors of syntax token classes, keyed by `TOKEN_NAMES`.
+    pub tokens: Option<BTreeMap<String, String>>,
+    /// Any other key, reported as unknown by `check`.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, YamlValue>,
+}
+
+impl RawThemeConfig {
+    /// Values set in `over` replace the ones here; tokens merge one by one.
+    pub fn overlay(&mut self, over: RawThemeConfig) {
+        fn set<T>(field: &mut Option<T>, value: Option<T>) {
+            if value.is_some() {
+                *field = value;
+            }
+        }
+        set(&mut self.extends, over.extends);
+        set(&mut self.background_color, over.background_color);
+        set(&mut self.text_color, over.text_color);
+        set(&mut self.cursor_color, over.cursor_color);
+        set(&mut self.selection_bg, over.selection_bg);
+        set(&mut self.current_line_bg, over.current_line_bg);
+        set(&mut self.current_line_highlight, over.current_line_highlight);
+        set(&mut self.statusbar_bg, over.statusbar_bg);
+        set(&mut self.minibuffer_bg, over.minibuffer_bg);
+        set(&mut self.background_image, over.background_image);
+        set(&mut self.font_family, over.font_family);
+        if let Some(tokens) = over.tokens {
+            self.tokens.get_or_insert_with(BTreeMap::new).extend(tokens);
+        }
+    }
+
+    /// Rejects unknown keys and token names; `path` is where the theme sits in the YAML.
+    pub fn check(&self, source: &ConfigSource, path: &[&str]) -> Result<(), String> {
+        if let Some(key) = self.unknown.keys().next() {
+            let mut key_path = path.to_vec();
+            key_path.push(key.as_str());
+            return Err(source.error_at(&key_path, format!("unknown theme key `{}`", key)));
+        }
+        let Some(name) = self
+            .tokens
+            .iter()
+            .flat_map(BTreeMap::keys)
+            .find(|name| !TOKEN_NAMES.contains(&name.as_str()))
+        else {
+            return Ok(());
+        };
+        let mut key_path = path.to_vec();
+        key_path.extend(["tokens", name.as_str()]);
+        Err(source.error_at(&key_path, format!("unknown token `{}`", name)))
+    }
+}
+
+/// A resolved theme as sent to the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemeConfig {
+    /// Named theme this one is, or is based on.
+    pub name: Option<String>,
+    pub background_color: Option<String>,
+    pub text_color: Option<String>,
+    pub cursor_color: Option<String>,
+    pub selection_bg: Option<String>,
+    pub current_line_bg: Option<String>,
+    pub current_line_highlight: Option<bool>,
+    pub statusbar_bg: Option<String>,
+    pub minibuffer_bg: Option<String>,
+    pub background_image: Option<String>,
+    pub font_family: Option<String>,
+    pub tokens: BTreeMap<String, String>,
+}
+
+impl ThemeConfig {
+    fn new(name: Option<String>, theme: RawThemeConfig) -> Self {
+        Self {
+            name,
+            background_color: theme.background_color,
+            text_color: theme.text_color,
+            cursor_color: theme.cursor_color,
+            selection_bg: theme.selection_bg,
+            current_line_bg: theme.current_line_bg,
+            current_line_highlight: theme.current_line_highlight,
+            statusbar_bg: theme.statusbar_bg,
+            minibuffer_bg: theme.minibuffer_bg,
+            background_image: theme.background_image,
+            font_family: theme.font_family,
+            tokens: theme.tokens.unwrap_or_default(),
+        }
+    }
+}
+
+/// An entry of `list_themes`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemeInfo {
+    pub name: String,
+    /// Theme file, or `None` for a built-in theme.
+    pub path: Option<String>,
+}
+
+pub fn themes_dir() -> Option<PathBuf> {
+    resolve_config_dir().map(|dir| dir.join(THEMES_DIR_NAME))
+}
+
+/// Built-in themes and the `*.yaml` files in `dir`, sorted by name.
+pub fn list_themes(dir: Option<&Path>) -> Vec<ThemeInfo> {
+    let mut themes: BTreeMap<String, Option<String>> = BUILTIN_THEMES
+        .iter()
+        .map(|(name, _)| (name.to_string(), None))
+        .collect();
+    let entries = dir.and_then(|dir| std::fs::read_dir(dir).ok());
+    for path in entries.into_iter().flatten().flatten().map(|entry| entry.path()) {
+        let is_yaml = path.extension().is_some_and(|ext| ext == "yaml");
+        if let (true, Some(stem)) = (is_yaml, path.file_stem()) {
+            themes.insert(stem.to_string_lossy().to_string(), Some(path.to_string_lossy().to_string()));
+        }
+    }
+    themes.into_iter().map(|(name, path)| ThemeInfo { name, path }).collect()
+}
+
+/// Resolves the theme called `name`, following `extends:` through user
+/// themes in `dir` and the built-in ones.
+pub fn load_theme(name: &str, dir: Option<&Path>) -> Result<ThemeConfig, String> {
+    let theme = resolve(name, dir, &mut Vec::new())?;
+    Ok(ThemeConfig::new(Some(name.to_string()), theme))
+}
+
+/// Resolves config.yaml's merged `theme:` section on top of the theme it extends.
+pub fn resolve_config_theme(theme: RawThemeConfig, dir: Option<&Path>) -> Result<ThemeConfig, String> {
+    let name = theme.extends.clone();
+    let mut resolved = match &name {
+        Some(name) => resolve(name, dir, &mut Vec::new())?,
+        None => RawThemeConfig::default(),
+    };
+    resolved.overlay(theme);
+    Ok(ThemeConfig::new(name, resolved))
+}
+
+/// Loads `name` and its ancestors. `chain` holds the origins being resolved
+/// to catch cycles.
+fn resolve(name: &str, dir: Option<&Path>, chain: &mut Vec<String>) -> Result<RawThemeConfig, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("invalid theme name `{}`", name));
+    }
+    // A user theme extending its own name means the built-in one.
+    let user = dir
+        .map(|dir| dir.join(format!("{}.yaml", name)))
+        .filter(|path| path.is_file() && chain.last() != Some(&path.to_string_lossy().to_string()));
+    let (origin, text) = match user {
+        Some(path) => {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|err| format!("failed to read theme {}: {err}", path.display()))?;
+            (path.to_string_lossy().to_string(), text)
+        }
+        None => {
+            let (_, text) = BUILTIN_THEMES
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+                .ok_or_else(|| format!("unknown theme `{}`", name))?;
+            (format!("builtin:{}", name), text.to_string())
+        }
+    };
+    if chain.contains(&origin) {
+        chain.push(origin);
+        return Err(format!("theme `{}` extends itself: {}", name, chain.join(" -> ")));
+    }
+    chain.push(origin.clone());
+
+    let source = ConfigSource { origin: &origin, text: &text };
+    let theme: RawThemeConfig = serde_yaml::from_str(&text)
+        .map_err(|err| format!("failed to parse theme {}: {err}", origin))?;
+    theme.check(&source, &[])?;
+    let mut resolved = match &theme.extends {
+        Some(parent) => resolve(parent, dir, chain)?,
+        None => RawThemeConfig::default(),
+    };
+    resolved.overlay(theme);
+    resolved.extends = None;
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{list_themes, load_theme, resolve_config_theme, RawThemeConfig};
+
+    #[test]
+    fn themes_inherit_from_user_and_builtin_themes() {
+        let light = load_theme("solarized-light", None).unwrap();
+        assert_eq!(light.background_color.as_deref(), Some("#fdf6e3"));
+        assert_eq!(light.tokens["keyword"], "#859900");
+        assert_eq!(light.tokens["comment"], "#93a1a1");
+
+        let dir = std::env::temp_dir().join(format!("femto-themes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("dark.yaml"), "extends: dark\ntokens:\n  keyword: \"#ff0000\"\n").unwrap();
+        std::fs::write(dir.join("loop-a.yaml"), "extends: loop-b\n").unwrap();
+        std::fs::write(dir.join("loop-b.yaml"), "extends: loop-a\n").unwrap();
+        std::fs::write(dir.join("typo.yaml"), "tokens:\n  keyword: red\n  kewyord: blue\n").unwrap();
+        std::fs::write(dir.join("misspelled.yaml"), "extends: dark\ntext_colour: \"#ffffff\"\n").unwrap();
+
+        let dark = load_theme("dark", Some(&dir)).unwrap();
+        assert_eq!(dark.tokens["keyword"], "#ff0000");
+        assert_eq!(dark.tokens["string"], "#ce9178");
+        assert!(load_theme("loop-a", Some(&dir)).unwrap_err().contains("extends itself"));
+        let typo = load_theme("typo", Some(&dir)).unwrap_err();
+        assert!(typo.ends_with("typo.yaml:3:3: unknown token `kewyord`"), "{typo}");
+        let misspelled = load_theme("misspelled", Some(&dir)).unwrap_err();
+        assert!(misspelled.ends_with("misspelled.yaml:2:1: unknown theme key `text_colour`"), "{misspelled}");
+        assert!(load_theme("../dark", Some(&dir)).is_err());
+
+        let names: Vec<_> = list_themes(Some(&dir)).into_iter().map(|theme| theme.name).collect();
+        assert!(names.contains(&"tokyo-night".to_string()) && names.contains(&"typo".to_string()));
+
+        let config = RawThemeConfig {
+            extends: Some("tokyo-night".to_string()),
+            cursor_color: Some("red".to_string()),
+            ..RawThemeConfig::default()
+        };
+        let resolved = resolve_config_theme(config, None).unwrap();
+        assert_eq!(resolved.name.as_deref(), Some("tokyo-night"));
+        assert_eq!(resolved.cursor_color.as_deref(), Some("red"));
+        assert_eq!(resolved.background_color.as_deref(), Some("#1a1b26"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
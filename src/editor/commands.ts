@@ -15,6 +15,8 @@ export interface QueryReplaceResponse {
 }
 
 export interface ThemeConfig {
+  /** Named theme this one is, or extends. */
+  name: string | null;
   backgroundColor: string | null;
   textColor: string | null;
   cursorColor: string | null;
@@ -25,21 +27,94 @@ export interface ThemeConfig {
   minibufferBg: string | null;
   backgroundImage: string | null;
   fontFamily: string | null;
+  /** Syntax token colors keyed by token class, e.g. `keyword` or `link_text`. */
+  tokens: Record<string, string>;
+}
+
+/** A theme for `load_theme`; `path` is null for a built-in theme. */
+export interface ThemeInfo {
+  name: string;
+  path: string | null;
+}
+
+/** Effective value of a config.yaml setting; `source` is the file that set it or `default`. */
+export interface SettingView {
+  name: string;
+  doc: string;
+  value: unknown;
+  default: unknown;
+  source: string;
+}
+
+/** A `keybindings:` entry from config.yaml; `command: null` unbinds the keys. */
+export interface KeyBinding {
+  keymap: string;
+  keys: string;
+  command: string | null;
+}
+
+/** Payload of the `config-changed` event; on `error` the previous config stays in effect. */
+export interface ConfigChangedEvent {
+  config: AppConfigResponse | null;
+  themeChanged: boolean;
+  keybindingsChanged: boolean;
+  changedSettings: string[];
+  error: string | null;
 }
 
 export interface AppConfigResponse {
   theme: ThemeConfig;
-  sourcePath: string | null;
+  keybindings: KeyBinding[];
+  /** Config files applied, in order: system, user, then the project's `.femto.yaml`. */
+  sourcePaths: string[];
 }
 
 export async function initializeEditor(): Promise<EditorSnapshot> {
   return invoke<EditorSnapshot>("initialize_editor");
 }
 
+/** Raw prefix argument: a number (`M-5`), `[4]`/`[16]` for bare `C-u`, or `"-"` for `M--`. */
+export type PrefixArg = number | [number] | "-";
+
 export async function runEditorCommand(command: string, payload?: unknown): Promise<EditorSnapshot> {
   return invoke<EditorSnapshot>("editor_command", { command, payload });
 }
 
+export interface CommandArgSpec {
+  name: string;
+  kind: "string" | "natural";
+  required: boolean;
+  doc: string;
+}
+
+export interface CommandSpec {
+  name: string;
+  doc: string;
+  args: CommandArgSpec[];
+}
+
+export async function listCommands(): Promise<CommandSpec[]> {
+  return invoke<CommandSpec[]>("list_commands");
+}
+
+export async function describeCommand(name: string): Promise<CommandSpec> {
+  return invoke<CommandSpec>("describe_command", { name });
+}
+
+export interface ExtendedCommandCandidate extends CommandSpec {
+  /** Key sequences bound to the command. */
+  keys: string[];
+  recent: boolean;
+}
+
+export async function extendedCommandCandidates(query: string): Promise<ExtendedCommandCandidate[]> {
+  return invoke<ExtendedCommandCandidate[]>("extended_command_candidates", { query });
+}
+
+export async function executeExtendedCommand(command: string, payload?: unknown): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("execute_extended_command", { command, payload });
+}
+
 export async function openFile(path: string): Promise<EditorSnapshot> {
   return invoke<EditorSnapshot>("open_file", { path });
 }
@@ -68,8 +143,67 @@ export async function pathCompletions(input: string): Promise<string[]> {
   return invoke<string[]>("path_completions", { input });
 }
 
-export async function loadAppConfig(): Promise<AppConfigResponse> {
-  return invoke<AppConfigResponse>("load_app_config");
+export interface GrepProgress {
+  filesScanned: number;
+  matchCount: number;
+  currentFile: string | null;
+  done: boolean;
+}
+
+/** Without `ignoreCase`, the backend follows the `search.case_fold` setting. */
+export async function grep(directory: string, pattern: string, ignoreCase?: boolean): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("grep", { payload: { directory, pattern, ignoreCase: ignoreCase ?? null } });
+}
+
+export async function rgrep(directory: string, pattern: string, files: string): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("rgrep", { payload: { directory, pattern, ignoreCase: null, files } });
+}
+
+export async function cancelGrep(): Promise<boolean> {
+  return invoke<boolean>("cancel_grep");
+}
+
+export async function grepGoto(): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("grep_goto");
+}
+
+export async function diredCommand(command: string, target?: string): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("dired_command", {
+    command,
+    payload: target === undefined ? null : { target },
+  });
+}
+
+export async function ibufferCommand(command: string, target?: string, force = false): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("ibuffer_command", {
+    command,
+    payload: { target: target ?? null, force },
+  });
+}
+
+export async function recentFiles(existingOnly = false): Promise<string[]> {
+  return invoke<string[]>("recent_files", { existingOnly });
+}
+
+export async function recentfCleanup(): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("recentf_cleanup");
+}
+
+/** Loads the layered config; the project layer is looked up from `directory`, or the current buffer's. */
+export async function loadAppConfig(directory?: string): Promise<AppConfigResponse> {
+  return invoke<AppConfigResponse>("load_app_config", { directory: directory ?? null });
+}
+
+export async function describeConfig(): Promise<SettingView[]> {
+  return invoke<SettingView[]>("describe_config");
+}
+
+export async function listThemes(): Promise<ThemeInfo[]> {
+  return invoke<ThemeInfo[]>("list_themes");
+}
+
+export async function loadTheme(name: string): Promise<ThemeConfig> {
+  return invoke<ThemeConfig>("load_theme", { name });
 }
 
 export async function startQueryReplace(query: string, replaceWith: string): Promise<QueryReplaceResponse> {
@@ -80,24 +214,145 @@ export async function queryReplaceStep(action: "y" | "n" | "!" | "q"): Promise<Q
   return invoke<QueryReplaceResponse>("query_replace_step", { payload: { action } });
 }
 
+export interface FileReplaceCount {
+  path: string;
+  replacedCount: number;
+}
+
+export interface ProjectReplaceStatus {
+  done: boolean;
+  file: string | null;
+  replacedCount: number;
+  totalReplaced: number;
+  nextLine: number | null;
+  nextCol: number | null;
+  message: string;
+  fileCounts: FileReplaceCount[];
+  modifiedBuffers: number[];
+}
+
+export interface ProjectReplaceResponse {
+  snapshot: EditorSnapshot;
+  status: ProjectReplaceStatus;
+}
+
+export async function startProjectQueryReplace(
+  directory: string,
+  query: string,
+  replaceWith: string,
+): Promise<ProjectReplaceResponse> {
+  return invoke<ProjectReplaceResponse>("start_project_query_replace", {
+    payload: { directory, query, replaceWith },
+  });
+}
+
+export async function projectQueryReplaceStep(action: "y" | "n" | "!" | "q"): Promise<ProjectReplaceResponse> {
+  return invoke<ProjectReplaceResponse>("project_query_replace_step", { payload: { action } });
+}
+
+export async function saveBuffers(ids: number[]): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("save_buffers", { ids });
+}
+
+export interface SaveSomeStatus {
+  done: boolean;
+  bufferId: number | null;
+  file: string | null;
+  savedCount: number;
+  unsavedCount: number;
+  message: string;
+}
+
+export interface SaveSomeResponse {
+  snapshot: EditorSnapshot;
+  status: SaveSomeStatus;
+}
+
+export async function saveSomeBuffers(): Promise<SaveSomeResponse> {
+  return invoke<SaveSomeResponse>("save_some_buffers");
+}
+
+export async function saveSomeBuffersStep(action: "y" | "n" | "!" | "q"): Promise<SaveSomeResponse> {
+  return invoke<SaveSomeResponse>("save_some_buffers_step", { payload: { action } });
+}
+
+export async function quitEditor(force = false): Promise<void> {
+  return invoke<void>("quit_editor", { force });
+}
+
+export interface BufferInfo {
+  id: number;
+  name: string;
+  size: number;
+  modified: boolean;
+  encoding: string;
+  lineEnding: string;
+  filePath: string | null;
+  mode: string;
+}
+
 export interface BufferListResponse {
-  names: string[];
-  current: string;
-  defaultSwitch: string;
+  buffers: BufferInfo[];
+  current: number;
+  defaultSwitch: number;
 }
 
-export async function switchBuffer(name: string): Promise<EditorSnapshot> {
-  return invoke<EditorSnapshot>("switch_buffer", { name });
+export function bufferName(list: BufferListResponse, id: number): string {
+  return list.buffers.find((entry) => entry.id === id)?.name ?? "";
+}
+
+export function bufferIdByName(list: BufferListResponse, name: string): number | null {
+  return list.buffers.find((entry) => entry.name === name)?.id ?? null;
+}
+
+export async function switchBuffer(id: number): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("switch_buffer", { id });
 }
 
 export async function listBuffers(): Promise<BufferListResponse> {
   return invoke<BufferListResponse>("list_buffers");
 }
 
-export async function killBuffer(name?: string | null, force = false): Promise<EditorSnapshot> {
+export interface StyleSpan {
+  start: number;
+  end: number;
+  style: string;
+}
+
+export interface LineSpans {
+  line: number;
+  spans: StyleSpan[];
+}
+
+export interface HighlightResponse {
+  bufferId: number;
+  majorMode: string;
+  lines: LineSpans[];
+}
+
+export interface KmacroOptions {
+  /** Times to run the macro; 0 repeats until an error. */
+  count?: number;
+  name?: string;
+  counter?: number;
+}
+
+export async function kmacroCommand(command: string, options: KmacroOptions = {}): Promise<EditorSnapshot> {
+  return invoke<EditorSnapshot>("kmacro_command", { command, payload: options });
+}
+
+export async function kmacroNames(): Promise<string[]> {
+  return invoke<string[]>("kmacro_names");
+}
+
+export async function highlightLines(firstLine: number, endLine: number): Promise<HighlightResponse> {
+  return invoke<HighlightResponse>("highlight_lines", { firstLine, endLine });
+}
+
+export async function killBuffer(id?: number | null, force = false): Promise<EditorSnapshot> {
   return invoke<EditorSnapshot>("kill_buffer", {
     payload: {
-      name: name ?? null,
+      id: id ?? null,
       force,
     },
   });
@@ -1,7 +1,11 @@
 import { loadAppConfig } from "./commands";
+import type { AppConfigResponse, ThemeConfig } from "./commands";
+import { setConfiguredKeybindings } from "./keybindings";
 
+// Unset values fall back to the stylesheet, so a color removed on reload goes back to its default.
 function setVar(name: string, value: string | null | undefined): void {
   if (!value || value.trim().length === 0) {
+    document.documentElement.style.removeProperty(name);
     return;
   }
   document.documentElement.style.setProperty(name, value);
@@ -92,10 +96,19 @@ function normalizeBackgroundImage(value: string): string {
   return `url("${trimmed}")`;
 }
 
-export async function loadAndApplyAppConfig(): Promise<void> {
-  const config = await loadAppConfig();
-  const theme = config.theme;
+export async function loadAndApplyAppConfig(directory?: string): Promise<void> {
+  applyAppConfig(await loadAppConfig(directory));
+}
+
+export function applyAppConfig(config: AppConfigResponse): void {
+  setConfiguredKeybindings(config.keybindings);
+  applyTheme(config.theme);
+}
 
+/** Token CSS variables set by the last theme, cleared when a theme drops them. */
+let appliedTokenVars: string[] = [];
+
+export function applyTheme(theme: ThemeConfig): void {
   const bg = normalizeColor(theme.backgroundColor);
   const fg = normalizeColor(theme.textColor);
   setVar("--bg-color", bg);
@@ -112,9 +125,20 @@ export async function loadAndApplyAppConfig(): Promise<void> {
   const highlightEnabled = theme.currentLineHighlight ?? true;
   document.documentElement.setAttribute("data-current-line", highlightEnabled ? "on" : "off");
 
+  const tokenVars = Object.keys(theme.tokens).map((token) => `--tok-${token.replace(/_/g, "-")}`);
+  for (const name of appliedTokenVars) {
+    if (!tokenVars.includes(name)) {
+      setVar(name, null);
+    }
+  }
+  Object.values(theme.tokens).forEach((color, i) => setVar(tokenVars[i], normalizeColor(color)));
+  appliedTokenVars = tokenVars;
+
   if (theme.backgroundImage) {
     document.body.style.backgroundImage = normalizeBackgroundImage(theme.backgroundImage);
     document.body.style.backgroundSize = "cover";
     document.body.style.backgroundPosition = "center";
+  } else {
+    document.body.style.backgroundImage = "";
   }
 }
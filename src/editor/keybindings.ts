@@ -1,59 +1,194 @@
-import { getCurrentWindow } from "@tauri-apps/api/window";
+import { listen } from "@tauri-apps/api/event";
 import {
+  bufferIdByName,
+  bufferName,
+  cancelGrep,
   defaultSaveDirectory,
+  describeCommand,
+  diredCommand,
+  executeExtendedCommand,
+  extendedCommandCandidates,
   fileExists,
+  grep,
+  grepGoto,
+  ibufferCommand,
   killBuffer,
+  kmacroCommand,
+  kmacroNames,
   listBuffers,
+  listThemes,
+  loadTheme,
   openFile,
   pathCompletions,
+  projectQueryReplaceStep,
   queryReplaceStep,
+  quitEditor,
+  recentFiles,
+  rgrep,
   runEditorCommand,
+  saveBuffers,
   saveFile,
   saveFileAs,
   saveFileAsWithOverwrite,
+  saveSomeBuffers,
+  saveSomeBuffersStep,
+  startProjectQueryReplace,
   startQueryReplace,
   switchBuffer,
 } from "./commands";
+import { applyTheme, loadAndApplyAppConfig } from "./config";
 import { promptMinibuffer } from "./minibuffer";
-import { adjustEditorFontSize, moveCursorByVisualLine, recenterTopBottom, renderSnapshot } from "./ui";
+import { adjustEditorFontSize, currentScrollLine, moveCursorByVisualLine, recenterTopBottom, renderSnapshot } from "./ui";
+import type { CommandArgSpec, ExtendedCommandCandidate, GrepProgress, KeyBinding, PrefixArg } from "./commands";
 import type { EditorSnapshot, EditorUiContext } from "./types";
 
 interface KeyState {
   ctrlXPrefix: boolean;
+  /** Second key of a three-key C-x sequence such as C-x 4 c or C-x n n. */
+  ctrlXSubPrefix: string | null;
+  ctrlCPrefix: boolean;
 }
 
 function eventKey(e: KeyboardEvent): string {
   return e.key.length === 1 ? e.key.toLowerCase() : e.key;
 }
 
+/** Appends the path's own separator (`\` on Windows paths, `/` elsewhere) if missing. */
+function withTrailingSeparator(dir: string): string {
+  if (/[\\/]$/.test(dir)) {
+    return dir;
+  }
+  return `${dir}${dir.includes("\\") ? "\\" : "/"}`;
+}
+
 function isQueryReplaceShortcut(event: KeyboardEvent, key: string): boolean {
   const altShiftPercent = event.altKey && event.shiftKey && !event.ctrlKey && key === "%";
   const ctrlAltFive = event.ctrlKey && event.altKey && (key === "5" || key === "%");
   return altShiftPercent || ctrlAltFive;
 }
 
+/** Bindings from the `keybindings:` section of config.yaml, checked before the built-in keys. */
+let configuredBindings: KeyBinding[] = [];
+
+export function setConfiguredKeybindings(bindings: KeyBinding[]): void {
+  configuredBindings = bindings;
+}
+
+// M-x commands handled here rather than by the backend.
+const FRONTEND_COMMANDS: ExtendedCommandCandidate[] = [
+  { name: "load_theme", doc: "Switch to a named theme.", args: [], keys: [], recent: false },
+  { name: "rgrep", doc: "Grep files matching wildcards in a directory.", args: [], keys: [], recent: false },
+];
+
+// Prefixes the built-in handlers below already wait on.
+const BUILTIN_PREFIXES = ["C-x", "C-c"];
+
+const NAMED_KEYS: Record<string, string> = {
+  Tab: "TAB",
+  Enter: "RET",
+  " ": "SPC",
+  Backspace: "DEL",
+  Escape: "ESC",
+  ArrowUp: "Up",
+  ArrowDown: "Down",
+  ArrowLeft: "Left",
+  ArrowRight: "Right",
+};
+
+/** Emacs-style name of a key press, such as `C-x`, `M-<` or `TAB`; null for a bare modifier. */
+function describeKey(event: KeyboardEvent): string | null {
+  if (event.key === "Shift" || event.key === "Control" || event.key === "Alt" || event.key === "Meta") {
+    return null;
+  }
+  let base = NAMED_KEYS[event.key] ?? event.key;
+  if (base.length === 1 && event.ctrlKey) {
+    base = base.toLowerCase();
+  }
+  return `${event.ctrlKey ? "C-" : ""}${event.altKey ? "M-" : ""}${base}`;
+}
+
+/** Adds a typed digit or `-` to a prefix argument, as after `C-u` or `M-<digit>`. */
+function extendPrefixArg(current: PrefixArg | null, key: string): PrefixArg {
+  if (key === "-") {
+    return "-";
+  }
+  const digit = Number(key);
+  if (current === "-") {
+    return -digit;
+  }
+  if (typeof current === "number") {
+    return current < 0 || Object.is(current, -0) ? current * 10 - digit : current * 10 + digit;
+  }
+  return digit;
+}
+
+function prefixArgStatus(arg: PrefixArg): string {
+  if (Array.isArray(arg)) {
+    const presses = Math.max(1, Math.round(Math.log(arg[0]) / Math.log(4)));
+    return `${Array(presses).fill("C-u").join(" ")}-`;
+  }
+  return `C-u ${arg}-`;
+}
+
 export function bindEditorKeys(ctx: EditorUiContext): void {
-  const keyState: KeyState = { ctrlXPrefix: false };
+  const keyState: KeyState = { ctrlXPrefix: false, ctrlXSubPrefix: null, ctrlCPrefix: false };
   let composing = false;
   let pendingCompositionText: string | null = null;
   let currentFilePath: string | null = null;
+  let currentBufferKind = "file";
+  let currentMajorMode = "fundamental";
+  let currentDefaultDirectory: string | null = null;
+  let currentBufferId: number | null = null;
+  // Real buffer offset of the first textarea character; non-zero while narrowed.
+  let narrowStart = 0;
+  let grepRunning = false;
   let defaultDirCache: string | null = null;
   let lastSearchQuery: string | null = null;
   let lastSearchDirection: "forward" | "backward" | null = null;
   let markPosition: number | null = null;
+  let definingMacro = false;
+  // After C-x e, a plain `e` runs the macro again.
+  let macroRepeat = false;
+  // Prefix argument being typed with C-u or M-<digit>; digits and `-` extend it.
+  let prefixArg: PrefixArg | null = null;
+  let prefixArgTyping = false;
+  // Prefix argument for the command of the current key, consumed by withPrefixArg.
+  let pendingPrefixArg: PrefixArg | null = null;
+  // Keys typed so far in the current key sequence, matched against configuredBindings.
+  let typedKeys: string[] = [];
+  // True after the first key of a prefix that only config.yaml defines, such as `C-z` in `C-z C-a`.
+  let configuredPrefixPending = false;
 
   const renderAndTrack = (
     snapshot: EditorSnapshot,
     statusOverride?: string,
     preserveMarkSelection = false,
   ): void => {
+    // The mark belongs to the buffer (and narrowing) it was set in.
+    if (snapshot.bufferId !== currentBufferId || snapshot.narrowStart !== narrowStart) {
+      clearMark();
+    }
+    currentBufferId = snapshot.bufferId;
+    narrowStart = snapshot.narrowStart;
     currentFilePath = snapshot.filePath;
+    currentBufferKind = snapshot.bufferKind;
+    currentMajorMode = snapshot.majorMode;
+    definingMacro = snapshot.definingMacro;
+    if (snapshot.defaultDirectory && snapshot.defaultDirectory !== currentDefaultDirectory) {
+      // The new directory may belong to another project with its own .femto.yaml.
+      loadAndApplyAppConfig(snapshot.defaultDirectory).catch((error: unknown) => {
+        const message = error instanceof Error ? error.message : String(error);
+        ctx.status.textContent = `Config load error: ${message}`;
+      });
+    }
+    currentDefaultDirectory = snapshot.defaultDirectory;
     renderSnapshot(ctx, snapshot, statusOverride);
     if (preserveMarkSelection && markPosition !== null) {
-      if (markPosition <= snapshot.cursor) {
-        ctx.editor.setSelectionRange(markPosition, snapshot.cursor, "forward");
+      const cursor = snapshot.cursor - snapshot.narrowStart;
+      if (markPosition <= cursor) {
+        ctx.editor.setSelectionRange(markPosition, cursor, "forward");
       } else {
-        ctx.editor.setSelectionRange(snapshot.cursor, markPosition, "backward");
+        ctx.editor.setSelectionRange(cursor, markPosition, "backward");
       }
     }
   };
@@ -68,10 +203,61 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
     renderAndTrack(snapshot, prefix);
   };
 
+  /** Adds the pending prefix argument to a command payload, consuming it. */
+  const withPrefixArg = (payload?: object): object | undefined => {
+    const prefix = pendingPrefixArg;
+    pendingPrefixArg = null;
+    return prefix === null ? payload : { ...payload, prefixArg: prefix };
+  };
+
+  /** The configured binding for `sequence`; the major mode's keymap wins over the global one. */
+  const configuredBinding = (sequence: string): KeyBinding | undefined =>
+    configuredBindings.find((b) => b.keys === sequence && b.keymap === currentMajorMode) ??
+    configuredBindings.find((b) => b.keys === sequence && b.keymap === "global");
+
+  const isConfiguredPrefix = (sequence: string): boolean =>
+    configuredBindings.some(
+      (b) =>
+        b.command !== null &&
+        b.keys.startsWith(`${sequence} `) &&
+        (b.keymap === "global" || b.keymap === currentMajorMode),
+    );
+
+  /**
+   * Fills a command's arguments for M-x and configured bindings: the region from the
+   * selection, the window scroll position, and the minibuffer for anything else.
+   * Returns null when a prompt is cancelled.
+   */
+  const collectCommandArgs = async (
+    name: string,
+    args: CommandArgSpec[],
+    payload: Record<string, unknown>,
+  ): Promise<Record<string, unknown> | null> => {
+    const region = regionFromSelection();
+    for (const arg of args) {
+      if (arg.name === "scrollLine") {
+        payload.scrollLine = currentScrollLine(ctx);
+      } else if (region && (arg.name === "start" || arg.name === "end")) {
+        payload[arg.name] = region[arg.name];
+      } else if (arg.required) {
+        const value = await promptMinibuffer(ctx, `${name} ${arg.name} (${arg.doc}):`, "", {
+          trim: arg.kind !== "string",
+        });
+        if (value === null) {
+          return null;
+        }
+        payload[arg.name] = arg.kind === "natural" ? Number(value) : value;
+      }
+    }
+    return payload;
+  };
+
   const syncCursorFromDom = async (): Promise<void> => {
-    const cursor = ctx.editor.selectionEnd;
+    const cursor = ctx.editor.selectionEnd + narrowStart;
     const snapshot = await runEditorCommand("set_cursor", { cursor });
     currentFilePath = snapshot.filePath;
+    currentBufferKind = snapshot.bufferKind;
+    currentDefaultDirectory = snapshot.defaultDirectory;
   };
 
   const regionFromSelection = (): { start: number; end: number } | null => {
@@ -82,12 +268,12 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
         const regionStart = Math.min(markPosition, ctx.editor.selectionEnd);
         const regionEnd = Math.max(markPosition, ctx.editor.selectionEnd);
         if (regionStart !== regionEnd) {
-          return { start: regionStart, end: regionEnd };
+          return { start: regionStart + narrowStart, end: regionEnd + narrowStart };
         }
       }
       return null;
     }
-    return { start, end };
+    return { start: start + narrowStart, end: end + narrowStart };
   };
 
   const getDefaultWritePath = async (): Promise<string> => {
@@ -99,28 +285,72 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       defaultDirCache = await defaultSaveDirectory();
     }
 
-    return defaultDirCache.endsWith("\\") ? defaultDirCache : `${defaultDirCache}\\`;
+    return withTrailingSeparator(defaultDirCache);
   };
 
   const getDefaultFindPath = async (): Promise<string> => {
-    if (currentFilePath) {
-      const normalized = currentFilePath.replaceAll("/", "\\");
-      const lastSep = normalized.lastIndexOf("\\");
-      if (lastSep >= 0) {
-        return normalized.slice(0, lastSep + 1);
-      }
+    if (currentDefaultDirectory) {
+      return withTrailingSeparator(currentDefaultDirectory);
     }
     return getDefaultWritePath();
   };
 
-  const handleCtrlXCommand = async (key: string): Promise<boolean> => {
+  // Asks about each modified file buffer in turn (y/n/!/q), like Emacs' save-some-buffers.
+  const runSaveSomeBuffers = async (): Promise<void> => {
+    let response = await saveSomeBuffers();
+    renderAndTrack(response.snapshot);
+    while (!response.status.done) {
+      const answerRaw = window.prompt(response.status.message, "y");
+      const answer = (answerRaw ?? "q").trim().toLowerCase();
+      const action = answer === "!" ? "!" : answer === "y" || answer === "n" ? answer : "q";
+      response = await saveSomeBuffersStep(action);
+      renderAndTrack(response.snapshot);
+    }
+  };
+
+  // C-x C-c and closing the window: offer to save, then confirm before dropping changes.
+  // Returns false when the user decided to stay.
+  const saveBuffersKillEditor = async (): Promise<boolean> => {
+    await runSaveSomeBuffers();
+    try {
+      await quitEditor(false);
+    } catch (error) {
+      const message = error instanceof Error ? error.message : String(error);
+      if (!message.includes("Confirmation required")) {
+        throw error;
+      }
+      if (!window.confirm(`${message.replace(" Confirmation required.", "")}\nQuit anyway?`)) {
+        return false;
+      }
+      await quitEditor(true);
+    }
+    return true;
+  };
+
+  listen("quit-requested", () => {
+    saveBuffersKillEditor()
+      .then((quit) => {
+        if (!quit) {
+          ctx.status.textContent = "Quit cancelled";
+        }
+      })
+      .catch((error: unknown) => {
+        const message = error instanceof Error ? error.message : String(error);
+        ctx.status.textContent = `Quit error: ${message}`;
+      });
+  }).catch((error: unknown) => {
+    const message = error instanceof Error ? error.message : String(error);
+    ctx.status.textContent = `Event error: ${message}`;
+  });
+
+  const handleCtrlXCommand = async (key: string, ctrl = false): Promise<boolean> => {
     if (!keyState.ctrlXPrefix) {
       return false;
     }
 
     keyState.ctrlXPrefix = false;
 
-    if (key === "f") {
+    if (key === "f" && ctrl) {
       const path = await promptMinibuffer(ctx, "Find file:", await getDefaultFindPath(), {
         completer: pathCompletions,
       });
@@ -189,16 +419,132 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       return true;
     }
 
+    if (key === "s" && !ctrl) {
+      await runSaveSomeBuffers();
+      return true;
+    }
+
     if (key === "c") {
+      if (!(await saveBuffersKillEditor())) {
+        await renderWithPrefix();
+      }
+      return true;
+    }
+
+    if (key === "g") {
+      await grepCommand(false);
+      return true;
+    }
+
+    if (key === "%") {
+      const directory = await promptMinibuffer(ctx, "Query replace in directory:", await getDefaultFindPath(), {
+        completer: pathCompletions,
+      });
+      if (!directory) {
+        await renderWithPrefix();
+        return true;
+      }
+      const query = await promptMinibuffer(ctx, "Project query replace:", "", { trim: false });
+      if (!query) {
+        await renderWithPrefix();
+        return true;
+      }
+      const replaceWith = await promptMinibuffer(ctx, `Project query replace ${query} with:`, "", { trim: false });
+      if (replaceWith === null) {
+        await renderWithPrefix();
+        return true;
+      }
+
+      let response = await startProjectQueryReplace(directory, query, replaceWith);
+      renderAndTrack(response.snapshot);
+      while (!response.status.done) {
+        const answerRaw = window.prompt(response.status.message, "y");
+        const answer = (answerRaw ?? "q").trim().toLowerCase();
+        const action = answer === "!" ? "!" : answer === "y" || answer === "n" ? answer : "q";
+        response = await projectQueryReplaceStep(action);
+        renderAndTrack(response.snapshot);
+      }
+
+      const report = response.status.fileCounts
+        .filter((entry) => entry.replacedCount > 0)
+        .map((entry) => `${entry.path}: ${entry.replacedCount}`)
+        .join("\n");
+      const modified = response.status.modifiedBuffers;
+      if (modified.length > 0 && window.confirm(`${response.status.message}\n${report}\n\nSave ${modified.length} modified buffers?`)) {
+        renderAndTrack(await saveBuffers(modified));
+      }
+      return true;
+    }
+
+    if (key === "r") {
+      const recent = await recentFiles(true);
+      if (recent.length === 0) {
+        const snapshot = await runEditorCommand("noop");
+        renderAndTrack(snapshot, "No recent files");
+        return true;
+      }
+      const path = await promptMinibuffer(ctx, "Recent file:", "", {
+        completer: async (input: string): Promise<string[]> => {
+          const lower = input.toLowerCase();
+          return recent.filter((candidate) => candidate.toLowerCase().includes(lower));
+        },
+      });
+      if (!path) {
+        await renderWithPrefix();
+        return true;
+      }
+      renderAndTrack(await openFile(path));
+      return true;
+    }
+
+    if (key === "q" && currentBufferKind === "dired") {
+      renderAndTrack(await diredCommand("wdired_start"));
+      return true;
+    }
+
+    if (key === "q" && ctrl) {
+      renderAndTrack(await runEditorCommand("toggle_minor_mode", { mode: "read-only-mode" }));
+      return true;
+    }
+
+    if (key === "f" && !ctrl) {
       const snapshot = await runEditorCommand("noop");
-      if (snapshot.modified) {
-        const ok = window.confirm("Buffer is modified. Quit without saving?");
-        if (!ok) {
-          await renderWithPrefix();
-          return true;
-        }
+      const input = await promptMinibuffer(ctx, `Set fill-column to (currently ${snapshot.fillColumn}):`, "");
+      const column = Number.parseInt(input ?? "", 10);
+      if (Number.isNaN(column)) {
+        await renderWithPrefix();
+        return true;
       }
-      await getCurrentWindow().close();
+      renderAndTrack(await runEditorCommand("set_fill_column", { fillColumn: column }));
+      return true;
+    }
+
+    if (ctrl && (key === "t" || key === "o")) {
+      clearMark();
+      await syncCursorFromDom();
+      renderAndTrack(await runEditorCommand(key === "t" ? "transpose_lines" : "delete_blank_lines", withPrefixArg()));
+      return true;
+    }
+
+    const windowCommands: Record<string, string> = {
+      "2": "split_window_below",
+      "3": "split_window_right",
+      o: "other_window",
+      "0": "delete_window",
+      "1": "delete_other_windows",
+    };
+    if (key === "4" || key === "n") {
+      keyState.ctrlXSubPrefix = key;
+      const snapshot = await runEditorCommand("noop");
+      renderAndTrack(snapshot, `C-x ${key}-`);
+      return true;
+    }
+
+    if (key in windowCommands) {
+      clearMark();
+      await syncCursorFromDom();
+      const snapshot = await runEditorCommand(windowCommands[key], { scrollLine: currentScrollLine(ctx) });
+      renderAndTrack(snapshot);
       return true;
     }
 
@@ -210,23 +556,33 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       return true;
     }
 
+    if (key === "b" && ctrl) {
+      await syncCursorFromDom();
+      renderAndTrack(await ibufferCommand("open"));
+      return true;
+    }
+
     if (key === "b") {
       const bufInfo = await listBuffers();
-      const defaultBuf = bufInfo.defaultSwitch;
+      const defaultBuf = bufferName(bufInfo, bufInfo.defaultSwitch);
       const prompt = `Switch to buffer (default ${defaultBuf}): `;
 
       const bufferCompleter = async (input: string): Promise<string[]> => {
-        const info = await listBuffers();
-        if (!input) return info.names;
+        const names = (await listBuffers()).buffers.map((entry) => entry.name);
+        if (!input) return names;
         const lower = input.toLowerCase();
-        return info.names.filter((n) => n.toLowerCase().startsWith(lower));
+        return names.filter((n) => n.toLowerCase().startsWith(lower));
       };
 
       const chosen = await promptMinibuffer(ctx, prompt, "", {
         completer: bufferCompleter,
       });
 
-      const target = chosen ?? defaultBuf;
+      const target = chosen ? bufferIdByName(await listBuffers(), chosen) : bufInfo.defaultSwitch;
+      if (target === null) {
+        renderAndTrack(await runEditorCommand("noop"), `No buffer named ${chosen}`);
+        return true;
+      }
       if (target !== bufInfo.current) {
         const snapshot = await runEditorCommand("noop");
         if (snapshot.modified) {
@@ -246,26 +602,49 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       return true;
     }
 
+    if (key === "(" || key === ")") {
+      await syncCursorFromDom();
+      renderAndTrack(await kmacroCommand(key === "(" ? "start" : "end"));
+      return true;
+    }
+
+    if (key === "e" && !ctrl) {
+      await syncCursorFromDom();
+      renderAndTrack(await kmacroCommand("call"), "Type e to repeat macro");
+      macroRepeat = true;
+      return true;
+    }
+
+    if (key === "k" && ctrl) {
+      keyState.ctrlXSubPrefix = "C-k";
+      renderAndTrack(await runEditorCommand("noop"), "C-x C-k-");
+      return true;
+    }
+
     if (key === "k") {
       const bufInfo = await listBuffers();
-      const prompt = `Kill buffer (default ${bufInfo.current}): `;
+      const prompt = `Kill buffer (default ${bufferName(bufInfo, bufInfo.current)}): `;
       const chosen = await promptMinibuffer(ctx, prompt, "", {
         completer: async (input: string): Promise<string[]> => {
-          const info = await listBuffers();
-          if (!input) return info.names;
+          const names = (await listBuffers()).buffers.map((entry) => entry.name);
+          if (!input) return names;
           const lower = input.toLowerCase();
-          return info.names.filter((n) => n.toLowerCase().startsWith(lower));
+          return names.filter((n) => n.toLowerCase().startsWith(lower));
         },
       });
 
-      const target = chosen ?? bufInfo.current;
+      const target = chosen ? bufferIdByName(await listBuffers(), chosen) : bufInfo.current;
+      if (target === null) {
+        renderAndTrack(await runEditorCommand("noop"), `No buffer named ${chosen}`);
+        return true;
+      }
       try {
         const snapshot = await killBuffer(target, false);
         renderAndTrack(snapshot);
       } catch (error) {
         const message = error instanceof Error ? error.message : String(error);
         if (message.includes("Confirmation required")) {
-          const ok = window.confirm(`Buffer '${target}' is modified. Kill anyway?`);
+          const ok = window.confirm(`Buffer '${chosen ?? bufferName(bufInfo, target)}' is modified. Kill anyway?`);
           if (!ok) {
             await renderWithPrefix();
             return true;
@@ -283,34 +662,203 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
     return true;
   };
 
+  // Dired keys; any other printable key is swallowed so the listing is not edited by accident.
+  const handleDiredKey = async (key: string): Promise<void> => {
+    const simple: Record<string, string> = {
+      Enter: "find_file",
+      f: "find_file",
+      m: "mark",
+      u: "unmark",
+      U: "unmark_all",
+      d: "flag_delete",
+      g: "refresh",
+      "^": "up",
+    };
+
+    await syncCursorFromDom();
+    if (key === "n" || key === " ") {
+      renderAndTrack(await runEditorCommand("move_next_line"));
+      return;
+    }
+    if (key === "p") {
+      renderAndTrack(await runEditorCommand("move_previous_line"));
+      return;
+    }
+    if (simple[key]) {
+      renderAndTrack(await diredCommand(simple[key]));
+      return;
+    }
+    if (key === "x" || key === "D") {
+      const what = key === "x" ? "flagged files" : "marked files (or the file at point)";
+      if (window.confirm(`Delete ${what}?`)) {
+        renderAndTrack(await diredCommand(key === "x" ? "execute" : "delete"));
+      }
+      return;
+    }
+    if (key === "R" || key === "C" || key === "+") {
+      const prompt = key === "R" ? "Rename to:" : key === "C" ? "Copy to:" : "Create directory:";
+      const target = await promptMinibuffer(ctx, prompt, await getDefaultFindPath(), {
+        completer: pathCompletions,
+      });
+      if (target) {
+        const command = key === "R" ? "rename" : key === "C" ? "copy" : "create_directory";
+        renderAndTrack(await diredCommand(command, target));
+      } else {
+        await renderWithPrefix();
+      }
+      return;
+    }
+  };
+
+  // Buffer list keys; "/" starts a filter sequence like ibuffer's filter prefix.
+  let ibufferFilterPrefix = false;
+  const handleIbufferKey = async (key: string): Promise<void> => {
+    const simple: Record<string, string> = {
+      Enter: "visit",
+      f: "visit",
+      m: "mark",
+      u: "unmark",
+      U: "unmark_all",
+      g: "refresh",
+      S: "save_marked",
+    };
+
+    await syncCursorFromDom();
+    if (ibufferFilterPrefix) {
+      ibufferFilterPrefix = false;
+      if (key === "m") {
+        renderAndTrack(await ibufferCommand("filter_modified"));
+      } else if (key === "F") {
+        const directory = await promptMinibuffer(ctx, "Filter by directory:", await getDefaultFindPath(), {
+          completer: pathCompletions,
+        });
+        renderAndTrack(await ibufferCommand("filter_directory", directory ?? ""));
+      } else if (key === "/") {
+        renderAndTrack(await ibufferCommand("clear_filters"));
+      } else {
+        renderAndTrack(await runEditorCommand("noop"), `/ ${key} is undefined`);
+      }
+      return;
+    }
+    if (key === "/") {
+      ibufferFilterPrefix = true;
+      renderAndTrack(await runEditorCommand("noop"), "Filter: m (modified), F (directory), / (clear)");
+      return;
+    }
+    if (key === "n" || key === " ") {
+      renderAndTrack(await runEditorCommand("move_next_line"));
+      return;
+    }
+    if (key === "p") {
+      renderAndTrack(await runEditorCommand("move_previous_line"));
+      return;
+    }
+    if (simple[key]) {
+      renderAndTrack(await ibufferCommand(simple[key]));
+      return;
+    }
+    if (key === "D") {
+      try {
+        renderAndTrack(await ibufferCommand("kill_marked"));
+      } catch (error) {
+        const message = error instanceof Error ? error.message : String(error);
+        if (!message.includes("Confirmation required")) {
+          throw error;
+        }
+        if (window.confirm(`${message.replace(" Confirmation required.", "")}\nKill anyway?`)) {
+          renderAndTrack(await ibufferCommand("kill_marked", undefined, true));
+        } else {
+          await renderWithPrefix();
+        }
+      }
+      return;
+    }
+    if (key === "V") {
+      if (window.confirm("Revert marked buffers (or the buffer at point) from their files?")) {
+        renderAndTrack(await ibufferCommand("revert_marked"));
+      }
+      return;
+    }
+  };
+
   const renderError = async (error: unknown): Promise<void> => {
     const fallback = await runEditorCommand("noop");
     const message = error instanceof Error ? error.message : String(error);
     renderAndTrack(fallback, `Error: ${message}`);
   };
 
+  // C-x g greps every file; M-x rgrep also asks for file name wildcards.
+  const grepCommand = async (withWildcards: boolean): Promise<void> => {
+    const directory = await promptMinibuffer(ctx, "Grep in directory:", await getDefaultFindPath(), {
+      completer: pathCompletions,
+    });
+    if (!directory) {
+      await renderWithPrefix();
+      return;
+    }
+    const pattern = await promptMinibuffer(ctx, "Grep (regexp):", lastSearchQuery ?? "", { trim: false });
+    if (!pattern) {
+      await renderWithPrefix();
+      return;
+    }
+    const files = withWildcards ? await promptMinibuffer(ctx, "In files matching wildcard:", "*") : null;
+    if (withWildcards && !files) {
+      await renderWithPrefix();
+      return;
+    }
+
+    grepRunning = true;
+    const unlisten = await listen<GrepProgress>("grep-progress", (event) => {
+      const progress = event.payload;
+      ctx.status.textContent = progress.done
+        ? `Grep: ${progress.matchCount} matches`
+        : `Grep: ${progress.filesScanned} files, ${progress.matchCount} matches (C-g to cancel)`;
+    });
+    try {
+      const snapshot = files ? await rgrep(directory, pattern, files) : await grep(directory, pattern);
+      renderAndTrack(snapshot);
+    } finally {
+      grepRunning = false;
+      unlisten();
+    }
+  };
+
+  const loadThemeCommand = async (): Promise<void> => {
+    const themes = await listThemes();
+    const name = await promptMinibuffer(ctx, "Load theme", "", {
+      completer: async (input: string): Promise<string[]> =>
+        themes.map((theme) => theme.name).filter((name) => name.startsWith(input)),
+      annotate: (name: string): string | undefined => {
+        const theme = themes.find((t) => t.name === name);
+        return theme ? (theme.path ?? "built-in") : undefined;
+      },
+    });
+    if (!name) {
+      await renderWithPrefix();
+      return;
+    }
+    applyTheme(await loadTheme(name));
+    renderAndTrack(await runEditorCommand("noop"), `Theme: ${name}`);
+  };
+
   const insertTextCommand = async (text: string): Promise<void> => {
     if (!text) {
       return;
     }
     clearMark();
-    const snapshot = await runEditorCommand("insert_text", { text });
+    const snapshot = await runEditorCommand("insert_text", withPrefixArg({ text }));
     renderAndTrack(snapshot);
   };
 
   const newlineAndMove = async (): Promise<void> => {
     await syncCursorFromDom();
+    if (currentBufferKind === "grep") {
+      renderAndTrack(await grepGoto());
+      return;
+    }
     await insertTextCommand("\n");
   };
 
-  const openLineKeepCursor = async (): Promise<void> => {
-    await syncCursorFromDom();
-    const originalCursor = ctx.editor.selectionEnd;
-    await runEditorCommand("insert_text", { text: "\n" });
-    const snapshot = await runEditorCommand("set_cursor", { cursor: originalCursor });
-    renderAndTrack(snapshot);
-  };
-
   const setImePreviewMode = (enabled: boolean): void => {
     if (enabled) {
       ctx.editor.classList.add("ime-composing");
@@ -364,16 +912,226 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       }
     }
 
+    if (macroRepeat) {
+      macroRepeat = false;
+      if (key === "e" && !event.ctrlKey && !event.altKey && !event.metaKey) {
+        event.preventDefault();
+        try {
+          renderAndTrack(await kmacroCommand("call"), "Type e to repeat macro");
+          macroRepeat = true;
+        } catch (error) {
+          await renderError(error);
+        }
+        return;
+      }
+    }
+
+    const inPrefixKey = keyState.ctrlXPrefix || keyState.ctrlXSubPrefix !== null || keyState.ctrlCPrefix;
+    if (!inPrefixKey) {
+      if (prefixArg !== null && (key === "Shift" || key === "Control" || key === "Alt" || key === "Meta")) {
+        return;
+      }
+      const isDigit = /^[0-9]$/.test(key);
+      const metaDigit = event.altKey && !event.ctrlKey && (isDigit || key === "-");
+      const typedDigit =
+        prefixArgTyping && !event.ctrlKey && !event.altKey && (isDigit || (key === "-" && Array.isArray(prefixArg)));
+      if (event.ctrlKey && !event.altKey && key === "u") {
+        event.preventDefault();
+        prefixArg = prefixArgTyping && Array.isArray(prefixArg) ? [prefixArg[0] * 4] : [4];
+        prefixArgTyping = true;
+        renderAndTrack(await runEditorCommand("noop"), prefixArgStatus(prefixArg));
+        return;
+      }
+      if (metaDigit || typedDigit) {
+        event.preventDefault();
+        prefixArg = extendPrefixArg(prefixArgTyping ? prefixArg : null, key);
+        prefixArgTyping = true;
+        renderAndTrack(await runEditorCommand("noop"), prefixArgStatus(prefixArg));
+        return;
+      }
+    }
+    pendingPrefixArg = prefixArg;
+    prefixArg = null;
+    prefixArgTyping = false;
+
+    const described = describeKey(event);
+    if (!described && configuredPrefixPending) {
+      // Wait for the real key when only a modifier is pressed after a configured prefix.
+      return;
+    }
+    const continuingConfiguredPrefix = configuredPrefixPending;
+    configuredPrefixPending = false;
+    if (!inPrefixKey && !continuingConfiguredPrefix) {
+      typedKeys = [];
+    }
+    if (described && configuredBindings.length > 0) {
+      typedKeys.push(described);
+      const sequence = typedKeys.join(" ");
+      const binding = configuredBinding(sequence);
+      if (binding || continuingConfiguredPrefix) {
+        event.preventDefault();
+        keyState.ctrlXPrefix = false;
+        keyState.ctrlXSubPrefix = null;
+        keyState.ctrlCPrefix = false;
+        typedKeys = [];
+        try {
+          if (!binding?.command) {
+            renderAndTrack(await runEditorCommand("noop"), `${sequence} is undefined`);
+            return;
+          }
+          const prefixPayload = withPrefixArg({}) as Record<string, unknown>;
+          const spec = await describeCommand(binding.command);
+          const payload = await collectCommandArgs(spec.name, spec.args, prefixPayload);
+          if (!payload) {
+            await renderWithPrefix();
+            return;
+          }
+          clearMark();
+          await syncCursorFromDom();
+          renderAndTrack(await runEditorCommand(spec.name, payload));
+        } catch (error) {
+          await renderError(error);
+        }
+        return;
+      }
+      if (typedKeys.length === 1 && !BUILTIN_PREFIXES.includes(sequence) && isConfiguredPrefix(sequence)) {
+        event.preventDefault();
+        configuredPrefixPending = true;
+        renderAndTrack(await runEditorCommand("noop"), `${sequence}-`);
+        return;
+      }
+    }
+
     if (keyState.ctrlXPrefix) {
       event.preventDefault();
+      // Wait for the real key when only a modifier (e.g. Shift for C-x %) is pressed.
+      if (key === "Shift" || key === "Control" || key === "Alt" || key === "Meta") {
+        return;
+      }
+      try {
+        await handleCtrlXCommand(key, event.ctrlKey);
+      } catch (error) {
+        await renderError(error);
+      }
+      return;
+    }
+
+    if (keyState.ctrlXSubPrefix) {
+      event.preventDefault();
+      if (key === "Shift" || key === "Control" || key === "Alt" || key === "Meta") {
+        return;
+      }
+      const sequence = `${keyState.ctrlXSubPrefix} ${key}`;
+      keyState.ctrlXSubPrefix = null;
+      try {
+        await syncCursorFromDom();
+        if (event.ctrlKey && key === "g") {
+          renderAndTrack(await runEditorCommand("keyboard_quit"));
+        } else if (sequence === "4 c") {
+          renderAndTrack(await runEditorCommand("clone_indirect_buffer_other_window", { scrollLine: currentScrollLine(ctx) }));
+        } else if (sequence === "n n") {
+          const region = regionFromSelection();
+          if (!region) {
+            renderAndTrack(await runEditorCommand("noop"), "The mark is not set now, so there is no region");
+          } else {
+            clearMark();
+            renderAndTrack(await runEditorCommand("narrow_to_region", region));
+          }
+        } else if (sequence === "n p") {
+          renderAndTrack(await runEditorCommand("narrow_to_page"));
+        } else if (sequence === "n w") {
+          renderAndTrack(await runEditorCommand("widen"));
+        } else if (sequence === "C-k i" && event.ctrlKey) {
+          renderAndTrack(await runEditorCommand("kmacro_insert_counter"));
+        } else if (sequence === "C-k c" && event.ctrlKey) {
+          const input = await promptMinibuffer(ctx, "Macro counter value:", "");
+          const counter = Number.parseInt(input ?? "", 10);
+          if (Number.isNaN(counter)) {
+            await renderWithPrefix();
+          } else {
+            renderAndTrack(await kmacroCommand("set_counter", { counter }));
+          }
+        } else if (sequence === "C-k n") {
+          const name = await promptMinibuffer(ctx, "Name for last kbd macro:", "");
+          if (!name) {
+            await renderWithPrefix();
+          } else {
+            renderAndTrack(await kmacroCommand("name", { name }));
+          }
+        } else if (sequence === "C-k x") {
+          const names = await kmacroNames();
+          const name = await promptMinibuffer(ctx, "Execute keyboard macro:", "", {
+            completer: async (input: string): Promise<string[]> => names.filter((n) => n.startsWith(input)),
+          });
+          if (!name) {
+            await renderWithPrefix();
+          } else {
+            renderAndTrack(await kmacroCommand("call", { name }));
+          }
+        } else {
+          renderAndTrack(await runEditorCommand("noop"), `C-x ${sequence} is undefined`);
+        }
+      } catch (error) {
+        await renderError(error);
+      }
+      return;
+    }
+
+    if (keyState.ctrlCPrefix) {
+      event.preventDefault();
+      if (key === "Shift" || key === "Control" || key === "Alt" || key === "Meta") {
+        return;
+      }
+      keyState.ctrlCPrefix = false;
       try {
-        await handleCtrlXCommand(key);
+        if (currentBufferKind === "wdired" && (key === "c" || key === "k")) {
+          await syncCursorFromDom();
+          renderAndTrack(await diredCommand(key === "c" ? "wdired_commit" : "wdired_abort"));
+        } else if (currentMajorMode === "markdown" && event.ctrlKey && (key === "-" || key === "=")) {
+          await syncCursorFromDom();
+          renderAndTrack(await runEditorCommand(key === "-" ? "markdown_promote_heading" : "markdown_demote_heading"));
+        } else {
+          await renderWithPrefix();
+        }
       } catch (error) {
         await renderError(error);
       }
       return;
     }
 
+    const hasCtrlCMap = currentBufferKind === "wdired" || (currentBufferKind === "file" && currentMajorMode === "markdown");
+    if (event.ctrlKey && !event.altKey && key === "c" && hasCtrlCMap) {
+      event.preventDefault();
+      keyState.ctrlCPrefix = true;
+      const snapshot = await runEditorCommand("noop");
+      renderAndTrack(snapshot, "C-c-");
+      return;
+    }
+
+    if (currentBufferKind === "dired" && !event.ctrlKey && !event.altKey && !event.metaKey) {
+      if (key.length === 1 || key === "Enter" || key === "Backspace" || key === "Delete") {
+        event.preventDefault();
+        try {
+          await handleDiredKey(key === "Enter" || key.length !== 1 ? key : event.key);
+        } catch (error) {
+          await renderError(error);
+        }
+        return;
+      }
+    }
+
+    if (currentBufferKind === "ibuffer" && !event.ctrlKey && !event.altKey && !event.metaKey) {
+      if (key.length === 1 || key === "Enter" || key === "Backspace" || key === "Delete") {
+        event.preventDefault();
+        try {
+          await handleIbufferKey(key === "Enter" || key.length !== 1 ? key : event.key);
+        } catch (error) {
+          await renderError(error);
+        }
+        return;
+      }
+    }
+
     const isUndoShortcut = event.ctrlKey && !event.altKey && (
       (key === "/" && !event.shiftKey) || key === "_"
     );
@@ -382,7 +1140,7 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       try {
         clearMark();
         await syncCursorFromDom();
-        const snapshot = await runEditorCommand("undo");
+        const snapshot = await runEditorCommand("undo", withPrefixArg());
         renderAndTrack(snapshot);
       } catch (error) {
         await renderError(error);
@@ -395,7 +1153,7 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       try {
         clearMark();
         await syncCursorFromDom();
-        const snapshot = await runEditorCommand("redo");
+        const snapshot = await runEditorCommand("redo", withPrefixArg());
         renderAndTrack(snapshot);
       } catch (error) {
         await renderError(error);
@@ -408,7 +1166,7 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       try {
         clearMark();
         await syncCursorFromDom();
-        const snapshot = await runEditorCommand("kill_line");
+        const snapshot = await runEditorCommand("kill_line", withPrefixArg());
         renderAndTrack(snapshot);
       } catch (error) {
         await renderError(error);
@@ -434,6 +1192,19 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       return;
     }
 
+    if (!event.ctrlKey && event.altKey && key === ";") {
+      event.preventDefault();
+      try {
+        const region = regionFromSelection();
+        await syncCursorFromDom();
+        clearMark();
+        renderAndTrack(await runEditorCommand("comment_line", region ?? undefined));
+      } catch (error) {
+        await renderError(error);
+      }
+      return;
+    }
+
     if (!event.ctrlKey && event.altKey && key === "w") {
       event.preventDefault();
       try {
@@ -457,7 +1228,7 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       try {
         clearMark();
         await syncCursorFromDom();
-        const snapshot = await runEditorCommand("yank");
+        const snapshot = await runEditorCommand("yank", withPrefixArg());
         renderAndTrack(snapshot);
       } catch (error) {
         await renderError(error);
@@ -513,6 +1284,9 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       event.preventDefault();
       keyState.ctrlXPrefix = false;
       clearMark();
+      if (grepRunning) {
+        await cancelGrep();
+      }
       const snapshot = await runEditorCommand("keyboard_quit");
       renderAndTrack(snapshot);
       return;
@@ -529,17 +1303,6 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       return;
     }
 
-    if (event.ctrlKey && !event.altKey && key === "o") {
-      event.preventDefault();
-      try {
-        clearMark();
-        await openLineKeepCursor();
-      } catch (error) {
-        await renderError(error);
-      }
-      return;
-    }
-
     if (event.ctrlKey && !event.altKey && key === "l") {
       event.preventDefault();
       try {
@@ -555,9 +1318,16 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
     if (event.ctrlKey && !event.altKey && (key === "n" || key === "p")) {
       event.preventDefault();
       try {
-        const direction: -1 | 1 = key === "n" ? 1 : -1;
-        const nextCursor = moveCursorByVisualLine(ctx, direction);
-        const snapshot = await runEditorCommand("set_cursor", { cursor: nextCursor });
+        let snapshot: EditorSnapshot;
+        if (definingMacro || pendingPrefixArg !== null) {
+          // Logical line moves replay correctly and take a count; a recorded absolute cursor would not.
+          await syncCursorFromDom();
+          snapshot = await runEditorCommand(key === "n" ? "move_next_line" : "move_previous_line", withPrefixArg());
+        } else {
+          const direction: -1 | 1 = key === "n" ? 1 : -1;
+          const nextCursor = moveCursorByVisualLine(ctx, direction);
+          snapshot = await runEditorCommand("set_cursor", { cursor: nextCursor + narrowStart });
+        }
         const preserveMarkSelection = markPosition !== null;
         renderAndTrack(snapshot, undefined, preserveMarkSelection);
       } catch (error) {
@@ -571,7 +1341,7 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       try {
         clearMark();
         await syncCursorFromDom();
-        const snapshot = await runEditorCommand("delete_backward_char");
+        const snapshot = await runEditorCommand("delete_backward_char", withPrefixArg());
         renderAndTrack(snapshot);
       } catch (error) {
         await renderError(error);
@@ -594,6 +1364,53 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
       return;
     }
 
+    if (event.altKey && !event.ctrlKey && key === "x") {
+      event.preventDefault();
+      try {
+        const prefixPayload = withPrefixArg({});
+        let candidates: ExtendedCommandCandidate[] = [];
+        const name = await promptMinibuffer(ctx, "M-x", "", {
+          completer: async (input: string): Promise<string[]> => {
+            const frontend = FRONTEND_COMMANDS.filter((command) => command.name.includes(input));
+            candidates = [...(await extendedCommandCandidates(input)), ...frontend];
+            return candidates.map((candidate) => candidate.name);
+          },
+          annotate: (name: string): string | undefined => {
+            const candidate = candidates.find((c) => c.name === name);
+            if (!candidate) {
+              return undefined;
+            }
+            return candidate.keys.length > 0 ? `${candidate.doc} (${candidate.keys.join(", ")})` : candidate.doc;
+          },
+        });
+        if (!name) {
+          await renderWithPrefix();
+          return;
+        }
+        if (name === "load_theme") {
+          await loadThemeCommand();
+          return;
+        }
+        if (name === "rgrep") {
+          await grepCommand(true);
+          return;
+        }
+
+        const spec = (await extendedCommandCandidates(name)).find((c) => c.name === name);
+        const payload = await collectCommandArgs(name, spec?.args ?? [], { ...prefixPayload });
+        if (!payload) {
+          await renderWithPrefix();
+          return;
+        }
+        clearMark();
+        await syncCursorFromDom();
+        renderAndTrack(await executeExtendedCommand(name, payload));
+      } catch (error) {
+        await renderError(error);
+      }
+      return;
+    }
+
     if (isQueryReplaceShortcut(event, key)) {
       event.preventDefault();
       try {
@@ -663,11 +1480,31 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
     if (event.ctrlKey && !event.altKey && key === "n") command = "move_next_line";
     if (event.ctrlKey && !event.altKey && key === "p") command = "move_previous_line";
     if (event.ctrlKey && !event.altKey && key === "d") command = "delete_char";
+    if (event.ctrlKey && !event.altKey && key === "t") command = "transpose_chars";
+    if (event.ctrlKey && !event.altKey && key === "o") command = "open_line";
+    if (event.altKey && !event.ctrlKey && key === "t") command = "transpose_words";
+    if (event.altKey && !event.ctrlKey && key === "u") command = "upcase_word";
+    if (event.altKey && !event.ctrlKey && key === "l") command = "downcase_word";
+    if (event.altKey && !event.ctrlKey && key === "c") command = "capitalize_word";
+    if (event.altKey && !event.ctrlKey && key === "d") command = "kill_word";
+    if (event.altKey && !event.ctrlKey && key === "Backspace") command = "backward_kill_word";
+    if (event.altKey && !event.ctrlKey && key === "\\") command = "delete_horizontal_space";
+    if (event.altKey && !event.ctrlKey && key === " ") command = "just_one_space";
     if (event.altKey && !event.ctrlKey && key === "f") command = "move_forward_word";
     if (event.altKey && !event.ctrlKey && key === "b") command = "move_backward_word";
     if (event.altKey && !event.ctrlKey && key === "<") command = "move_to_buffer_start";
     if (event.altKey && !event.ctrlKey && key === ">") command = "move_to_buffer_end";
     if (!event.ctrlKey && !event.altKey && key === "Backspace") command = "delete_backward_char";
+    if (!event.ctrlKey && !event.altKey && key === "Insert") {
+      event.preventDefault();
+      try {
+        renderAndTrack(await runEditorCommand("toggle_minor_mode", { mode: "overwrite-mode" }));
+      } catch (error) {
+        await renderError(error);
+      }
+      return;
+    }
+    if (!event.ctrlKey && !event.altKey && !event.shiftKey && key === "Tab" && currentBufferKind === "file") command = "indent_line";
 
     if (!command) {
       return;
@@ -677,7 +1514,7 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
     keyState.ctrlXPrefix = false;
     try {
       await syncCursorFromDom();
-      const snapshot = await runEditorCommand(command);
+      const snapshot = await runEditorCommand(command, withPrefixArg());
       const preserveMarkSelection =
         markPosition !== null && (
           command === "move_to_line_start" ||
@@ -737,7 +1574,11 @@ export function bindEditorKeys(ctx: EditorUiContext): void {
     if (inputType === "insertLineBreak" || inputType === "insertParagraph") {
       event.preventDefault();
       try {
-        const snapshot = await runEditorCommand("insert_text", { text: "\n" });
+        if (currentBufferKind === "grep") {
+          await newlineAndMove();
+          return;
+        }
+        const snapshot = await runEditorCommand("insert_text", withPrefixArg({ text: "\n" }));
         renderAndTrack(snapshot);
       } catch (error) {
         await renderError(error);
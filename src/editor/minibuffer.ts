@@ -3,6 +3,8 @@ import type { EditorUiContext } from "./types";
 interface MinibufferOptions {
   trim?: boolean;
   completer?: (value: string) => Promise<string[]>;
+  /** Extra text shown after a candidate, such as a docstring. */
+  annotate?: (candidate: string) => string | undefined;
 }
 
 function moveCursor(input: HTMLTextAreaElement, delta: number): void {
@@ -65,6 +67,7 @@ export function promptMinibuffer(
 ): Promise<string | null> {
   const trim = options.trim ?? true;
   const completer = options.completer;
+  const annotate = options.annotate;
 
   ctx.minibufferPrompt.textContent = prompt;
   ctx.minibufferInput.value = initialValue;
@@ -102,7 +105,9 @@ export function promptMinibuffer(
       ctx.minibufferCandidates.innerHTML = completionCandidates
         .map((candidate, index) => {
           const active = index === completionIndex ? "minibuffer-candidate active" : "minibuffer-candidate";
-          return `<span class="${active}">${escapeHtml(candidate)}</span>`;
+          const annotation = annotate?.(candidate);
+          const suffix = annotation ? `<span class="minibuffer-annotation">${escapeHtml(annotation)}</span>` : "";
+          return `<span class="${active}">${escapeHtml(candidate)}${suffix}</span>`;
         })
         .join("");
       const active = ctx.minibufferCandidates.querySelector<HTMLElement>(".minibuffer-candidate.active");
@@ -1,4 +1,17 @@
-type LanguageId = "markdown" | "plain";
+import type { StyleSpan } from "./commands";
+
+/** Spans last received for a line, valid while the line's text is unchanged. */
+export interface HighlightedLine {
+  text: string;
+  spans: StyleSpan[];
+}
+
+/** Per-buffer span cache, keyed by line number. */
+export interface HighlightCache {
+  bufferId: number;
+  majorMode: string;
+  lines: Map<number, HighlightedLine>;
+}
 
 function escapeHtml(value: string): string {
   return value
@@ -7,101 +20,34 @@ function escapeHtml(value: string): string {
     .replaceAll(">", "&gt;");
 }
 
-function detectLanguage(filePath: string | null): LanguageId {
-  if (!filePath) {
-    return "plain";
-  }
-  const lowered = filePath.toLowerCase();
-  if (lowered.endsWith(".md") || lowered.endsWith(".markdown")) {
-    return "markdown";
-  }
-  return "plain";
-}
-
-function highlightInlineMarkdown(line: string): string {
-  const pattern = /(`[^`\n]*`)|(https?:\/\/[^\s<>\])"]+)|(<[a-zA-Z\/][^>]*>)|(\*\*[^*\n]+\*\*)|(\*[^*\n]+\*)|(\[[^\]\n]+\]\([^)]+\))/g;
+/** Span offsets are character columns, so the line is sliced by code point. */
+function renderLine(line: string, spans: StyleSpan[]): string {
+  const chars = Array.from(line);
   let result = "";
   let last = 0;
-
-  for (const match of line.matchAll(pattern)) {
-    const token = match[0];
-    const index = match.index ?? 0;
-
-    result += escapeHtml(line.slice(last, index));
-
-    if (match[1]) {
-      // inline code
-      result += `<span class="tok-md-inline-code">${escapeHtml(token)}</span>`;
-    } else if (match[2]) {
-      // raw URL
-      result += `<span class="tok-md-link-url">${escapeHtml(token)}</span>`;
-    } else if (match[3]) {
-      // HTML tag
-      result += `<span class="tok-md-html-tag">${escapeHtml(token)}</span>`;
-    } else if (match[4]) {
-      // bold
-      result += `<span class="tok-md-strong">${escapeHtml(token)}</span>`;
-    } else if (match[5]) {
-      // italic
-      result += `<span class="tok-md-em">${escapeHtml(token)}</span>`;
-    } else if (match[6]) {
-      // [text](url) link
-      const linkMatch = token.match(/^\[([^\]]+)\]\(([^)]+)\)$/);
-      if (linkMatch) {
-        result += `<span class="tok-md-link-text">[${escapeHtml(linkMatch[1])}]</span><span class="tok-md-link-url">(${escapeHtml(linkMatch[2])})</span>`;
-      } else {
-        result += `<span class="tok-md-link-text">${escapeHtml(token)}</span>`;
-      }
+  for (const span of spans) {
+    if (span.start < last) {
+      continue;
     }
-
-    last = index + token.length;
+    result += escapeHtml(chars.slice(last, span.start).join(""));
+    result += `<span class="tok-${span.style}">${escapeHtml(chars.slice(span.start, span.end).join(""))}</span>`;
+    last = span.end;
   }
-
-  result += escapeHtml(line.slice(last));
+  result += escapeHtml(chars.slice(last).join(""));
   return result;
 }
 
-function highlightMarkdown(text: string): string {
-  const lines = text.split("\n");
-  let inFence = false;
-
-  return lines
-    .map((line) => {
-      if (/^\s*```/.test(line)) {
-        inFence = !inFence;
-        return `<span class="tok-md-fence">${escapeHtml(line)}</span>`;
-      }
-
-      if (inFence) {
-        return `<span class="tok-md-code-block">${escapeHtml(line)}</span>`;
-      }
-
-      if (/^\s{0,3}#{1,6}\s/.test(line)) {
-        return `<span class="tok-md-heading">${highlightInlineMarkdown(line)}</span>`;
-      }
-
-      if (/^\s*>\s?/.test(line)) {
-        return `<span class="tok-md-quote">${highlightInlineMarkdown(line)}</span>`;
-      }
-
-      const listMatch = line.match(/^(\s*(?:[-*+]|\d+\.)\s+)(.*)$/);
-      if (listMatch) {
-        return `<span class="tok-md-list-marker">${escapeHtml(listMatch[1])}</span>${highlightInlineMarkdown(listMatch[2])}`;
-      }
-
-      if (/^\s*([-*_])(?:\s*\1){2,}\s*$/.test(line)) {
-        return `<span class="tok-md-hr">${escapeHtml(line)}</span>`;
-      }
-
-      return highlightInlineMarkdown(line);
+/**
+ * Renders `text` with the spans the backend tokenizer produced. Lines without
+ * cached spans, or whose text changed since they were tokenized, stay plain
+ * until the next response arrives.
+ */
+export function highlightText(text: string, cache: HighlightCache | null): string {
+  return text
+    .split("\n")
+    .map((line, index) => {
+      const cached = cache?.lines.get(index);
+      return cached && cached.text === line ? renderLine(line, cached.spans) : escapeHtml(line);
     })
     .join("\n");
 }
-
-export function highlightText(text: string, filePath: string | null): string {
-  const language = detectLanguage(filePath);
-  if (language === "markdown") {
-    return highlightMarkdown(text);
-  }
-  return escapeHtml(text);
-}
@@ -1,5 +1,7 @@
 export interface EditorSnapshot {
   text: string;
+  narrowStart: number;
+  narrowed: boolean;
   cursor: number;
   line: number;
   col: number;
@@ -8,11 +10,50 @@ export interface EditorSnapshot {
   encoding: string;
   lineEnding: string;
   filePath: string | null;
+  defaultDirectory: string | null;
+  bufferId: number;
+  bufferName: string;
+  bufferKind: string;
+  majorMode: string;
+  modeName: string;
+  minorModes: MinorModeView[];
+  fillColumn: number;
+  /** Columns a tab character advances to. */
+  tabWidth: number;
+  definingMacro: boolean;
   statusMessage: string | null;
+  layout: WindowLayoutNode;
+  windows: WindowView[];
+}
+
+export interface MinorModeView {
+  name: string;
+  lighter: string;
+}
+
+export type WindowLayoutNode =
+  | { type: "leaf"; window: number }
+  | { type: "split"; direction: "vertical" | "horizontal"; children: WindowLayoutNode[] };
+
+export interface WindowView {
+  id: number;
+  selected: boolean;
+  bufferName: string;
+  bufferKind: string;
+  text: string;
+  narrowStart: number;
+  cursor: number;
+  line: number;
+  col: number;
+  scrollLine: number;
+  modified: boolean;
+  tabWidth: number;
 }
 
 export interface EditorUiContext {
   editor: HTMLTextAreaElement;
+  editorStack: HTMLElement;
+  windowArea: HTMLElement;
   highlight: HTMLElement;
   currentLine: HTMLElement;
   cursorBlock: HTMLElement;
@@ -1,5 +1,6 @@
-import type { EditorSnapshot, EditorUiContext } from "./types";
-import { highlightText } from "./syntax";
+import type { EditorSnapshot, EditorUiContext, WindowLayoutNode, WindowView } from "./types";
+import { highlightLines } from "./commands";
+import { highlightText, type HighlightCache } from "./syntax";
 import { findVisualLineTarget } from "./visual_line";
 
 const FONT_SIZE_KEY = "femto.editor.fontSizePx";
@@ -7,12 +8,19 @@ const DEFAULT_FONT_SIZE = 14;
 const MIN_FONT_SIZE = 10;
 const MAX_FONT_SIZE = 32;
 const RECENTER_STATE = new WeakMap<HTMLTextAreaElement, number>();
+const WINDOW_STATE = new WeakMap<HTMLElement, { key: string; selected: number }>();
+const HIGHLIGHT_STATE = new WeakMap<HTMLElement, { cache: HighlightCache; generation: number }>();
+/** Lines above and below the viewport that are highlighted ahead of scrolling. */
+const HIGHLIGHT_MARGIN = 40;
+const HIGHLIGHT_SCROLL_DELAY_MS = 60;
 
 function buildBaseStatus(snapshot: EditorSnapshot): string {
   const modified = snapshot.modified ? "Modified" : "Saved";
+  const narrow = snapshot.narrowed ? " Narrow" : "";
+  const lighters = snapshot.minorModes.map((mode) => ` ${mode.lighter}`).join("") + (snapshot.definingMacro ? " Def" : "");
   const fileLabel = snapshot.filePath ?? "No File";
   const message = snapshot.statusMessage ? `  |  ${snapshot.statusMessage}` : "";
-  return `L:${snapshot.line} C:${snapshot.col}  |  ${snapshot.chars} chars  |  ${snapshot.encoding} (${snapshot.lineEnding})  |  ${modified}  |  ${fileLabel}${message}`;
+  return `L:${snapshot.line} C:${snapshot.col}  |  ${snapshot.chars} chars  |  ${snapshot.encoding} (${snapshot.lineEnding})  |  ${modified}  |  (${snapshot.modeName}${lighters}${narrow})  |  ${fileLabel}${message}`;
 }
 
 export function renderSnapshot(
@@ -20,20 +28,159 @@ export function renderSnapshot(
   snapshot: EditorSnapshot,
   statusOverride?: string,
 ): void {
+  const selectedChanged = renderWindows(ctx, snapshot);
+  // The textarea and the highlight overlay must expand tabs alike.
+  ctx.editorStack.style.setProperty("--tab-width", String(snapshot.tabWidth));
   if (ctx.editor.value !== snapshot.text) {
     ctx.editor.value = snapshot.text;
   }
-  ctx.editor.selectionStart = snapshot.cursor;
-  ctx.editor.selectionEnd = snapshot.cursor;
+  const cursor = snapshot.cursor - snapshot.narrowStart;
+  ctx.editor.selectionStart = cursor;
+  ctx.editor.selectionEnd = cursor;
+  if (selectedChanged) {
+    const selected = snapshot.windows.find((view) => view.selected);
+    ctx.editor.scrollTop = (selected?.scrollLine ?? 0) * editorLineHeight(ctx.editor);
+  }
   ensureCursorVisible(ctx, snapshot.line);
   syncOverlayScroll(ctx);
-  updateCurrentLine(ctx, snapshot.cursor);
+  updateCurrentLine(ctx, cursor);
   updateCursorBlock(ctx);
-  ctx.highlight.innerHTML = highlightText(snapshot.text, snapshot.filePath);
+  renderHighlight(ctx, snapshot);
 
   ctx.status.textContent = statusOverride ?? buildBaseStatus(snapshot);
 }
 
+/** First visible line of the selected window, sent along with window commands. */
+export function currentScrollLine(ctx: EditorUiContext): number {
+  return Math.floor(ctx.editor.scrollTop / editorLineHeight(ctx.editor));
+}
+
+/**
+ * Paints the overlay with the spans cached so far, then asks the backend for the
+ * visible lines and repaints if the text is still the same when they arrive.
+ */
+function renderHighlight(ctx: EditorUiContext, snapshot: EditorSnapshot): void {
+  let state = HIGHLIGHT_STATE.get(ctx.highlight);
+  if (!state || state.cache.bufferId !== snapshot.bufferId || state.cache.majorMode !== snapshot.majorMode) {
+    const cache: HighlightCache = { bufferId: snapshot.bufferId, majorMode: snapshot.majorMode, lines: new Map() };
+    state = { cache, generation: state?.generation ?? 0 };
+    HIGHLIGHT_STATE.set(ctx.highlight, state);
+  }
+  ctx.highlight.innerHTML = highlightText(snapshot.text, state.cache);
+  requestVisibleHighlight(ctx);
+}
+
+function requestVisibleHighlight(ctx: EditorUiContext): void {
+  const state = HIGHLIGHT_STATE.get(ctx.highlight);
+  if (!state) {
+    return;
+  }
+  const generation = ++state.generation;
+  const { cache } = state;
+  const text = ctx.editor.value;
+  const rows = Math.ceil(ctx.editor.clientHeight / editorLineHeight(ctx.editor));
+  const top = currentScrollLine(ctx);
+  const first = Math.max(0, top - HIGHLIGHT_MARGIN);
+  const end = top + rows + HIGHLIGHT_MARGIN;
+
+  void highlightLines(first, end)
+    .then((response) => {
+      if (
+        state.generation !== generation
+        || state.cache !== cache
+        || ctx.editor.value !== text
+        || response.bufferId !== cache.bufferId
+        || response.majorMode !== cache.majorMode
+      ) {
+        return;
+      }
+      const lines = text.split("\n");
+      for (const entry of response.lines) {
+        cache.lines.set(entry.line, { text: lines[entry.line] ?? "", spans: entry.spans });
+      }
+      ctx.highlight.innerHTML = highlightText(text, cache);
+    })
+    .catch(() => {
+      // Keep showing the spans already cached; the next render asks again.
+    });
+}
+
+function editorLineHeight(editor: HTMLTextAreaElement): number {
+  return Number.parseFloat(getComputedStyle(editor).lineHeight) || 22;
+}
+
+/**
+ * Lays out the window tree. The selected window hosts the live editor stack; the
+ * others are read-only panes. Returns true when the selected window changed.
+ */
+function renderWindows(ctx: EditorUiContext, snapshot: EditorSnapshot): boolean {
+  const selected = snapshot.windows.find((view) => view.selected)?.id ?? 0;
+  const key = JSON.stringify(snapshot.layout);
+  const previous = WINDOW_STATE.get(ctx.windowArea);
+  const views = new Map(snapshot.windows.map((view) => [view.id, view]));
+
+  if (!previous || previous.key !== key || previous.selected !== selected) {
+    const hadFocus = document.activeElement === ctx.editor;
+    ctx.windowArea.replaceChildren(buildWindowNode(ctx, snapshot.layout, views));
+    ctx.windowArea.classList.toggle("multiple-windows", snapshot.windows.length > 1);
+    if (hadFocus) {
+      ctx.editor.focus();
+    }
+    WINDOW_STATE.set(ctx.windowArea, { key, selected });
+  }
+
+  const lineHeight = editorLineHeight(ctx.editor);
+  for (const view of snapshot.windows) {
+    const pane = ctx.windowArea.querySelector<HTMLElement>(`[data-window="${view.id}"]`);
+    if (!pane) {
+      continue;
+    }
+    const modeline = pane.querySelector<HTMLElement>(".window-modeline");
+    if (modeline) {
+      modeline.textContent = `${view.modified ? "**" : "--"} ${view.bufferName}  L${view.line}`;
+    }
+    const text = pane.querySelector<HTMLElement>(".window-text");
+    if (text) {
+      text.style.setProperty("--tab-width", String(view.tabWidth));
+      text.textContent = view.text;
+      text.scrollTop = view.scrollLine * lineHeight;
+    }
+  }
+
+  return previous !== undefined && previous.selected !== selected;
+}
+
+function buildWindowNode(
+  ctx: EditorUiContext,
+  node: WindowLayoutNode,
+  views: Map<number, WindowView>,
+): HTMLElement {
+  if (node.type === "split") {
+    const split = document.createElement("div");
+    split.className = `window-split ${node.direction}`;
+    for (const child of node.children) {
+      split.appendChild(buildWindowNode(ctx, child, views));
+    }
+    return split;
+  }
+
+  const pane = document.createElement("div");
+  pane.className = "window";
+  pane.dataset.window = String(node.window);
+  if (views.get(node.window)?.selected) {
+    pane.classList.add("selected");
+    pane.appendChild(ctx.editorStack);
+  } else {
+    const text = document.createElement("pre");
+    text.className = "window-text";
+    pane.appendChild(text);
+  }
+  const modeline = document.createElement("div");
+  modeline.className = "window-modeline";
+  pane.appendChild(modeline);
+  return pane;
+}
+
 export function initializeEditorView(ctx: EditorUiContext): void {
   const saved = Number.parseInt(window.localStorage.getItem(FONT_SIZE_KEY) ?? "", 10);
   const size = Number.isFinite(saved) ? clampFontSize(saved) : DEFAULT_FONT_SIZE;
@@ -45,7 +192,12 @@ export function initializeEditorView(ctx: EditorUiContext): void {
     updateCursorBlock(ctx);
   };
 
-  ctx.editor.addEventListener("scroll", refreshCursor);
+  let highlightTimer: number | undefined;
+  ctx.editor.addEventListener("scroll", () => {
+    refreshCursor();
+    window.clearTimeout(highlightTimer);
+    highlightTimer = window.setTimeout(() => requestVisibleHighlight(ctx), HIGHLIGHT_SCROLL_DELAY_MS);
+  });
   ctx.editor.addEventListener("click", refreshCursor);
   ctx.editor.addEventListener("mouseup", refreshCursor);
   ctx.editor.addEventListener("keyup", refreshCursor);
@@ -1,8 +1,9 @@
 import "./styles/main.css";
 import { listen } from "@tauri-apps/api/event";
 import { getCurrentWindow } from "@tauri-apps/api/window";
-import { loadAndApplyAppConfig } from "./editor/config";
+import { applyAppConfig, loadAndApplyAppConfig } from "./editor/config";
 import { initializeEditor, openFile } from "./editor/commands";
+import type { ConfigChangedEvent } from "./editor/commands";
 import { bindEditorKeys } from "./editor/keybindings";
 import { initializeEditorView, renderSnapshot } from "./editor/ui";
 
@@ -13,11 +14,13 @@ if (!app) {
 
 app.innerHTML = `
   <main class="layout">
-    <div class="editor-stack">
-      <div id="current-line" class="current-line" aria-hidden="true"></div>
-      <pre id="highlight" class="highlight" aria-hidden="true"></pre>
-      <div id="cursor-block" class="cursor-block" aria-hidden="true"></div>
-      <textarea id="editor" wrap="off" spellcheck="false" autocomplete="off" autocapitalize="off"></textarea>
+    <div id="window-area" class="window-area">
+      <div id="editor-stack" class="editor-stack">
+        <div id="current-line" class="current-line" aria-hidden="true"></div>
+        <pre id="highlight" class="highlight" aria-hidden="true"></pre>
+        <div id="cursor-block" class="cursor-block" aria-hidden="true"></div>
+        <textarea id="editor" wrap="off" spellcheck="false" autocomplete="off" autocapitalize="off"></textarea>
+      </div>
     </div>
     <div id="minibuffer" class="minibuffer hidden">
       <span id="minibuffer-prompt" class="minibuffer-prompt"></span>
@@ -29,6 +32,8 @@ app.innerHTML = `
 `;
 
 const editor = document.querySelector<HTMLTextAreaElement>("#editor");
+const editorStack = document.querySelector<HTMLDivElement>("#editor-stack");
+const windowArea = document.querySelector<HTMLDivElement>("#window-area");
 const highlight = document.querySelector<HTMLPreElement>("#highlight");
 const currentLine = document.querySelector<HTMLDivElement>("#current-line");
 const cursorBlock = document.querySelector<HTMLDivElement>("#cursor-block");
@@ -38,11 +43,11 @@ const minibufferInput = document.querySelector<HTMLTextAreaElement>("#minibuffer
 const minibufferCandidates = document.querySelector<HTMLDivElement>("#minibuffer-candidates");
 const status = document.querySelector<HTMLDivElement>("#status");
 
-if (!editor || !highlight || !currentLine || !cursorBlock || !minibuffer || !minibufferPrompt || !minibufferInput || !minibufferCandidates || !status) {
+if (!editor || !editorStack || !windowArea || !highlight || !currentLine || !cursorBlock || !minibuffer || !minibufferPrompt || !minibufferInput || !minibufferCandidates || !status) {
   throw new Error("Missing editor UI");
 }
 
-const ctx = { editor, highlight, currentLine, cursorBlock, minibuffer, minibufferPrompt, minibufferInput, minibufferCandidates, status };
+const ctx = { editor, editorStack, windowArea, highlight, currentLine, cursorBlock, minibuffer, minibufferPrompt, minibufferInput, minibufferCandidates, status };
 initializeEditorView(ctx);
 bindEditorKeys(ctx);
 
@@ -55,7 +60,10 @@ listen<string>("open-file", (event) => {
     return;
   }
   openFile(event.payload)
-    .then((snapshot) => renderSnapshot(ctx, snapshot))
+    .then((snapshot) => {
+      renderSnapshot(ctx, snapshot);
+      return loadAndApplyAppConfig();
+    })
     .catch((error: unknown) => {
       const message = error instanceof Error ? error.message : String(error);
       status.textContent = `Open file error: ${message}`;
@@ -65,6 +73,24 @@ listen<string>("open-file", (event) => {
   status.textContent = `Event error: ${message}`;
 });
 
+listen<ConfigChangedEvent>("config-changed", (event) => {
+  const { config, error } = event.payload;
+  if (error || !config) {
+    status.textContent = `Config not reloaded: ${error ?? "unknown error"}`;
+    return;
+  }
+  applyAppConfig(config);
+  const changes = [
+    ...(event.payload.themeChanged ? ["theme"] : []),
+    ...(event.payload.keybindingsChanged ? ["keybindings"] : []),
+    ...event.payload.changedSettings,
+  ];
+  status.textContent = changes.length > 0 ? `Config reloaded: ${changes.join(", ")}` : "Config reloaded";
+}).catch((error: unknown) => {
+  const message = error instanceof Error ? error.message : String(error);
+  status.textContent = `Event error: ${message}`;
+});
+
 loadAndApplyAppConfig()
   .catch((error: unknown) => {
     const message = error instanceof Error ? error.message : String(error);
@@ -78,7 +104,11 @@ loadAndApplyAppConfig()
         const path = pendingOpenPath;
         pendingOpenPath = null;
         openFile(path)
-          .then((opened) => renderSnapshot(ctx, opened))
+          .then((opened) => {
+            renderSnapshot(ctx, opened);
+            // Pick up the .femto.yaml of the opened file's project.
+            return loadAndApplyAppConfig();
+          })
           .catch((error: unknown) => {
             const message = error instanceof Error ? error.message : String(error);
             status.textContent = `Open file error: ${message}`;